@@ -0,0 +1,140 @@
+//! Compares the original byte-by-byte bracket scan with the current
+//! tokenizer on a few content shapes.
+//!
+//! Run with and without SIMD acceleration:
+//!
+//! ```text
+//! cargo bench --bench tokenize
+//! cargo bench --bench tokenize --features memchr
+//! ```
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use shortcode_parser::shortcode::Shortcode;
+use shortcode_parser::{TokenSegment, Tokenizer};
+
+const PARAGRAPH: &str = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do \
+eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis \
+nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis aute irure \
+dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla pariatur.\n\n";
+
+/// The scanner as it was before acceleration: every byte is inspected.
+fn byte_loop(content: &str) -> Vec<TokenSegment<'_>> {
+    let bytes = content.as_bytes();
+    let mut segments = vec![];
+    let mut text_start = 0;
+    let mut pos = 0;
+    let total_len = bytes.len();
+
+    while pos < total_len {
+        if bytes[pos] == b'[' {
+            if text_start < pos {
+                segments.push(TokenSegment::Text(&content[text_start..pos]));
+            }
+            let bracket_pos = pos;
+            pos += 1;
+            let tag_start = pos;
+            while pos < total_len && bytes[pos] != b']' {
+                pos += 1;
+            }
+            if pos < total_len {
+                segments.push(TokenSegment::Tag(&bytes[tag_start..pos]));
+                pos += 1;
+                text_start = pos;
+            } else {
+                segments.push(TokenSegment::Text(&content[bracket_pos..total_len]));
+                text_start = total_len;
+                break;
+            }
+        } else {
+            pos += 1;
+        }
+    }
+
+    if text_start < total_len {
+        segments.push(TokenSegment::Text(&content[text_start..]));
+    } else if segments.is_empty() {
+        segments.push(TokenSegment::Text(content));
+    }
+
+    segments
+}
+
+/// Long blog post: many paragraphs, a shortcode every few of them.
+fn blog_post() -> String {
+    let mut post = String::new();
+    for i in 0..200 {
+        post.push_str(PARAGRAPH);
+        if i % 25 == 0 {
+            post.push_str("[gallery ids=\"1,2,3\" columns=\"3\"]\n\n");
+        }
+        if i % 40 == 0 {
+            post.push_str("[note class=\"warning\"]Mind the gap.[/note]\n\n");
+        }
+    }
+    post
+}
+
+/// Plain prose with no shortcodes at all.
+fn plain_prose() -> String {
+    PARAGRAPH.repeat(200)
+}
+
+/// Layout-heavy page built mostly out of shortcodes.
+fn dense_markup() -> String {
+    let mut page = String::new();
+    for i in 0..2000 {
+        page.push_str(&format!(
+            "[row][col width=\"{}\"][button url=\"/p/{i}\"]Go[/button][/col][/row] ",
+            i % 12
+        ));
+    }
+    page
+}
+
+fn corpora() -> Vec<(&'static str, String)> {
+    vec![
+        ("blog_post", blog_post()),
+        ("plain_prose", plain_prose()),
+        ("dense_markup", dense_markup()),
+    ]
+}
+
+fn bench_tokenize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tokenize");
+    for (name, corpus) in corpora() {
+        assert_eq!(byte_loop(&corpus), Tokenizer::new(&corpus).tokenize());
+        group.throughput(Throughput::Bytes(corpus.len() as u64));
+        group.bench_with_input(BenchmarkId::new("byte_loop", name), &corpus, |b, input| {
+            b.iter(|| byte_loop(black_box(input)))
+        });
+        group.bench_with_input(BenchmarkId::new("tokenizer", name), &corpus, |b, input| {
+            b.iter(|| Tokenizer::new(black_box(input)).tokenize())
+        });
+    }
+    group.finish();
+}
+
+fn bench_render(c: &mut Criterion) {
+    let mut sc = Shortcode::new();
+    sc.add("gallery", |_, attrs| {
+        format!(
+            "<div class=\"gallery\">{}</div>",
+            attrs.get("ids").unwrap_or("")
+        )
+    });
+    sc.add("note", |content, _| {
+        format!("<aside>{}</aside>", content.unwrap_or(""))
+    });
+
+    let mut group = c.benchmark_group("render");
+    for (name, corpus) in corpora() {
+        group.throughput(Throughput::Bytes(corpus.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &corpus, |b, input| {
+            b.iter(|| sc.render(black_box(input)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_tokenize, bench_render);
+criterion_main!(benches);
@@ -0,0 +1,191 @@
+//! `#[derive(ShortcodeArgs)]` for `shortcode_parser`.
+//!
+//! Enable the `derive` feature of `shortcode_parser` and use the macro
+//! re-exported there; see its `ShortcodeArgs` trait for the field rules.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Expr, ExprLit, Field, Fields, GenericArgument, Lit,
+    LitStr, Meta, PathArguments, Type,
+};
+
+/// Implements `shortcode_parser::ShortcodeArgs` for a struct with named fields.
+#[proc_macro_derive(ShortcodeArgs, attributes(default, shortcode))]
+pub fn derive_shortcode_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "ShortcodeArgs needs a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "ShortcodeArgs can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut names = Vec::new();
+    let mut inits = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named fields have names");
+        let options = FieldOptions::parse(field)?;
+        let value = read(field, &options);
+        inits.push(quote! { #ident: #value });
+        names.push(options.name);
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::shortcode_parser::ShortcodeArgs for #ident #ty_generics #where_clause {
+            fn from_attrs(
+                attrs: &::shortcode_parser::ShortcodeAttrs,
+            ) -> ::core::result::Result<Self, ::shortcode_parser::AttrError> {
+                attrs.deny_unknown(&[#(#names),*])?;
+                ::core::result::Result::Ok(Self {
+                    #(#inits,)*
+                })
+            }
+        }
+    })
+}
+
+/// What the attributes on a field ask for.
+struct FieldOptions {
+    /// The attribute read into the field: its name, or
+    /// `#[shortcode(rename = "...")]`.
+    name: LitStr,
+    /// `Some(None)` for `#[default]`, `Some(Some(expr))` for
+    /// `#[default(expr)]`. `#[shortcode(default)]` and
+    /// `#[shortcode(default = expr)]` do the same on structs that also
+    /// derive `Default`, which claims `#[default]`.
+    default: Option<Option<Expr>>,
+}
+
+impl FieldOptions {
+    fn parse(field: &Field) -> syn::Result<Self> {
+        let ident = field.ident.as_ref().expect("named fields have names");
+        let mut options = FieldOptions {
+            name: LitStr::new(&ident.to_string(), ident.span()),
+            default: None,
+        };
+        for attr in &field.attrs {
+            if attr.path().is_ident("default") {
+                options.default = Some(match &attr.meta {
+                    Meta::Path(_) => None,
+                    Meta::List(_) => Some(attr.parse_args()?),
+                    Meta::NameValue(_) => {
+                        return Err(syn::Error::new_spanned(
+                            attr,
+                            "expected `#[default]` or `#[default(value)]`",
+                        ))
+                    }
+                });
+            } else if attr.path().is_ident("shortcode") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        options.name = meta.value()?.parse()?;
+                    } else if meta.path.is_ident("default") {
+                        options.default = Some(match meta.value() {
+                            Ok(value) => Some(value.parse()?),
+                            Err(_) => None,
+                        });
+                    } else {
+                        return Err(meta.error("expected `rename = \"...\"` or `default`"));
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The expression reading `field` from `attrs`.
+fn read(field: &Field, options: &FieldOptions) -> TokenStream2 {
+    let ty = &field.ty;
+    let name = &options.name;
+    if let Some(default) = &options.default {
+        let default = match default {
+            // `#[default("text")]` also works for `String` fields.
+            Some(Expr::Lit(ExprLit {
+                lit: Lit::Str(text),
+                ..
+            })) => quote! { ::core::convert::Into::into(#text) },
+            Some(expr) => quote! { #expr },
+            None => quote! { ::core::default::Default::default() },
+        };
+        return match read_bool(ty, name) {
+            Some(present) => quote! {
+                if attrs.has(#name) { #present } else { #default }
+            },
+            None => quote! {
+                attrs.get_parsed::<#ty>(#name)?.unwrap_or_else(|| #default)
+            },
+        };
+    }
+
+    if is_type(ty, "bool") {
+        quote! { attrs.get_bool(#name)? }
+    } else if let Some(present) = read_bool(ty, name) {
+        quote! {
+            if attrs.has(#name) { #present } else { ::core::option::Option::None }
+        }
+    } else if let Some(inner) = generic_arg(ty, "Option") {
+        quote! { attrs.get_parsed::<#inner>(#name)? }
+    } else if let Some(inner) = generic_arg(ty, "Vec") {
+        quote! { attrs.get_list_parsed::<#inner>(#name)? }
+    } else {
+        quote! { attrs.get_required::<#ty>(#name)? }
+    }
+}
+
+/// For a `bool` or `Option<bool>` field, reads a present attribute with
+/// `get_bool`, so a flag is `true` and `yes` or `off` are accepted.
+fn read_bool(ty: &Type, name: &LitStr) -> Option<TokenStream2> {
+    if is_type(ty, "bool") {
+        Some(quote! { attrs.get_bool(#name)? })
+    } else if generic_arg(ty, "Option").is_some_and(|inner| is_type(inner, "bool")) {
+        Some(quote! { ::core::option::Option::Some(attrs.get_bool(#name)?) })
+    } else {
+        None
+    }
+}
+
+/// Whether `ty` is a path ending in `name`, such as `bool`.
+fn is_type(ty: &Type, name: &str) -> bool {
+    matches!(ty, Type::Path(path) if path.qself.is_none() && path.path.is_ident(name))
+}
+
+/// The type argument of `ty` if it is `name<T>`, such as `Option<T>`.
+fn generic_arg<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if path.qself.is_some() || segment.ident != name {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) if args.args.len() == 1 => Some(inner),
+        _ => None,
+    }
+}
@@ -0,0 +1,195 @@
+use shortcode_parser::shortcode::Shortcode;
+use shortcode_parser::{AttrError, CaseMatching, RenderError, ShortcodeArgs, ShortcodeAttrs};
+
+#[derive(Debug, PartialEq, ShortcodeArgs)]
+struct Video {
+    id: String,
+    #[default(640)]
+    width: u32,
+    autoplay: bool,
+    title: Option<String>,
+    #[default("mp4")]
+    format: String,
+    #[default]
+    start: f32,
+    tracks: Vec<u8>,
+    #[shortcode(rename = "data-theme")]
+    theme: Option<String>,
+}
+
+fn parse(attrs: &[(&str, Option<&str>)]) -> Result<Video, AttrError> {
+    Video::from_attrs(&ShortcodeAttrs::new(attrs))
+}
+
+#[test]
+fn test_defaults() {
+    assert_eq!(
+        parse(&[("id", Some("a"))]),
+        Ok(Video {
+            id: "a".to_string(),
+            width: 640,
+            autoplay: false,
+            title: None,
+            format: "mp4".to_string(),
+            start: 0.0,
+            tracks: vec![],
+            theme: None,
+        })
+    );
+}
+
+#[test]
+fn test_all_fields() {
+    let video = parse(&[
+        ("id", Some("a")),
+        ("width", Some("320")),
+        ("autoplay", None),
+        ("title", Some("Intro")),
+        ("format", Some("webm")),
+        ("start", Some("1.5")),
+        ("tracks", Some("1, 2")),
+        ("data-theme", Some("dark")),
+    ])
+    .unwrap();
+    assert_eq!(video.width, 320);
+    assert!(video.autoplay);
+    assert_eq!(video.title.as_deref(), Some("Intro"));
+    assert_eq!(video.format, "webm");
+    assert_eq!(video.start, 1.5);
+    assert_eq!(video.tracks, [1, 2]);
+    assert_eq!(video.theme.as_deref(), Some("dark"));
+}
+
+#[test]
+fn test_errors_name_the_attribute() {
+    assert_eq!(
+        parse(&[]),
+        Err(AttrError::Missing {
+            name: "id".to_string()
+        })
+    );
+    assert_eq!(
+        parse(&[("id", Some("a")), ("theme", Some("dark"))]),
+        Err(AttrError::Unknown {
+            name: "theme".to_string()
+        })
+    );
+    assert_eq!(
+        parse(&[("id", Some("a")), ("width", Some("x"))])
+            .unwrap_err()
+            .name(),
+        "width"
+    );
+    assert_eq!(
+        parse(&[("id", Some("a")), ("tracks", Some("1,300"))])
+            .unwrap_err()
+            .name(),
+        "tracks"
+    );
+    assert_eq!(
+        parse(&[("id", None)]),
+        Err(AttrError::MissingValue {
+            name: "id".to_string()
+        })
+    );
+}
+
+#[test]
+fn test_add_typed() {
+    let mut sc = Shortcode::new();
+    sc.add_typed("video", |content, video: Video| {
+        format!("{}:{}:{}", video.id, video.width, content.unwrap_or(""))
+    });
+    assert_eq!(sc.render("[video id=\"a\"]x[/video]"), "a:640:x");
+    assert_eq!(sc.render("[video]"), "[video]");
+    assert!(matches!(
+        sc.try_render("[video id=\"a\" widht=\"1\"]"),
+        Err(RenderError::InvalidAttribute {
+            error: AttrError::Unknown { .. },
+            ..
+        })
+    ));
+
+    sc.set_case_matching(CaseMatching::Ascii);
+    assert_eq!(sc.render("[VIDEO ID=\"b\" Width=\"2\"]"), "b:2:");
+}
+
+#[derive(ShortcodeArgs)]
+struct Generic<T: std::str::FromStr>
+where
+    T::Err: std::fmt::Display,
+{
+    value: T,
+}
+
+#[test]
+fn test_generic_struct() {
+    let attrs = [("value", Some("7"))];
+    let parsed = Generic::<u64>::from_attrs(&ShortcodeAttrs::new(&attrs)).unwrap();
+    assert_eq!(parsed.value, 7);
+}
+
+#[derive(Default, ShortcodeArgs)]
+struct WithDefault {
+    #[shortcode(default = 3)]
+    count: u8,
+    #[shortcode(default, rename = "label")]
+    name: String,
+}
+
+#[test]
+fn test_alongside_derive_default() {
+    let parsed = WithDefault::from_attrs(&ShortcodeAttrs::new(&[])).unwrap();
+    assert_eq!(parsed.count, 3);
+    assert_eq!(parsed.name, "");
+    assert_eq!(WithDefault::default().count, 0);
+}
+
+#[derive(Debug, PartialEq, ShortcodeArgs)]
+struct Player {
+    #[default(true)]
+    autoplay: bool,
+    muted: Option<bool>,
+    #[default(Some(false))]
+    looped: Option<bool>,
+}
+
+fn player(attrs: &[(&str, Option<&str>)]) -> Result<Player, AttrError> {
+    Player::from_attrs(&ShortcodeAttrs::new(attrs))
+}
+
+#[test]
+fn test_bool_fields_read_flags() {
+    assert_eq!(
+        player(&[]),
+        Ok(Player {
+            autoplay: true,
+            muted: None,
+            looped: Some(false),
+        })
+    );
+    assert_eq!(
+        player(&[("autoplay", Some("no")), ("muted", None), ("looped", None)]),
+        Ok(Player {
+            autoplay: false,
+            muted: Some(true),
+            looped: Some(true),
+        })
+    );
+    assert_eq!(
+        player(&[
+            ("autoplay", None),
+            ("muted", Some("off")),
+            ("looped", Some("yes"))
+        ]),
+        Ok(Player {
+            autoplay: true,
+            muted: Some(false),
+            looped: Some(true),
+        })
+    );
+    assert_eq!(
+        player(&[("muted", Some("maybe"))]).unwrap_err().name(),
+        "muted"
+    );
+}
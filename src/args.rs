@@ -0,0 +1,54 @@
+//! Typed shortcode attributes.
+
+use crate::attrs::{AttrError, ShortcodeAttrs};
+
+/// A type built from a tag's attributes, for handlers registered with
+/// [`Shortcode::add_typed`](crate::shortcode::Shortcode::add_typed).
+///
+/// With the `derive` feature, `#[derive(ShortcodeArgs)]` implements it for
+/// a struct with named fields, reading one attribute per field:
+///
+/// - `bool` fields use [`ShortcodeAttrs::get_bool`], so a flag is `true` and
+///   a missing attribute `false`, or the field's default.
+/// - `Option<T>` fields are `None` when the attribute is missing.
+///   `Option<bool>` reads a present attribute with `get_bool` too.
+/// - `Vec<T>` fields are comma-separated lists, empty when missing.
+/// - Fields marked `#[default]` or `#[default(expr)]` fall back to
+///   `Default::default()` or `expr`. On a struct that also derives
+///   `Default`, write `#[shortcode(default)]` or `#[shortcode(default = expr)]`.
+/// - Any other field is required and parsed with `FromStr`.
+///
+/// The attribute name is the field name, unless given with
+/// `#[shortcode(rename = "data-id")]`. Attributes without a field are
+/// rejected with [`AttrError::Unknown`].
+///
+/// ```rust
+/// # #[cfg(feature = "derive")]
+/// # {
+/// use shortcode_parser::shortcode::Shortcode;
+/// use shortcode_parser::ShortcodeArgs;
+///
+/// #[derive(ShortcodeArgs)]
+/// struct Video {
+///     id: String,
+///     #[default(640)]
+///     width: u32,
+///     autoplay: bool,
+/// }
+///
+/// let mut sc = Shortcode::new();
+/// sc.add_typed("video", |_, video: Video| {
+///     format!("{} {} {}", video.id, video.width, video.autoplay)
+/// });
+/// assert_eq!(sc.render("[video id=\"a\" autoplay]"), "a 640 true");
+/// assert_eq!(
+///     sc.try_render("[video id=\"a\" width=\"wide\"]").unwrap_err().to_string(),
+///     "attribute `width` has invalid value \"wide\": invalid digit found in string \
+///      in [video id=\"a\" width=\"wide\"]"
+/// );
+/// # }
+/// ```
+pub trait ShortcodeArgs: Sized {
+    /// Reads `Self` from the attributes of one tag.
+    fn from_attrs(attrs: &ShortcodeAttrs) -> Result<Self, AttrError>;
+}
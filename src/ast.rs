@@ -0,0 +1,587 @@
+//! Owned parse tree.
+//!
+//! [`Document`] holds the same tree the renderer builds from borrowed
+//! [`Token`]s, but with owned strings, so it can be stored, returned from
+//! functions, cached or sent to other threads independently of the input.
+
+use crate::code::{Code, RenderNode};
+use crate::parser::Parser;
+use crate::renderer::Renderer;
+use crate::syntax::{CaseMatching, Delimiters, Syntax};
+use crate::token::Token;
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// An owned parse tree of a whole input.
+///
+/// Example:
+/// ```rust
+/// use shortcode_parser::{Document, Node, Tag};
+///
+/// let doc = Document::parse("Hi [b]there[/b]");
+/// assert_eq!(
+///     doc.nodes,
+///     vec![
+///         Node::Text("Hi ".to_string()),
+///         Node::Tag(Tag {
+///             name: "b".to_string(),
+///             attrs: vec![],
+///             children: Some(vec![Node::Text("there".to_string())].into()),
+///         }),
+///     ]
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Document {
+    pub nodes: Vec<Node>,
+}
+
+impl Document {
+    /// Parses `content` into an owned tree.
+    pub fn parse(content: &str) -> Self {
+        Self::from_tokens(Parser::new(content))
+    }
+
+    /// Parses `content` into an owned tree, recognizing tags according to `syntax`.
+    pub fn parse_with(content: &str, syntax: &Syntax) -> Self {
+        Self::parse_with_case(content, syntax, CaseMatching::Exact)
+    }
+
+    /// Like [`Document::parse_with`], but pairs opening and closing tags
+    /// whose names are equal under `case`, as in `[Note]hi[/note]`.
+    pub fn parse_with_case(content: &str, syntax: &Syntax, case: CaseMatching) -> Self {
+        Self {
+            nodes: Renderer::with_matching(
+                Parser::with_syntax(content, syntax),
+                syntax.dialect,
+                case,
+            )
+            .into_nodes(),
+        }
+    }
+
+    /// Builds an owned tree from borrowed tokens, pairing opening and
+    /// closing tags the same way rendering does.
+    ///
+    /// ```rust
+    /// use shortcode_parser::{Document, Parser};
+    ///
+    /// let source = String::from("[note]hi[/note]");
+    /// let doc = Document::from_tokens(Parser::new(&source));
+    /// drop(source);
+    /// assert_eq!(doc.nodes.len(), 1);
+    /// ```
+    pub fn from_tokens<'a>(tokens: impl IntoIterator<Item = Token<'a>>) -> Self {
+        Self {
+            nodes: Renderer::new(tokens).into_nodes(),
+        }
+    }
+}
+
+/// A node of an owned parse tree.
+///
+/// Dropping a tree is iterative, but the derived `Clone`, `PartialEq` and
+/// `Debug` recurse once per nesting level, as do `Serialize` and
+/// `Deserialize` with the `serde` feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Node {
+    /// Plain text between tags.
+    Text(String),
+    /// A closing tag without a matching opener, rendered verbatim.
+    CloseTag(String),
+    /// A self-closing or enclosing shortcode tag.
+    Tag(Tag),
+}
+
+/// An owned shortcode tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tag {
+    pub name: String,
+    /// Attribute pairs in source order. Flags have no value.
+    pub attrs: Vec<(String, Option<String>)>,
+    /// Inner nodes of an enclosing tag; `None` for self-closing tags.
+    pub children: Option<Children>,
+}
+
+/// The inner nodes of an enclosing [`Tag`], used as a `Vec<Node>`.
+///
+/// A parsed tag also remembers the name its closing tag was written with,
+/// so `[Note]x[/NOTE]` renders back unchanged when it is not expanded.
+/// That name is not part of the tree: `==` and serde ignore it, and
+/// [`Tag::to_source`] writes the opening name.
+///
+/// Dropping it tears the subtree down iteratively; the derived drop glue
+/// would recurse once per nesting level.
+#[derive(Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Children(
+    Vec<Node>,
+    #[cfg_attr(feature = "serde", serde(skip))] Option<String>,
+);
+
+impl Children {
+    /// The nodes, moved out.
+    pub fn into_vec(mut self) -> Vec<Node> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl PartialEq for Children {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Children {}
+
+impl Deref for Children {
+    type Target = Vec<Node>;
+
+    fn deref(&self) -> &Vec<Node> {
+        &self.0
+    }
+}
+
+impl DerefMut for Children {
+    fn deref_mut(&mut self) -> &mut Vec<Node> {
+        &mut self.0
+    }
+}
+
+impl From<Vec<Node>> for Children {
+    fn from(nodes: Vec<Node>) -> Self {
+        Self(nodes, None)
+    }
+}
+
+impl From<Children> for Vec<Node> {
+    fn from(children: Children) -> Self {
+        children.into_vec()
+    }
+}
+
+impl fmt::Debug for Children {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Drop for Children {
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.0);
+        while let Some(node) = stack.pop() {
+            if let Node::Tag(Tag {
+                children: Some(mut children),
+                ..
+            }) = node
+            {
+                stack.append(&mut children.0);
+            }
+        }
+    }
+}
+
+impl Tag {
+    /// Writes the tag, its content and its closing tag as shortcode source
+    /// for `syntax`.
+    ///
+    /// Values are double-quoted, or single-quoted if they contain a double
+    /// quote. Shortcode syntax has no escapes, so not every tag can be
+    /// written: a value with both kinds of quote and a space, a name with
+    /// spaces or brackets, or text containing the tag's own closing tag
+    /// would read back as a different tree. Such tags are a [`SourceError`].
+    ///
+    /// ```rust
+    /// use shortcode_parser::{Delimiters, Node, Syntax, Tag};
+    ///
+    /// let tag = Tag {
+    ///     name: "note".to_string(),
+    ///     attrs: vec![("title".to_string(), Some("Say \"hi\"".to_string()))],
+    ///     children: Some(vec![Node::Text("x".to_string())].into()),
+    /// };
+    /// assert_eq!(
+    ///     tag.to_source(&Syntax::new()).unwrap(),
+    ///     "[note title='Say \"hi\"']x[/note]"
+    /// );
+    /// let hugo = Syntax {
+    ///     delimiters: Delimiters::HUGO,
+    ///     ..Syntax::new()
+    /// };
+    /// assert_eq!(
+    ///     tag.to_source(&hugo).unwrap(),
+    ///     "{{< note title='Say \"hi\"' >}}x{{< /note >}}"
+    /// );
+    ///
+    /// let closed_early = Tag {
+    ///     children: Some(vec![Node::Text("see [/note] here".to_string())].into()),
+    ///     ..tag
+    /// };
+    /// assert!(closed_early.to_source(&Syntax::new()).is_err());
+    /// ```
+    pub fn to_source(&self, syntax: &Syntax) -> Result<String, SourceError> {
+        let source = self.write_source(&syntax.delimiters);
+        match Document::parse_with(&source, syntax).nodes.as_slice() {
+            [Node::Tag(tag)] if tag == self => Ok(source),
+            _ => Err(SourceError { written: source }),
+        }
+    }
+
+    /// Writes the tag, its content and its closing tag with `delimiters`,
+    /// whether or not the result parses back to it.
+    fn write_source(&self, delimiters: &Delimiters) -> String {
+        let mut out = self.open_source(delimiters);
+        let Some(children) = &self.children else {
+            return out;
+        };
+        // Enclosing tags whose children are being written.
+        let mut stack = vec![(self, children.iter())];
+        while let Some((tag, children)) = stack.last_mut() {
+            match children.next() {
+                Some(Node::Tag(child)) => {
+                    out.push_str(&child.open_source(delimiters));
+                    if let Some(children) = &child.children {
+                        stack.push((child, children.iter()));
+                    }
+                }
+                Some(node) => out.push_str(&node.render_raw(delimiters)),
+                None => {
+                    out.push_str(&Token::CloseTag(&tag.name).render_raw_with(delimiters));
+                    stack.pop();
+                }
+            }
+        }
+        out
+    }
+
+    /// The opening tag, written with `delimiters`.
+    fn open_source(&self, delimiters: &Delimiters) -> String {
+        if self.attrs.is_empty() {
+            return delimiters.wrap(&self.name);
+        }
+        let attrs: Vec<_> = self
+            .attrs
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_deref()))
+            .collect();
+        Token::SelfCloseAttr(&self.name, attrs)
+            .render_raw_with(delimiters)
+            .into_owned()
+    }
+
+    fn from_token(token: &Token, children: Option<Children>) -> Self {
+        Self {
+            name: token.tag_name().unwrap_or_default().to_string(),
+            attrs: token
+                .attrs_slice()
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.map(str::to_string)))
+                .collect(),
+            children,
+        }
+    }
+}
+
+/// A [`Tag`] that shortcode syntax cannot represent, returned by
+/// [`Tag::to_source`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceError {
+    /// The closest source that could be written, which parses to a
+    /// different tree.
+    pub written: String,
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tag cannot be written as shortcode source: `{}`",
+            self.written
+        )
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+impl From<Tag> for Node {
+    fn from(tag: Tag) -> Self {
+        Node::Tag(tag)
+    }
+}
+
+impl From<Code<'_>> for Node {
+    /// Converts with an explicit stack, so deep trees do not grow the native stack.
+    fn from(code: Code<'_>) -> Self {
+        /// An enclosing tag whose children are being converted.
+        struct Pending<'a> {
+            token: Token<'a>,
+            close: Option<&'a str>,
+            children: std::vec::IntoIter<Code<'a>>,
+            nodes: Vec<Node>,
+        }
+
+        let mut stack: Vec<Pending> = Vec::new();
+        let mut next = code;
+        loop {
+            let mut done = match next {
+                Code::Inline(Token::Text(text)) => Some(Node::Text(text.to_string())),
+                Code::Inline(Token::CloseTag(name)) => Some(Node::CloseTag(name.to_string())),
+                Code::Inline(token) => Some(Node::Tag(Tag::from_token(&token, None))),
+                Code::Nested(token, children, close) => {
+                    stack.push(Pending {
+                        token,
+                        close,
+                        children: children.into_iter(),
+                        nodes: Vec::new(),
+                    });
+                    None
+                }
+            };
+
+            // Attach finished nodes to their parent until one has a child
+            // left to descend into.
+            loop {
+                let Some(top) = stack.last_mut() else {
+                    return done.expect("the root is finished last");
+                };
+                top.nodes.extend(done.take());
+                if let Some(child) = top.children.next() {
+                    next = child;
+                    break;
+                }
+                let pending = stack.pop().expect("checked above");
+                done = Some(Node::Tag(Tag::from_token(
+                    &pending.token,
+                    Some(Children(pending.nodes, pending.close.map(str::to_string))),
+                )));
+            }
+        }
+    }
+}
+
+impl RenderNode for Node {
+    fn opening_name(&self) -> Option<&str> {
+        match self {
+            Node::Tag(tag) => Some(&tag.name),
+            Node::Text(_) | Node::CloseTag(_) => None,
+        }
+    }
+
+    fn attrs(&self) -> Cow<'_, [(&str, Option<&str>)]> {
+        match self {
+            Node::Tag(tag) => Cow::Owned(
+                tag.attrs
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_deref()))
+                    .collect(),
+            ),
+            Node::Text(_) | Node::CloseTag(_) => Cow::Borrowed(&[]),
+        }
+    }
+
+    fn children(&self) -> Option<&[Self]> {
+        match self {
+            Node::Tag(tag) => tag.children.as_deref().map(Vec::as_slice),
+            Node::Text(_) | Node::CloseTag(_) => None,
+        }
+    }
+
+    fn closing_name(&self) -> Option<&str> {
+        match self {
+            Node::Tag(tag) => tag.children.as_ref()?.1.as_deref(),
+            Node::Text(_) | Node::CloseTag(_) => None,
+        }
+    }
+
+    fn render_raw(&self, delimiters: &Delimiters) -> Cow<'_, str> {
+        match self {
+            Node::Text(text) => Cow::Borrowed(text),
+            Node::CloseTag(name) => Token::CloseTag(name).render_raw_with(delimiters),
+            Node::Tag(tag) => Cow::Owned(tag.open_source(delimiters)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<Document>();
+    }
+
+    #[test]
+    fn test_parse_outlives_source() {
+        let doc = {
+            let source = String::from("a [x id=\"1\"] [/y]");
+            Document::parse(&source)
+        };
+        assert_eq!(
+            doc.nodes,
+            vec![
+                Node::Text("a ".to_string()),
+                Node::Tag(Tag {
+                    name: "x".to_string(),
+                    attrs: vec![("id".to_string(), Some("1".to_string()))],
+                    children: None,
+                }),
+                Node::Text(" ".to_string()),
+                Node::CloseTag("y".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        let doc = Document::parse("");
+        assert_eq!(doc.nodes, vec![Node::Text(String::new())]);
+    }
+
+    #[test]
+    fn test_from_inline_text() {
+        let node = Node::from(Code::Inline(Token::Text("hello")));
+        assert_eq!(node, Node::Text("hello".to_string()));
+    }
+
+    #[test]
+    fn test_from_inline_close_tag() {
+        let node = Node::from(Code::Inline(Token::CloseTag("note")));
+        assert_eq!(node, Node::CloseTag("note".to_string()));
+    }
+
+    #[test]
+    fn test_from_nested_with_attrs() {
+        let code = Code::Nested(
+            Token::SelfCloseAttr("note", vec![("class", Some("warn")), ("open", None)]),
+            vec![Code::Inline(Token::Text("hi"))],
+            None,
+        );
+        assert_eq!(
+            Node::from(code),
+            Node::Tag(Tag {
+                name: "note".to_string(),
+                attrs: vec![
+                    ("class".to_string(), Some("warn".to_string())),
+                    ("open".to_string(), None),
+                ],
+                children: Some(vec![Node::Text("hi".to_string())].into()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_render_raw_matches_token() {
+        let token = Token::SelfCloseAttr("video", vec![("id", Some("1")), ("autoplay", None)]);
+        let node = Node::from(Code::Inline(Token::SelfCloseAttr(
+            "video",
+            vec![("id", Some("1")), ("autoplay", None)],
+        )));
+        assert_eq!(node.render_raw(&Delimiters::BRACKETS), token.render_raw());
+        assert_eq!(
+            node.render_raw(&Delimiters::HUGO),
+            token.render_raw_with(&Delimiters::HUGO)
+        );
+
+        let token = Token::SelfCloseAttr("quote", vec![("", Some("a b")), ("x", None)]);
+        assert_eq!(token.render_raw(), "[quote=\"a b\" x]");
+        let node = Node::from(Code::Inline(Token::SelfCloseAttr(
+            "quote",
+            vec![("", Some("a b")), ("x", None)],
+        )));
+        assert_eq!(node.render_raw(&Delimiters::BRACKETS), token.render_raw());
+    }
+
+    #[test]
+    fn test_parse_bbcode_list() {
+        let doc = Document::parse_with("[LIST][*]a[*]b[/list]", &Syntax::bbcode());
+        let item = |text: &str| {
+            Node::Tag(Tag {
+                name: "*".to_string(),
+                attrs: vec![],
+                children: Some(vec![Node::Text(text.to_string())].into()),
+            })
+        };
+        assert_eq!(
+            doc.nodes,
+            [Node::Tag(Tag {
+                name: "LIST".to_string(),
+                attrs: vec![],
+                children: Some(vec![item("a"), item("b")].into()),
+            })]
+        );
+    }
+
+    #[test]
+    fn test_move_fields_out_of_tag() {
+        let doc = Document::parse("[note a=\"1\"]hi[/note]");
+        let Some(Node::Tag(tag)) = doc.nodes.into_iter().next() else {
+            panic!("expected a tag");
+        };
+        let Tag {
+            name,
+            attrs,
+            children,
+        } = tag;
+        assert_eq!(name, "note");
+        assert_eq!(attrs, [("a".to_string(), Some("1".to_string()))]);
+        assert_eq!(Vec::from(children.unwrap()), [Node::Text("hi".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_deeply_nested() {
+        let depth = 100_000;
+        let content = format!("{}x{}", "[b]".repeat(depth), "[/b]".repeat(depth));
+        let doc = Document::parse(&content);
+
+        let mut node = &doc.nodes[0];
+        let mut levels = 0;
+        while let Node::Tag(tag) = node {
+            levels += 1;
+            node = &tag.children.as_ref().unwrap()[0];
+        }
+        assert_eq!(levels, depth);
+        assert_eq!(*node, Node::Text("x".to_string()));
+
+        let template = crate::CompiledTemplate::from(doc);
+        let shortcodes = crate::shortcode::Shortcode::new();
+        assert_eq!(
+            template.render(&shortcodes, &crate::Context::new()),
+            content
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let doc = Document::parse("Hi [video id=\"1\" autoplay][b]x[/b][/video][/p]");
+        let json = serde_json::to_value(&doc).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "nodes": [
+                    {"text": "Hi "},
+                    {"tag": {
+                        "name": "video",
+                        "attrs": [["id", "1"], ["autoplay", null]],
+                        "children": [
+                            {"tag": {"name": "b", "attrs": [], "children": [{"text": "x"}]}}
+                        ]
+                    }},
+                    {"close_tag": "p"}
+                ]
+            })
+        );
+        assert_eq!(serde_json::from_value::<Document>(json).unwrap(), doc);
+    }
+}
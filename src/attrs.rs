@@ -1,8 +1,20 @@
+use crate::syntax::CaseMatching;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
 /// Thin wrapper around attribute pairs providing `.get()` lookup without allocation.
 ///
 /// Wraps `&[(&str, Option<&str>)]` and delegates `.iter()` to the slice.
 /// Use `.get("key")` for attribute lookups — it returns `Option<&str>`.
-/// An attribute without a value (e.g., `[flag]`) or a missing attribute both return `None`.
+/// An attribute without a value (e.g., `[flag]`) or a missing attribute both return `None`;
+/// use [`has`](Self::has) or [`is_flag`](Self::is_flag) to tell them apart.
+/// Keys are compared exactly unless the registry sets a [`CaseMatching`].
+///
+/// The typed getters parse values and report malformed ones as an
+/// [`AttrError`] naming the attribute.
 ///
 /// Example:
 /// ```rust
@@ -17,23 +29,606 @@
 /// assert_eq!(sc.render("[greet name=\"Rust\"]"), "Hello, Rust");
 /// ```
 #[derive(Debug, PartialEq, Eq)]
-pub struct ShortcodeAttrs<'a>(&'a [(&'a str, Option<&'a str>)]);
+pub struct ShortcodeAttrs<'a> {
+    attrs: &'a [(&'a str, Option<&'a str>)],
+    case: CaseMatching,
+    /// How many leading attributes the author wrote; the rest are defaults.
+    authored: usize,
+}
 
 impl<'a> ShortcodeAttrs<'a> {
     /// Looks up an attribute by name. Returns `Some(&str)` if present with a value, `None` otherwise.
     pub fn get(&self, name: &str) -> Option<&'a str> {
-        self.0
+        self.attrs
             .iter()
-            .find(|(k, _)| *k == name)
+            .find(|(k, _)| self.case.eq(k, name))
             .and_then(|(_, v)| *v)
     }
 
+    /// Every value given for `name`, in order, as in `[post tag="a" tag="b"]`.
+    /// Flags are skipped.
+    ///
+    /// Repeated attributes reach the handler only under
+    /// [`DuplicateAttrs::CollectAll`].
+    ///
+    /// ```rust
+    /// use shortcode_parser::ShortcodeAttrs;
+    ///
+    /// let attrs = ShortcodeAttrs::new(&[("tag", Some("a")), ("id", Some("1")), ("tag", Some("b"))]);
+    /// assert_eq!(attrs.get("tag"), Some("a"));
+    /// assert_eq!(attrs.get_all("tag"), ["a", "b"]);
+    /// ```
+    pub fn get_all(&self, name: &str) -> Vec<&'a str> {
+        self.attrs
+            .iter()
+            .filter(|(k, _)| self.case.eq(k, name))
+            .filter_map(|(_, v)| *v)
+            .collect()
+    }
+
+    /// The value given with the tag name, as in BBCode's `[url=https://example.com]`.
+    ///
+    /// It is stored as an attribute with an empty name.
+    pub fn value(&self) -> Option<&'a str> {
+        self.get("")
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &(&'a str, Option<&'a str>)> {
-        self.0.iter()
+        self.attrs.iter()
     }
 
     pub fn new(attrs: &'a [(&'a str, Option<&'a str>)]) -> Self {
-        Self(attrs)
+        Self {
+            attrs,
+            case: CaseMatching::Exact,
+            authored: attrs.len(),
+        }
+    }
+
+    /// Compares keys in [`get`](Self::get) with `case`.
+    ///
+    /// ```rust
+    /// use shortcode_parser::{CaseMatching, ShortcodeAttrs};
+    ///
+    /// let attrs = ShortcodeAttrs::new(&[("ID", Some("1"))]).with_case(CaseMatching::Ascii);
+    /// assert_eq!(attrs.get("id"), Some("1"));
+    /// ```
+    pub fn with_case(mut self, case: CaseMatching) -> Self {
+        self.case = case;
+        self
+    }
+
+    /// Marks every attribute after the first `count` as a default.
+    pub(crate) fn authored(mut self, count: usize) -> Self {
+        self.authored = count;
+        self
+    }
+
+    /// Returns `true` if `name` was not written in the tag and its value
+    /// comes from the defaults registered with the handler or its schema.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use shortcode_parser::Defaults;
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add_with_defaults("w", Defaults::new([("width", "640")]), |_, attrs| {
+    ///     let mark = if attrs.is_default("width") { "*" } else { "" };
+    ///     format!("{}{mark}", attrs.get("width").unwrap())
+    /// });
+    /// assert_eq!(sc.render("[w] [w width=\"640\"]"), "640* 640");
+    /// ```
+    pub fn is_default(&self, name: &str) -> bool {
+        self.attrs
+            .iter()
+            .position(|(k, _)| self.case.eq(k, name))
+            .is_some_and(|index| index >= self.authored)
+    }
+
+    /// How keys are compared.
+    pub(crate) fn case(&self) -> CaseMatching {
+        self.case
+    }
+
+    /// The first attribute named `name`: `Some(None)` for a flag.
+    fn entry(&self, name: &str) -> Option<Option<&'a str>> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| self.case.eq(k, name))
+            .map(|(_, v)| *v)
+    }
+
+    /// Returns `true` if the attribute is present, with or without a value.
+    pub fn has(&self, name: &str) -> bool {
+        self.entry(name).is_some()
+    }
+
+    /// Returns `true` if the attribute is present without a value, as in `[video autoplay]`.
+    pub fn is_flag(&self, name: &str) -> bool {
+        self.entry(name) == Some(None)
+    }
+
+    /// The value of `name`, or an error if it is a flag.
+    fn value_of(&self, name: &str) -> Result<Option<&'a str>, AttrError> {
+        match self.entry(name) {
+            None => Ok(None),
+            Some(None) => Err(AttrError::MissingValue {
+                name: name.to_string(),
+            }),
+            Some(value) => Ok(value),
+        }
+    }
+
+    /// Parses the value of `name` with [`FromStr`], ignoring surrounding
+    /// whitespace. Returns `Ok(None)` if the attribute is missing.
+    ///
+    /// ```rust
+    /// use shortcode_parser::ShortcodeAttrs;
+    ///
+    /// let attrs = ShortcodeAttrs::new(&[("width", Some("640")), ("height", Some("tall"))]);
+    /// assert_eq!(attrs.get_parsed::<u32>("width"), Ok(Some(640)));
+    /// assert_eq!(attrs.get_parsed::<u32>("depth"), Ok(None));
+    /// assert_eq!(
+    ///     attrs.get_parsed::<u32>("height").unwrap_err().to_string(),
+    ///     "attribute `height` has invalid value \"tall\": invalid digit found in string"
+    /// );
+    /// ```
+    pub fn get_parsed<T>(&self, name: &str) -> Result<Option<T>, AttrError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.value_of(name)?
+            .map(|value| {
+                value
+                    .trim()
+                    .parse()
+                    .map_err(|err: T::Err| AttrError::invalid(name, value, err))
+            })
+            .transpose()
+    }
+
+    /// Like [`get_parsed`](Self::get_parsed), but a missing attribute is an error.
+    pub fn get_required<T>(&self, name: &str) -> Result<T, AttrError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.get_parsed(name)?.ok_or_else(|| AttrError::Missing {
+            name: name.to_string(),
+        })
+    }
+
+    /// Like [`get_parsed`](Self::get_parsed), with `default` for a missing attribute.
+    pub fn get_or<T>(&self, name: &str, default: T) -> Result<T, AttrError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        Ok(self.get_parsed(name)?.unwrap_or(default))
+    }
+
+    /// Reads a yes/no attribute.
+    ///
+    /// A flag, an empty value, `true`, `yes`, `on` and `1` are true; `false`,
+    /// `no`, `off` and `0` are false, ignoring ASCII case. A missing
+    /// attribute is false.
+    ///
+    /// ```rust
+    /// use shortcode_parser::ShortcodeAttrs;
+    ///
+    /// let attrs = ShortcodeAttrs::new(&[("autoplay", None), ("loop", Some("No"))]);
+    /// assert_eq!(attrs.get_bool("autoplay"), Ok(true));
+    /// assert_eq!(attrs.get_bool("loop"), Ok(false));
+    /// assert_eq!(attrs.get_bool("muted"), Ok(false));
+    /// ```
+    pub fn get_bool(&self, name: &str) -> Result<bool, AttrError> {
+        let Some(value) = self.entry(name) else {
+            return Ok(false);
+        };
+        let value = value.unwrap_or_default();
+        parse_bool(value).ok_or_else(|| AttrError::invalid(name, value, "expected true or false"))
+    }
+
+    /// Splits the value of `name` on commas, trimming each item and
+    /// skipping empty ones. A missing attribute or a flag gives an empty list.
+    ///
+    /// ```rust
+    /// use shortcode_parser::ShortcodeAttrs;
+    ///
+    /// let attrs = ShortcodeAttrs::new(&[("ids", Some("1, 2,,3"))]);
+    /// assert_eq!(attrs.get_list("ids"), ["1", "2", "3"]);
+    /// assert_eq!(attrs.get_list_parsed::<u32>("ids"), Ok(vec![1, 2, 3]));
+    /// ```
+    pub fn get_list(&self, name: &str) -> Vec<&'a str> {
+        self.entry(name)
+            .flatten()
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Like [`get_list`](Self::get_list), parsing each item with [`FromStr`].
+    pub fn get_list_parsed<T>(&self, name: &str) -> Result<Vec<T>, AttrError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.get_list(name)
+            .into_iter()
+            .map(|item| {
+                item.parse()
+                    .map_err(|err: T::Err| AttrError::invalid(name, item, err))
+            })
+            .collect()
+    }
+
+    /// Reads a duration such as `90` (seconds), `1.5s`, `250ms`, `2m` or
+    /// `1h30m`. Units are `ms`, `s`, `m`, `h` and `d`.
+    ///
+    /// ```rust
+    /// use shortcode_parser::ShortcodeAttrs;
+    /// use std::time::Duration;
+    ///
+    /// let attrs = ShortcodeAttrs::new(&[("delay", Some("1m30s")), ("start", Some("soon"))]);
+    /// assert_eq!(attrs.get_duration("delay"), Ok(Some(Duration::from_secs(90))));
+    /// assert!(attrs.get_duration("start").is_err());
+    /// ```
+    pub fn get_duration(&self, name: &str) -> Result<Option<Duration>, AttrError> {
+        self.value_of(name)?
+            .map(|value| parse_duration(value).map_err(|err| AttrError::invalid(name, value, err)))
+            .transpose()
+    }
+
+    /// Reads a size in bytes such as `512`, `10KB` or `1.5MiB`.
+    ///
+    /// `k`, `M`, `G` and `T` are powers of 1000 and `Ki`, `Mi`, `Gi` and
+    /// `Ti` powers of 1024. Units ignore case and may end in `B`.
+    ///
+    /// ```rust
+    /// use shortcode_parser::ShortcodeAttrs;
+    ///
+    /// let attrs = ShortcodeAttrs::new(&[("max", Some("2 KiB")), ("min", Some("1.5kb"))]);
+    /// assert_eq!(attrs.get_size("max"), Ok(Some(2048)));
+    /// assert_eq!(attrs.get_size("min"), Ok(Some(1500)));
+    /// ```
+    pub fn get_size(&self, name: &str) -> Result<Option<u64>, AttrError> {
+        self.value_of(name)?
+            .map(|value| parse_size(value).map_err(|err| AttrError::invalid(name, value, err)))
+            .transpose()
+    }
+
+    /// Parses the value of `name` as JSON, for structured values such as
+    /// `data='[1,2,3]'`. Returns `Ok(None)` if the attribute is missing.
+    ///
+    /// ```rust
+    /// use shortcode_parser::ShortcodeAttrs;
+    /// use std::collections::HashMap;
+    ///
+    /// let attrs = ShortcodeAttrs::new(&[
+    ///     ("data", Some("[1,2,3]")),
+    ///     ("options", Some("{\"stacked\":true}")),
+    /// ]);
+    /// assert_eq!(attrs.get_json::<Vec<u32>>("data"), Ok(Some(vec![1, 2, 3])));
+    /// let options: HashMap<String, bool> = attrs.get_json("options").unwrap().unwrap();
+    /// assert!(options["stacked"]);
+    /// assert!(attrs.get_json::<Vec<u32>>("options").is_err());
+    /// ```
+    #[cfg(feature = "serde_json")]
+    pub fn get_json<T>(&self, name: &str) -> Result<Option<T>, AttrError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.value_of(name)?
+            .map(|value| {
+                serde_json::from_str(value).map_err(|err| AttrError::invalid(name, value, err))
+            })
+            .transpose()
+    }
+
+    /// Deserializes the attributes into `T`, such as a struct deriving
+    /// `serde::Deserialize`.
+    ///
+    /// Attributes are read as a map from name to value. Numbers, bools and
+    /// unit enum variants are parsed from the value, `Option` fields are
+    /// `None` when the attribute is missing, and sequences split values on
+    /// commas like [`get_list`](Self::get_list). Struct fields match names
+    /// under the registry's [`CaseMatching`]. A flag is `true` for a bool
+    /// and an error for other types.
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use shortcode_parser::ShortcodeAttrs;
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct Video {
+    ///     id: u32,
+    ///     autoplay: bool,
+    ///     width: Option<u32>,
+    /// }
+    ///
+    /// let attrs = ShortcodeAttrs::new(&[("id", Some("7")), ("autoplay", None)]);
+    /// let video: Video = attrs.deserialize().unwrap();
+    /// assert_eq!((video.id, video.autoplay, video.width), (7, true, None));
+    ///
+    /// let attrs = ShortcodeAttrs::new(&[("id", Some("seven"))]);
+    /// assert_eq!(
+    ///     attrs.deserialize::<Video>().unwrap_err().to_string(),
+    ///     "attribute `id` has invalid value \"seven\": invalid digit found in string"
+    /// );
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn deserialize<T>(&self) -> Result<T, AttrError>
+    where
+        T: serde::Deserialize<'a>,
+    {
+        crate::de::from_attrs(self)
+    }
+
+    /// Fails with [`AttrError::Unknown`] for the first attribute whose name
+    /// is not in `known`.
+    ///
+    /// ```rust
+    /// use shortcode_parser::{AttrError, ShortcodeAttrs};
+    ///
+    /// let attrs = ShortcodeAttrs::new(&[("id", Some("1")), ("widht", Some("2"))]);
+    /// assert_eq!(
+    ///     attrs.deny_unknown(&["id", "width"]),
+    ///     Err(AttrError::Unknown { name: "widht".to_string() })
+    /// );
+    /// ```
+    pub fn deny_unknown(&self, known: &[&str]) -> Result<(), AttrError> {
+        match self
+            .attrs
+            .iter()
+            .find(|(k, _)| !known.iter().any(|name| self.case.eq(k, name)))
+        {
+            Some((name, _)) => Err(AttrError::Unknown {
+                name: name.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Serializes as a list of `[name, value]` pairs in source order, like
+/// [`Tag::attrs`](crate::Tag::attrs); flags have a `null` value.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ShortcodeAttrs<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.attrs)
+    }
+}
+
+/// What a registry does with an attribute given more than once, as in
+/// `[video id="1" id="2"]`.
+///
+/// Set with [`Shortcode::set_duplicate_attrs`](crate::shortcode::Shortcode::set_duplicate_attrs).
+/// Whatever the policy, [`Shortcode::validate`](crate::shortcode::Shortcode::validate)
+/// reports each repeat.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateAttrs {
+    /// Every occurrence reaches the handler: [`ShortcodeAttrs::get`]
+    /// returns the first and [`ShortcodeAttrs::get_all`] all of them.
+    #[default]
+    CollectAll,
+    /// Only the first occurrence reaches the handler.
+    FirstWins,
+    /// Only the last occurrence reaches the handler.
+    LastWins,
+    /// The render fails with [`AttrError::Duplicate`].
+    Error,
+}
+
+impl DuplicateAttrs {
+    /// Applies the policy to `attrs`, keeping the position of each
+    /// surviving attribute.
+    pub(crate) fn apply(
+        self,
+        attrs: &mut Cow<'_, [(&str, Option<&str>)]>,
+        case: CaseMatching,
+    ) -> Result<(), AttrError> {
+        if self == DuplicateAttrs::CollectAll {
+            return Ok(());
+        }
+        let repeated = repeated(attrs, case);
+        if repeated.is_empty() {
+            return Ok(());
+        }
+        let mut keep = vec![true; attrs.len()];
+        match self {
+            DuplicateAttrs::CollectAll => return Ok(()),
+            DuplicateAttrs::Error => {
+                return Err(AttrError::Duplicate {
+                    name: attrs[repeated[0]].0.to_string(),
+                })
+            }
+            DuplicateAttrs::FirstWins => {
+                for index in repeated {
+                    keep[index] = false;
+                }
+            }
+            DuplicateAttrs::LastWins => {
+                // Drop every occurrence followed by another one.
+                let mut seen = HashSet::new();
+                for (index, (name, _)) in attrs.iter().enumerate().rev() {
+                    keep[index] = seen.insert(case.fold(name));
+                }
+            }
+        }
+        let mut keep = keep.into_iter();
+        attrs.to_mut().retain(|_| keep.next().unwrap_or(true));
+        Ok(())
+    }
+}
+
+/// Reads a yes/no value as [`ShortcodeAttrs::get_bool`] does.
+pub(crate) fn parse_bool(value: &str) -> Option<bool> {
+    let value = value.trim();
+    let is = |words: &[&str]| words.iter().any(|word| value.eq_ignore_ascii_case(word));
+    if is(&["", "true", "yes", "on", "1"]) {
+        Some(true)
+    } else if is(&["false", "no", "off", "0"]) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Indices of the attributes whose name was already given earlier.
+pub(crate) fn repeated(attrs: &[(&str, Option<&str>)], case: CaseMatching) -> Vec<usize> {
+    let mut seen = HashSet::new();
+    (0..attrs.len())
+        .filter(|&i| !seen.insert(case.fold(attrs[i].0)))
+        .collect()
+}
+
+/// An attribute that is missing, unexpected, or could not be read as the
+/// requested type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum AttrError {
+    /// A required attribute is missing.
+    Missing { name: String },
+    /// The attribute is not one the handler accepts.
+    Unknown { name: String },
+    /// The attribute is a flag, but a value is needed.
+    MissingValue { name: String },
+    /// The attribute is given more than once; see [`DuplicateAttrs::Error`].
+    Duplicate { name: String },
+    /// The value could not be parsed.
+    Invalid {
+        name: String,
+        value: String,
+        reason: String,
+    },
+    /// Any other problem, such as a type that
+    /// [`ShortcodeAttrs::deserialize`] cannot build from attributes.
+    Other { message: String },
+}
+
+impl AttrError {
+    pub(crate) fn invalid(name: &str, value: &str, reason: impl fmt::Display) -> Self {
+        AttrError::Invalid {
+            name: name.to_string(),
+            value: value.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// The name of the attribute, or `""` for [`AttrError::Other`].
+    pub fn name(&self) -> &str {
+        match self {
+            AttrError::Missing { name }
+            | AttrError::Unknown { name }
+            | AttrError::MissingValue { name }
+            | AttrError::Duplicate { name }
+            | AttrError::Invalid { name, .. } => name,
+            AttrError::Other { .. } => "",
+        }
+    }
+}
+
+impl fmt::Display for AttrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttrError::Missing { name } => write!(f, "missing required attribute `{name}`"),
+            AttrError::Unknown { name } => write!(f, "unknown attribute `{name}`"),
+            AttrError::MissingValue { name } => write!(f, "attribute `{name}` needs a value"),
+            AttrError::Duplicate { name } => {
+                write!(f, "attribute `{name}` is given more than once")
+            }
+            AttrError::Invalid {
+                name,
+                value,
+                reason,
+            } => write!(
+                f,
+                "attribute `{name}` has invalid value {value:?}: {reason}"
+            ),
+            AttrError::Other { message } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for AttrError {}
+
+/// Splits `text` into a leading decimal number and the rest.
+fn split_number(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+fn parse_duration(value: &str) -> Result<Duration, &'static str> {
+    const EXPECTED: &str = "expected a duration such as 90, 1.5s, 250ms or 1h30m";
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(EXPECTED);
+    }
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|_| EXPECTED);
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let (number, after) = split_number(rest);
+        let unit_len = after
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(after.len());
+        let (unit, after) = after.split_at(unit_len);
+        let scale = match unit {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            "d" => 86400.0,
+            _ => return Err(EXPECTED),
+        };
+        let amount: f64 = number.parse().map_err(|_| EXPECTED)?;
+        let part = Duration::try_from_secs_f64(amount * scale).map_err(|_| EXPECTED)?;
+        total = total.checked_add(part).ok_or(EXPECTED)?;
+        rest = after;
+    }
+    Ok(total)
+}
+
+fn parse_size(value: &str) -> Result<u64, &'static str> {
+    const EXPECTED: &str = "expected a size such as 512, 10KB or 1.5MiB";
+    let (number, unit) = split_number(value.trim());
+    let unit = unit.trim_start().to_ascii_lowercase();
+    let unit = unit.strip_suffix('b').unwrap_or(&unit);
+    let multiplier: u64 = match unit {
+        "" => 1,
+        "k" => 1000,
+        "m" => 1000u64.pow(2),
+        "g" => 1000u64.pow(3),
+        "t" => 1000u64.pow(4),
+        "ki" => 1 << 10,
+        "mi" => 1 << 20,
+        "gi" => 1 << 30,
+        "ti" => 1 << 40,
+        _ => return Err(EXPECTED),
+    };
+
+    if let Ok(whole) = number.parse::<u64>() {
+        return whole.checked_mul(multiplier).ok_or(EXPECTED);
+    }
+    let amount: f64 = number.parse().map_err(|_| EXPECTED)?;
+    let bytes = (amount * multiplier as f64).round();
+    // `u64::MAX as f64` rounds up to 2^64, which is out of range.
+    if bytes.is_finite() && bytes < u64::MAX as f64 {
+        Ok(bytes as u64)
+    } else {
+        Err(EXPECTED)
     }
 }
 
@@ -81,12 +676,99 @@ mod tests {
         assert_eq!(attrs.get(""), Some("value"));
     }
 
+    #[test]
+    fn test_value() {
+        let attrs = ShortcodeAttrs::new(&[("", Some("red")), ("x", Some("1"))]);
+        assert_eq!(attrs.value(), Some("red"));
+        assert_eq!(ShortcodeAttrs::new(&[("x", Some("1"))]).value(), None);
+    }
+
     #[test]
     fn test_get_duplicate_keys() {
         let attrs = ShortcodeAttrs::new(&[("id", Some("1")), ("id", Some("2"))]);
         assert_eq!(attrs.get("id"), Some("1"));
     }
 
+    #[test]
+    fn test_get_all() {
+        let pairs = [("Tag", Some("a")), ("tag", None), ("TAG", Some("b"))];
+        assert_eq!(
+            ShortcodeAttrs::new(&pairs).get_all("tag"),
+            Vec::<&str>::new()
+        );
+        let attrs = ShortcodeAttrs::new(&pairs).with_case(CaseMatching::Ascii);
+        assert_eq!(attrs.get_all("tag"), ["a", "b"]);
+    }
+
+    #[test]
+    fn test_duplicate_policies() {
+        let pairs = [
+            ("id", Some("1")),
+            ("x", None),
+            ("ID", Some("2")),
+            ("id", Some("3")),
+        ];
+        let apply = |policy: DuplicateAttrs, case| {
+            let mut attrs = Cow::Borrowed(&pairs[..]);
+            policy.apply(&mut attrs, case).map(|()| attrs.into_owned())
+        };
+        assert_eq!(repeated(&pairs, CaseMatching::Exact), [3]);
+        assert_eq!(repeated(&pairs, CaseMatching::Ascii), [2, 3]);
+        assert_eq!(
+            apply(DuplicateAttrs::CollectAll, CaseMatching::Ascii),
+            Ok(pairs.to_vec())
+        );
+        assert_eq!(
+            apply(DuplicateAttrs::FirstWins, CaseMatching::Ascii),
+            Ok(vec![("id", Some("1")), ("x", None)])
+        );
+        assert_eq!(
+            apply(DuplicateAttrs::LastWins, CaseMatching::Ascii),
+            Ok(vec![("x", None), ("id", Some("3"))])
+        );
+        assert_eq!(
+            apply(DuplicateAttrs::LastWins, CaseMatching::Exact),
+            Ok(vec![("x", None), ("ID", Some("2")), ("id", Some("3"))])
+        );
+        assert_eq!(
+            apply(DuplicateAttrs::Error, CaseMatching::Ascii),
+            Err(AttrError::Duplicate {
+                name: "ID".to_string()
+            })
+        );
+        assert_eq!(
+            AttrError::Duplicate {
+                name: "id".to_string()
+            }
+            .to_string(),
+            "attribute `id` is given more than once"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_policies_many_attrs() {
+        // Each attribute would otherwise be compared with every earlier one.
+        let names: Vec<String> = (0..40_000).map(|i| format!("f{i}")).collect();
+        let mut pairs: Vec<(&str, Option<&str>)> =
+            names.iter().map(|name| (name.as_str(), None)).collect();
+        pairs.push(("F0", Some("last")));
+        for policy in [
+            DuplicateAttrs::CollectAll,
+            DuplicateAttrs::FirstWins,
+            DuplicateAttrs::LastWins,
+        ] {
+            let mut attrs = Cow::Borrowed(&pairs[..]);
+            policy.apply(&mut attrs, CaseMatching::Ascii).unwrap();
+            let expected = if policy == DuplicateAttrs::CollectAll {
+                40_001
+            } else {
+                40_000
+            };
+            assert_eq!(attrs.len(), expected);
+        }
+        assert_eq!(repeated(&pairs, CaseMatching::Ascii), [40_000]);
+    }
+
     #[test]
     fn test_get_case_sensitive() {
         let attrs = ShortcodeAttrs::new(&[("ID", Some("1"))]);
@@ -94,6 +776,35 @@ mod tests {
         assert_eq!(attrs.get("ID"), Some("1"));
     }
 
+    #[test]
+    fn test_is_default() {
+        let pairs = [("id", Some("1")), ("ID", Some("2")), ("width", Some("640"))];
+        let attrs = ShortcodeAttrs::new(&pairs).authored(2);
+        assert!(!attrs.is_default("id"));
+        assert!(!attrs.is_default("ID"));
+        assert!(attrs.is_default("width"));
+        assert!(!attrs.is_default("height"));
+        assert!(!ShortcodeAttrs::new(&pairs).is_default("width"));
+
+        let attrs = ShortcodeAttrs::new(&pairs[1..])
+            .with_case(CaseMatching::Ascii)
+            .authored(1);
+        assert!(!attrs.is_default("id"));
+        assert!(attrs.is_default("WIDTH"));
+    }
+
+    #[test]
+    fn test_get_with_case_matching() {
+        let pairs = [("Größe", Some("1")), ("ID", Some("2"))];
+        let ascii = ShortcodeAttrs::new(&pairs).with_case(CaseMatching::Ascii);
+        assert_eq!(ascii.get("id"), Some("2"));
+        assert_eq!(ascii.get("grösse"), None);
+        assert_eq!(ascii.get("gröSSe"), None);
+        let unicode = ShortcodeAttrs::new(&pairs).with_case(CaseMatching::Unicode);
+        assert_eq!(unicode.get("GRÖßE"), Some("1"));
+        assert_eq!(unicode.get("iD"), Some("2"));
+    }
+
     #[test]
     fn test_iter_single_flag() {
         let attrs = ShortcodeAttrs::new(&[("autoplay", None)]);
@@ -133,4 +844,116 @@ mod tests {
         let attrs = ShortcodeAttrs::new(&[("src", Some("a&b"))]);
         assert_eq!(attrs.get("src"), Some("a&b"));
     }
+
+    #[test]
+    fn test_has_and_is_flag() {
+        let attrs = ShortcodeAttrs::new(&[("autoplay", None), ("id", Some(""))]);
+        assert!(attrs.has("autoplay") && attrs.is_flag("autoplay"));
+        assert!(attrs.has("id") && !attrs.is_flag("id"));
+        assert!(!attrs.has("loop") && !attrs.is_flag("loop"));
+    }
+
+    #[test]
+    fn test_get_parsed() {
+        let attrs = ShortcodeAttrs::new(&[
+            ("width", Some(" 640 ")),
+            ("ratio", Some("1.5")),
+            ("flag", None),
+            ("bad", Some("x")),
+        ]);
+        assert_eq!(attrs.get_parsed::<u32>("width"), Ok(Some(640)));
+        assert_eq!(attrs.get_parsed::<f64>("ratio"), Ok(Some(1.5)));
+        assert_eq!(attrs.get_parsed::<u32>("missing"), Ok(None));
+        assert_eq!(
+            attrs.get_parsed::<u32>("flag"),
+            Err(AttrError::MissingValue {
+                name: "flag".to_string()
+            })
+        );
+        let err = attrs.get_parsed::<u8>("bad").unwrap_err();
+        assert_eq!(err.name(), "bad");
+        assert!(matches!(err, AttrError::Invalid { ref value, .. } if value == "x"));
+
+        assert_eq!(attrs.get_or("height", 360u32), Ok(360));
+        assert_eq!(attrs.get_or("width", 360u32), Ok(640));
+        assert!(attrs.get_or("bad", 0u32).is_err());
+    }
+
+    #[test]
+    fn test_get_required_and_deny_unknown() {
+        let attrs = ShortcodeAttrs::new(&[("ID", Some("7")), ("x", None)]);
+        assert_eq!(
+            attrs.get_required::<u32>("id"),
+            Err(AttrError::Missing {
+                name: "id".to_string()
+            })
+        );
+        assert_eq!(attrs.deny_unknown(&["ID", "x"]), Ok(()));
+        assert_eq!(
+            attrs.deny_unknown(&["id", "x"]).unwrap_err().to_string(),
+            "unknown attribute `ID`"
+        );
+
+        let attrs = attrs.with_case(CaseMatching::Ascii);
+        assert_eq!(attrs.get_required::<u32>("id"), Ok(7));
+        assert_eq!(attrs.deny_unknown(&["id", "x"]), Ok(()));
+    }
+
+    #[test]
+    fn test_get_bool() {
+        let attrs = ShortcodeAttrs::new(&[
+            ("a", None),
+            ("b", Some("YES")),
+            ("c", Some("0")),
+            ("d", Some("")),
+            ("e", Some("maybe")),
+        ]);
+        assert_eq!(attrs.get_bool("a"), Ok(true));
+        assert_eq!(attrs.get_bool("b"), Ok(true));
+        assert_eq!(attrs.get_bool("c"), Ok(false));
+        assert_eq!(attrs.get_bool("d"), Ok(true));
+        assert_eq!(attrs.get_bool("missing"), Ok(false));
+        assert_eq!(
+            attrs.get_bool("e").unwrap_err().to_string(),
+            "attribute `e` has invalid value \"maybe\": expected true or false"
+        );
+    }
+
+    #[test]
+    fn test_get_list() {
+        let attrs = ShortcodeAttrs::new(&[("ids", Some("1,x")), ("tags", Some(" ")), ("f", None)]);
+        assert_eq!(attrs.get_list("ids"), ["1", "x"]);
+        assert!(attrs.get_list("tags").is_empty());
+        assert!(attrs.get_list("f").is_empty());
+        assert!(attrs.get_list("missing").is_empty());
+        let err = attrs.get_list_parsed::<u32>("ids").unwrap_err();
+        assert!(
+            matches!(err, AttrError::Invalid { ref name, ref value, .. } if name == "ids" && value == "x")
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(172_800)));
+        for bad in ["", "s", "1x", "-1s", "1 s", "1e400"] {
+            assert!(parse_duration(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10KB"), Ok(10_000));
+        assert_eq!(parse_size("10 kib"), Ok(10_240));
+        assert_eq!(parse_size("1.5M"), Ok(1_500_000));
+        assert_eq!(parse_size("1GiB"), Ok(1 << 30));
+        assert_eq!(parse_size("3b"), Ok(3));
+        for bad in ["", "KB", "1XB", "-1", "20000000TB", "1e3"] {
+            assert!(parse_size(bad).is_err(), "{bad:?}");
+        }
+    }
 }
@@ -0,0 +1,257 @@
+//! Handlers for common BBCode tags.
+//!
+//! [`registry`] returns a registry set up for untrusted forum posts: it
+//! recognizes [`Syntax::bbcode`], escapes all text outside tags
+//! ([`OutputMode::Html`]) and renders these tags:
+//!
+//! | Tag | HTML |
+//! |-----|------|
+//! | `[b]`, `[i]`, `[u]`, `[s]` | `<strong>`, `<em>`, `<u>`, `<s>` |
+//! | `[url]https://x[/url]`, `[url=https://x]text[/url]` | `<a href="https://x">` |
+//! | `[img]https://x/a.png[/img]` | `<img src="https://x/a.png" alt="">` |
+//! | `[quote]`, `[quote="name"]` | `<blockquote>`, with a `<cite>` for the name |
+//! | `[code]`, `[code=rust]` | `<pre><code>`, with class `language-rust` |
+//! | `[color=red]`, `[color=#f00]` | `<span style="color: red">` |
+//! | `[size=150]` | `<span style="font-size: 150%">` |
+//! | `[list]`, `[list=1]`, `[ul]`, `[ol]`, `[*]` | `<ul>`, `<ol>`, `<li>` |
+//!
+//! URLs go through [`escape_url`], so `javascript:` links are dropped. Colors
+//! and sizes that are not a plain name, hex code or number leave the
+//! content unstyled.
+//!
+//! ```rust
+//! use shortcode_parser::bbcode;
+//!
+//! let sc = bbcode::registry();
+//! assert_eq!(
+//!     sc.render("[B]Hi[/b] <3 [url=https://example.com]site[/url]"),
+//!     "<strong>Hi</strong> &lt;3 <a href=\"https://example.com\">site</a>"
+//! );
+//! assert_eq!(
+//!     sc.render("[list][*]one[*]two[/list]"),
+//!     "<ul><li>one</li><li>two</li></ul>"
+//! );
+//! ```
+
+use crate::attrs::ShortcodeAttrs;
+use crate::html::{escape_attr, escape_text, escape_url, OutputMode};
+use crate::shortcode::Shortcode;
+use crate::syntax::Syntax;
+
+/// A registry with the BBCode syntax, HTML output mode and every handler
+/// in this module.
+pub fn registry() -> Shortcode {
+    let mut shortcodes = Shortcode::new();
+    shortcodes.set_syntax(Syntax::bbcode());
+    shortcodes.set_output_mode(OutputMode::Html);
+    register(&mut shortcodes);
+    shortcodes
+}
+
+/// Adds the handlers in this module to `shortcodes`, replacing any already
+/// registered under the same names.
+///
+/// The handlers treat their content as HTML, so render with
+/// [`OutputMode::Html`] unless the input is trusted.
+pub fn register(shortcodes: &mut Shortcode) {
+    for (name, element) in [("b", "strong"), ("i", "em"), ("u", "u"), ("s", "s")] {
+        shortcodes.add(name, move |content, _| {
+            format!("<{element}>{}</{element}>", content.unwrap_or(""))
+        });
+    }
+    shortcodes.add("url", url);
+    shortcodes.add("img", img);
+    shortcodes.add("quote", quote);
+    shortcodes.add("code", code);
+    shortcodes.add("color", color);
+    shortcodes.add("size", size);
+    shortcodes.add("list", list);
+    shortcodes.add("ul", |content, _| {
+        format!("<ul>{}</ul>", content.unwrap_or(""))
+    });
+    shortcodes.add("ol", |content, _| {
+        format!("<ol>{}</ol>", content.unwrap_or(""))
+    });
+    shortcodes.add("*", |content, _| {
+        format!("<li>{}</li>", content.unwrap_or(""))
+    });
+}
+
+/// `[url=href]text[/url]`, or `[url]href[/url]` using the address as text.
+fn url(content: Option<&str>, attrs: ShortcodeAttrs) -> String {
+    let content = content.unwrap_or("");
+    match attrs.value() {
+        Some(href) if content.is_empty() => {
+            format!("<a href=\"{}\">{}</a>", escape_url(href), escape_text(href))
+        }
+        Some(href) => format!("<a href=\"{}\">{}</a>", escape_url(href), content),
+        None => format!(
+            "<a href=\"{}\">{}</a>",
+            escape_url(&unescape(content)),
+            content
+        ),
+    }
+}
+
+fn img(content: Option<&str>, _: ShortcodeAttrs) -> String {
+    format!(
+        "<img src=\"{}\" alt=\"\">",
+        escape_url(&unescape(content.unwrap_or("")))
+    )
+}
+
+fn quote(content: Option<&str>, attrs: ShortcodeAttrs) -> String {
+    let content = content.unwrap_or("");
+    match attrs.value() {
+        Some(name) => format!(
+            "<blockquote><cite>{}</cite>{}</blockquote>",
+            escape_text(name),
+            content
+        ),
+        None => format!("<blockquote>{content}</blockquote>"),
+    }
+}
+
+fn code(content: Option<&str>, attrs: ShortcodeAttrs) -> String {
+    let content = content.unwrap_or("");
+    match attrs.value() {
+        Some(language) => format!(
+            "<pre><code class=\"language-{}\">{}</code></pre>",
+            escape_attr(language),
+            content
+        ),
+        None => format!("<pre><code>{content}</code></pre>"),
+    }
+}
+
+/// A color name or `#` hex code; anything else could escape the property.
+fn color(content: Option<&str>, attrs: ShortcodeAttrs) -> String {
+    let content = content.unwrap_or("");
+    let valid = |color: &&str| match color.strip_prefix('#') {
+        Some(hex) => {
+            matches!(hex.len(), 3 | 4 | 6 | 8) && hex.bytes().all(|b| b.is_ascii_hexdigit())
+        }
+        None => !color.is_empty() && color.bytes().all(|b| b.is_ascii_alphabetic()),
+    };
+    match attrs.value().filter(valid) {
+        Some(color) => format!("<span style=\"color: {color}\">{content}</span>"),
+        None => content.to_string(),
+    }
+}
+
+/// A font size in percent, up to 400.
+fn size(content: Option<&str>, attrs: ShortcodeAttrs) -> String {
+    let content = content.unwrap_or("");
+    let size = attrs
+        .value()
+        .filter(|size| size.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|size| size.parse::<u16>().ok())
+        .filter(|size| (1..=400).contains(size));
+    match size {
+        Some(size) => format!("<span style=\"font-size: {size}%\">{content}</span>"),
+        None => content.to_string(),
+    }
+}
+
+/// `[list]` is unordered; `[list=1]`, `[list=a]`, `[list=A]`, `[list=i]`
+/// and `[list=I]` are ordered with that numbering.
+fn list(content: Option<&str>, attrs: ShortcodeAttrs) -> String {
+    let content = content.unwrap_or("");
+    match attrs.value() {
+        Some("1") => format!("<ol>{content}</ol>"),
+        Some(kind @ ("a" | "A" | "i" | "I")) => format!("<ol type=\"{kind}\">{content}</ol>"),
+        _ => format!("<ul>{content}</ul>"),
+    }
+}
+
+/// Reverses [`escape_text`], for content used as a URL. Harmless on
+/// unescaped text, since the URL is escaped again.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_styles() {
+        let sc = registry();
+        assert_eq!(
+            sc.render("[b]a[/b][I]b[/I][u]c[/U][s]d[/s]"),
+            "<strong>a</strong><em>b</em><u>c</u><s>d</s>"
+        );
+        assert_eq!(
+            sc.render("[color=red]x[/color][color=#00ff00]y[/color]"),
+            "<span style=\"color: red\">x</span><span style=\"color: #00ff00\">y</span>"
+        );
+        assert_eq!(sc.render("[color=red;background:x]z[/color]"), "z");
+        assert_eq!(
+            sc.render("[size=150]x[/size][size=9999]y[/size]"),
+            "<span style=\"font-size: 150%\">x</span>y"
+        );
+    }
+
+    #[test]
+    fn test_links_and_images() {
+        let sc = registry();
+        assert_eq!(
+            sc.render("[url]https://x.test/?a=1&b=2[/url]"),
+            "<a href=\"https://x.test/?a=1&amp;b=2\">https://x.test/?a=1&amp;b=2</a>"
+        );
+        assert_eq!(
+            sc.render("[url=\"https://x.test\"][/url]"),
+            "<a href=\"https://x.test\">https://x.test</a>"
+        );
+        assert_eq!(
+            sc.render("[url=javascript:alert(1)]x[/url]"),
+            "<a href=\"\">x</a>"
+        );
+        assert_eq!(
+            sc.render("[img]/a \"b\".png[/img]"),
+            "<img src=\"/a%20%22b%22.png\" alt=\"\">"
+        );
+    }
+
+    #[test]
+    fn test_quote_and_code() {
+        let sc = registry();
+        assert_eq!(
+            sc.render("[quote=\"<Jane>\"]hi[/quote]"),
+            "<blockquote><cite>&lt;Jane&gt;</cite>hi</blockquote>"
+        );
+        assert_eq!(
+            sc.render("[code=rust][b]x[/b] <T>[/code]"),
+            "<pre><code class=\"language-rust\">[b]x[/b] &lt;T&gt;</code></pre>"
+        );
+    }
+
+    #[test]
+    fn test_lists() {
+        let sc = registry();
+        assert_eq!(
+            sc.render("[list=1]\n[*]a\n[*]b [b]c[/b]\n[/list]"),
+            "<ol>\n<li>a\n</li><li>b <strong>c</strong>\n</li></ol>"
+        );
+        assert_eq!(
+            sc.render("[LIST][*]a[list=A][*]b[*]c[/list][*]d[/LIST]"),
+            "<ul><li>a<ol type=\"A\"><li>b</li><li>c</li></ol></li><li>d</li></ul>"
+        );
+        assert_eq!(
+            sc.render("[ul][*]a[/*][*]b[/ul]"),
+            "<ul><li>a</li><li>b</li></ul>"
+        );
+    }
+
+    #[test]
+    fn test_unregistered_tags_are_written_back() {
+        let mut sc = Shortcode::new();
+        sc.set_syntax(Syntax::bbcode());
+        assert_eq!(
+            sc.render("[spoiler=\"x\"]a[/SPOILER] [list][*]b[*]c[/list]"),
+            "[spoiler=\"x\"]a[/SPOILER] [list][*]b[*]c[/list]"
+        );
+    }
+}
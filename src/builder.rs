@@ -0,0 +1,236 @@
+use crate::ast::{Children, Node, SourceError, Tag};
+use crate::syntax::Syntax;
+
+/// Builds a shortcode tag in code, such as one inserted by an editor.
+///
+/// The result is the same [`Tag`] the parser produces, written with
+/// [`ShortcodeBuilder::to_source`]. Values are double-quoted, or
+/// single-quoted if they contain a double quote. Shortcode syntax has no
+/// escapes, so a tag that would read back differently, such as one whose
+/// text contains its own closing tag, is a [`SourceError`].
+///
+/// ```rust
+/// use shortcode_parser::{ShortcodeBuilder, Syntax};
+///
+/// let video = ShortcodeBuilder::new("video")
+///     .attr("id", "1")
+///     .attr("title", "Say \"hi\"")
+///     .flag("autoplay")
+///     .content("Watch this");
+/// assert_eq!(
+///     video.to_source(&Syntax::new()).unwrap(),
+///     "[video id=\"1\" title='Say \"hi\"' autoplay]Watch this[/video]"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShortcodeBuilder {
+    tag: Tag,
+}
+
+impl ShortcodeBuilder {
+    /// A self-closing tag named `name`, without attributes.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            tag: Tag {
+                name: name.into(),
+                attrs: Vec::new(),
+                children: None,
+            },
+        }
+    }
+
+    /// Adds the attribute `name="value"`.
+    pub fn attr(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tag.attrs.push((name.into(), Some(value.into())));
+        self
+    }
+
+    /// Adds a flag: an attribute without a value, as in `[video autoplay]`.
+    pub fn flag(mut self, name: impl Into<String>) -> Self {
+        self.tag.attrs.push((name.into(), None));
+        self
+    }
+
+    /// Sets the tag's own value, written as BBCode's `[url="https://x"]`.
+    /// See [`ShortcodeAttrs::value`](crate::ShortcodeAttrs::value).
+    pub fn value(mut self, value: impl Into<String>) -> Self {
+        self.tag.attrs.retain(|(name, _)| !name.is_empty());
+        self.tag
+            .attrs
+            .insert(0, (String::new(), Some(value.into())));
+        self
+    }
+
+    /// Appends text content, making this an enclosing tag. Empty text
+    /// gives an enclosing tag without content.
+    pub fn content(self, text: impl Into<String>) -> Self {
+        self.child(Node::Text(text.into()))
+    }
+
+    /// Appends a nested node, such as another built tag, making this an
+    /// enclosing tag.
+    ///
+    /// ```rust
+    /// use shortcode_parser::{ShortcodeBuilder, Syntax};
+    ///
+    /// let list = ShortcodeBuilder::new("tabs")
+    ///     .child(ShortcodeBuilder::new("tab").attr("title", "One").content("1"))
+    ///     .child(ShortcodeBuilder::new("tab").attr("title", "Two").content("2"));
+    /// assert_eq!(
+    ///     list.to_source(&Syntax::new()).unwrap(),
+    ///     "[tabs][tab title=\"One\"]1[/tab][tab title=\"Two\"]2[/tab][/tabs]"
+    /// );
+    /// ```
+    pub fn child(mut self, node: impl Into<Node>) -> Self {
+        let children = self.tag.children.get_or_insert_with(Children::default);
+        // Adjacent text is one node, as the parser would produce it.
+        match (node.into(), children.last_mut()) {
+            (Node::Text(text), Some(Node::Text(last))) => last.push_str(&text),
+            (Node::Text(text), _) if text.is_empty() => {}
+            (node, _) => children.push(node),
+        }
+        self
+    }
+
+    /// The built tag.
+    pub fn build(self) -> Tag {
+        self.tag
+    }
+
+    /// Writes the tag as shortcode source for `syntax`, or fails if that
+    /// source would not parse back to the built tag. See [`Tag::to_source`].
+    ///
+    /// ```rust
+    /// use shortcode_parser::{ShortcodeBuilder, Syntax};
+    ///
+    /// let quote = ShortcodeBuilder::new("q").attr("text", r#"it's "x""#);
+    /// assert!(quote.to_source(&Syntax::new()).is_err());
+    /// ```
+    pub fn to_source(&self, syntax: &Syntax) -> Result<String, SourceError> {
+        self.tag.to_source(syntax)
+    }
+}
+
+impl From<ShortcodeBuilder> for Tag {
+    fn from(builder: ShortcodeBuilder) -> Self {
+        builder.tag
+    }
+}
+
+impl From<ShortcodeBuilder> for Node {
+    fn from(builder: ShortcodeBuilder) -> Self {
+        Node::Tag(builder.tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Document;
+    use crate::syntax::Delimiters;
+
+    fn source(builder: &ShortcodeBuilder) -> String {
+        builder.to_source(&Syntax::new()).unwrap()
+    }
+
+    #[test]
+    fn test_quoting() {
+        let tag = ShortcodeBuilder::new("chart")
+            .attr("plain", "a b")
+            .attr("json", r#"{"stacked":true,"data":[1,2]}"#)
+            .attr("both", r#"it's"x""#)
+            .attr("empty", "")
+            .flag("wide");
+        assert_eq!(
+            source(&tag),
+            r#"[chart plain="a b" json='{"stacked":true,"data":[1,2]}' both=it's"x" empty="" wide]"#
+        );
+    }
+
+    #[test]
+    fn test_parses_back() {
+        let tag = ShortcodeBuilder::new("note")
+            .attr("title", r#"Say "hi" [now]"#)
+            .flag("open")
+            .content("see ")
+            .child(ShortcodeBuilder::new("b").content("this"));
+        let doc = Document::parse(&source(&tag));
+        assert_eq!(doc.nodes, [tag.into()]);
+    }
+
+    #[test]
+    fn test_unrepresentable() {
+        let syntax = Syntax::new();
+        let lossy = [
+            ShortcodeBuilder::new("q").attr("text", r#"it's "x""#),
+            ShortcodeBuilder::new("a").content("see [/a] here"),
+            ShortcodeBuilder::new("a b]"),
+            ShortcodeBuilder::new("a").attr("k=v", "1"),
+            ShortcodeBuilder::new("a").flag("x y"),
+            ShortcodeBuilder::new("a").child(Node::CloseTag("a".to_string())),
+        ];
+        for builder in lossy {
+            let Err(SourceError { written }) = builder.to_source(&syntax) else {
+                panic!("{builder:?} was written");
+            };
+            assert_ne!(Document::parse(&written).nodes, [builder.into()]);
+        }
+
+        let hugo = Syntax {
+            delimiters: Delimiters::HUGO,
+            ..Syntax::new()
+        };
+        let fine_in_hugo = ShortcodeBuilder::new("a").content("see [/a] here");
+        assert_eq!(
+            fine_in_hugo.to_source(&hugo).unwrap(),
+            "{{< a >}}see [/a] here{{< /a >}}"
+        );
+        assert!(ShortcodeBuilder::new("a")
+            .content("{{< /a >}}")
+            .to_source(&hugo)
+            .is_err());
+    }
+
+    #[test]
+    fn test_adjacent_text() {
+        let tag = ShortcodeBuilder::new("p")
+            .content("a")
+            .content("")
+            .content("b");
+        assert_eq!(source(&tag), "[p]ab[/p]");
+        assert_eq!(
+            tag.build().children.as_deref(),
+            Some(&vec![Node::Text("ab".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_enclosing_and_value() {
+        assert_eq!(source(&ShortcodeBuilder::new("hr")), "[hr]");
+        assert_eq!(source(&ShortcodeBuilder::new("p").content("")), "[p][/p]");
+
+        let url = ShortcodeBuilder::new("url")
+            .value("https://x.test")
+            .value("https://y.test")
+            .content("link");
+        assert_eq!(
+            url.to_source(&Syntax::bbcode()).unwrap(),
+            "[url=\"https://y.test\"]link[/url]"
+        );
+    }
+
+    #[test]
+    fn test_delimiters() {
+        let tag = ShortcodeBuilder::new("figure")
+            .attr("src", "a.png")
+            .content("x");
+        let hugo = Syntax {
+            delimiters: Delimiters::HUGO,
+            ..Syntax::new()
+        };
+        assert_eq!(
+            tag.to_source(&hugo).unwrap(),
+            "{{< figure src=\"a.png\" >}}x{{< /figure >}}"
+        );
+    }
+}
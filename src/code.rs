@@ -1,75 +1,500 @@
-use crate::attrs::ShortcodeAttrs;
-use crate::shortcode::ShortcodeFn;
+use crate::attrs::{AttrError, ShortcodeAttrs};
+use crate::context::Context;
+use crate::html::{escape_attr, OutputMode};
+use crate::interpolate::UndefinedVars;
+use crate::limits::{RenderError, RenderLimits};
+use crate::parser::Parser;
+use crate::renderer::Renderer;
+use crate::schema::Violation;
+use crate::shortcode::{Handler, Shortcode};
+use crate::syntax::{Delimiters, Dialect};
 use crate::token::Token;
+use std::borrow::Cow;
 
 #[derive(Debug)]
 pub enum Code<'a> {
-    Inline(&'a Token<'a>),
-    Nested(&'a Token<'a>, Vec<Code<'a>>),
+    Inline(Token<'a>),
+    /// An enclosing tag, its children, and the name its closing tag was
+    /// written with, if it was closed explicitly.
+    Nested(Token<'a>, Vec<Code<'a>>, Option<&'a str>),
 }
 
 impl<'a> Code<'a> {
     /// Returns the tag name of this code node, if it represents a shortcode tag.
     pub(crate) fn tag_name(&self) -> Option<&str> {
         match self {
-            Code::Inline(token) | Code::Nested(token, _) => token.tag_name(),
+            Code::Inline(token) | Code::Nested(token, ..) => token.tag_name(),
         }
     }
 
-    fn lookup_handler<'b>(
-        shortcodes: &'b [(&str, ShortcodeFn)],
-        code_name: &str,
-    ) -> Option<&'b ShortcodeFn> {
-        shortcodes
-            .iter()
-            .find(|(name, _)| *name == code_name)
-            .map(|(_, f)| f)
+    pub fn render(&self, shortcodes: &Shortcode, ctx: &Context) -> Result<String, RenderError> {
+        render_nodes(std::slice::from_ref(self), shortcodes, ctx, true)
+    }
+}
+
+/// A node of a parsed tree, either borrowed from the input (`Code`) or owned.
+///
+/// Rendering only needs these accessors, so both trees share `render_nodes`.
+pub(crate) trait RenderNode: Sized {
+    /// Name of an opening tag. `None` for text and stray close tags.
+    fn opening_name(&self) -> Option<&str>;
+
+    /// Attribute pairs of an opening tag.
+    fn attrs(&self) -> Cow<'_, [(&str, Option<&str>)]>;
+
+    /// Inner nodes of an enclosing tag; `None` if the tag is self-closing.
+    fn children(&self) -> Option<&[Self]>;
+
+    /// Name an enclosing tag's closing tag was written with, which may
+    /// differ from the opening name under case-insensitive matching.
+    /// `None` if it was closed implicitly or built without one.
+    fn closing_name(&self) -> Option<&str>;
+
+    /// The node as it appeared in the source (opening tag only for enclosing tags).
+    fn render_raw(&self, delimiters: &Delimiters) -> Cow<'_, str>;
+}
+
+impl<'a> RenderNode for Code<'a> {
+    fn opening_name(&self) -> Option<&str> {
+        match self {
+            Code::Inline(Token::CloseTag(_)) => None,
+            _ => self.tag_name(),
+        }
+    }
+
+    fn attrs(&self) -> Cow<'_, [(&str, Option<&str>)]> {
+        match self {
+            Code::Inline(token) | Code::Nested(token, ..) => Cow::Borrowed(token.attrs_slice()),
+        }
     }
 
-    pub fn render(&self, shortcodes: &[(&str, ShortcodeFn)]) -> String {
+    fn children(&self) -> Option<&[Self]> {
         match self {
-            Code::Inline(token) => {
-                if let Some(code_name) = token.tag_name() {
-                    if let Some(code_fn) = Self::lookup_handler(shortcodes, code_name) {
-                        code_fn(None, ShortcodeAttrs::new(token.attrs_slice()))
-                    } else {
-                        token.render_raw().into_owned()
+            Code::Inline(_) => None,
+            Code::Nested(_, children, _) => Some(children),
+        }
+    }
+
+    fn closing_name(&self) -> Option<&str> {
+        match self {
+            Code::Inline(_) => None,
+            Code::Nested(_, _, close) => *close,
+        }
+    }
+
+    fn render_raw(&self, delimiters: &Delimiters) -> Cow<'_, str> {
+        match self {
+            Code::Inline(token) | Code::Nested(token, ..) => token.render_raw_with(delimiters),
+        }
+    }
+}
+
+/// Renders top-level nodes, replacing registered shortcodes with their
+/// handler output, within the registry's [`RenderLimits`].
+///
+/// If `strict`, a tag rejected by its handler or schema fails the render;
+/// otherwise it is written back as source.
+pub(crate) fn render_nodes<N: RenderNode>(
+    nodes: &[N],
+    shortcodes: &Shortcode,
+    ctx: &Context,
+    strict: bool,
+) -> Result<String, RenderError> {
+    let mut pass = RenderPass {
+        shortcodes,
+        ctx,
+        limits: shortcodes.limits(),
+        expansions: 0,
+        output: String::new(),
+        reexpanding: Vec::new(),
+        mode: shortcodes.output_mode(),
+        delimiters: &shortcodes.syntax().delimiters,
+        dialect: shortcodes.syntax().dialect,
+        strict,
+    };
+    pass.run(nodes)?;
+    Ok(pass.output)
+}
+
+/// Writes nodes back as source with `delimiters`, expanding nothing.
+///
+/// Walks the tree with an explicit stack, like rendering, but cannot fail.
+pub(crate) fn render_source<N: RenderNode>(
+    nodes: &[N],
+    delimiters: &Delimiters,
+    dialect: Dialect,
+) -> String {
+    let mut output = String::new();
+    let mut stack: Vec<(&N, std::slice::Iter<'_, N>)> = Vec::new();
+    let mut siblings = nodes.iter();
+    loop {
+        let Some(node) = siblings.next() else {
+            let Some((node, rest)) = stack.pop() else {
+                return output;
+            };
+            siblings = rest;
+            if let Some(close) = close_source(node, delimiters, dialect) {
+                output.push_str(&close);
+            }
+            continue;
+        };
+        output.push_str(&node.render_raw(delimiters));
+        if let (Some(_), Some(children)) = (node.opening_name(), node.children()) {
+            stack.push((node, std::mem::replace(&mut siblings, children.iter())));
+        }
+    }
+}
+
+/// The closing tag of an enclosing `node` as written in the source, or
+/// `None` for BBCode list items, which are closed implicitly.
+fn close_source<'n, N: RenderNode>(
+    node: &'n N,
+    delimiters: &Delimiters,
+    dialect: Dialect,
+) -> Option<Cow<'n, str>> {
+    let name = node.opening_name().unwrap_or_default();
+    if dialect == Dialect::BbCode && name == "*" {
+        return None;
+    }
+    let name = node.closing_name().unwrap_or(name);
+    Some(Token::CloseTag(name).render_raw_with(delimiters))
+}
+
+/// Attribute pairs after defaults are merged in.
+type MergedAttrs<'a> = Cow<'a, [(&'a str, Option<&'a str>)]>;
+
+/// State of a single render, shared by every node it visits.
+struct RenderPass<'r> {
+    shortcodes: &'r Shortcode,
+    ctx: &'r Context,
+    limits: &'r RenderLimits,
+    expansions: usize,
+    /// Everything rendered so far, including the content of open tags.
+    output: String,
+    /// Raw source of the tags whose output is being re-expanded, outermost first.
+    reexpanding: Vec<String>,
+    /// How text outside handler output is treated; `Raw` while re-expanding
+    /// trusted handler output.
+    mode: OutputMode,
+    /// Used to write unknown tags back as they appeared.
+    delimiters: &'r Delimiters,
+    dialect: Dialect,
+    /// Whether a tag error fails the render instead of writing the tag back.
+    strict: bool,
+}
+
+/// An enclosing tag whose children are being rendered.
+struct Frame<'n, 'r, N> {
+    node: &'n N,
+    handler: Option<&'r Handler>,
+    /// Offset in the output where the tag's rendered content begins.
+    start: usize,
+    /// The tag's following siblings, resumed once it is closed.
+    siblings: std::slice::Iter<'n, N>,
+}
+
+impl<'r> RenderPass<'r> {
+    /// Walks the tree with an explicit stack of open tags, so nesting depth
+    /// is bounded by the heap rather than the native stack.
+    ///
+    /// Unknown tags are rendered verbatim, with their children rendered in place.
+    fn run<N: RenderNode>(&mut self, nodes: &[N]) -> Result<(), RenderError> {
+        let mut stack: Vec<Frame<'_, 'r, N>> = Vec::new();
+        let mut siblings = nodes.iter();
+
+        loop {
+            let Some(node) = siblings.next() else {
+                let Some(frame) = stack.pop() else {
+                    return Ok(());
+                };
+                siblings = frame.siblings;
+                match frame.handler {
+                    Some(handler) => {
+                        let content = self.output.split_off(frame.start);
+                        self.expand(handler, frame.node, Some(&content))?;
+                    }
+                    None => {
+                        if let Some(close) = close_source(frame.node, self.delimiters, self.dialect)
+                        {
+                            self.push_text(&close)?;
+                        }
+                    }
+                }
+                continue;
+            };
+
+            let Some(code_name) = node.opening_name() else {
+                self.push_text(&node.render_raw(self.delimiters))?;
+                continue;
+            };
+
+            if stack.len() >= self.limits.max_depth {
+                return Err(RenderError::DepthExceeded {
+                    limit: self.limits.max_depth,
+                });
+            }
+
+            let handler = self.shortcodes.lookup(code_name);
+            match (node.children(), handler) {
+                (Some(children), _) => {
+                    if handler.is_none() {
+                        self.push_text(&node.render_raw(self.delimiters))?;
                     }
-                } else {
-                    token.render_raw().into_owned()
+                    stack.push(Frame {
+                        node,
+                        handler,
+                        start: self.output.len(),
+                        siblings: std::mem::replace(&mut siblings, children.iter()),
+                    });
                 }
+                (None, Some(handler)) => self.expand(handler, node, None)?,
+                (None, None) => self.push_text(&node.render_raw(self.delimiters))?,
             }
-            Code::Nested(token, children) => {
-                if let Some(code_name) = token.tag_name() {
-                    let rendered_children = children
-                        .iter()
-                        .map(|code| code.render(shortcodes))
-                        .collect::<String>();
-
-                    if let Some(code_fn) = Self::lookup_handler(shortcodes, code_name) {
-                        code_fn(
-                            Some(rendered_children.as_str()),
-                            ShortcodeAttrs::new(token.attrs_slice()),
-                        )
-                    } else {
-                        format!(
-                            "{}{}{}",
-                            token.render_raw(),
-                            rendered_children,
-                            Token::CloseTag(code_name).render_raw(),
-                        )
+        }
+    }
+
+    /// Calls `handler` for `node` and appends its output.
+    fn expand<N: RenderNode>(
+        &mut self,
+        handler: &Handler,
+        node: &N,
+        content: Option<&str>,
+    ) -> Result<(), RenderError> {
+        self.expansions += 1;
+        if self.expansions > self.limits.max_expansions {
+            return Err(RenderError::ExpansionsExceeded {
+                limit: self.limits.max_expansions,
+            });
+        }
+
+        let name = node.opening_name().unwrap_or_default();
+        if !self.shortcodes.reexpands(name) {
+            return match self.call_or_write_back(handler, node, content)? {
+                Some(output) => self.push(&output),
+                None => Ok(()),
+            };
+        }
+
+        let tag = format!(
+            "{}{}",
+            node.render_raw(self.delimiters),
+            content.unwrap_or_default()
+        );
+        if self.reexpanding.contains(&tag) {
+            return Err(RenderError::ReexpansionCycle {
+                tag: node.render_raw(self.delimiters).into_owned(),
+            });
+        }
+        if self.reexpanding.len() >= self.limits.max_reexpansion_depth {
+            return Err(RenderError::ReexpansionDepthExceeded {
+                limit: self.limits.max_reexpansion_depth,
+            });
+        }
+
+        match self.call_or_write_back(handler, node, content)? {
+            Some(output) => self.reexpand(tag, &output),
+            None => Ok(()),
+        }
+    }
+
+    /// Calls `handler` for `node`. Unless the render is strict, a tag the
+    /// handler or its schema rejects is written back as source, as unknown
+    /// tags are, and `None` is returned.
+    fn call_or_write_back<N: RenderNode>(
+        &mut self,
+        handler: &Handler,
+        node: &N,
+        content: Option<&str>,
+    ) -> Result<Option<String>, RenderError> {
+        match self.call(handler, node, content) {
+            Ok(output) => Ok(Some(output)),
+            Err(error) if !self.strict && error.is_tag_error() => {
+                self.push_text(&node.render_raw(self.delimiters))?;
+                if let Some(content) = content {
+                    // `content` is already rendered.
+                    self.push(content)?;
+                    if let Some(close) = close_source(node, self.delimiters, self.dialect) {
+                        self.push_text(&close)?;
                     }
-                } else {
-                    token.render_raw().into_owned()
                 }
+                Ok(None)
             }
-      }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn call<N: RenderNode>(
+        &self,
+        handler: &Handler,
+        node: &N,
+        content: Option<&str>,
+    ) -> Result<String, RenderError> {
+        let written = node.attrs();
+        let interpolated;
+        let resolved: Vec<(&str, Option<&str>)>;
+        let written = match self.shortcodes.interpolation() {
+            Some(interpolation) => {
+                let (values, undefined) = interpolation.apply(&written, self.ctx);
+                if let (UndefinedVars::Error, Some(var)) =
+                    (interpolation.undefined_vars(), undefined.first())
+                {
+                    return Err(RenderError::UndefinedVariable {
+                        tag: node.render_raw(self.delimiters).into_owned(),
+                        name: var.name.to_string(),
+                    });
+                }
+                interpolated = values;
+                resolved = interpolated
+                    .iter()
+                    .map(|(name, value)| (*name, value.as_deref()))
+                    .collect();
+                Cow::Borrowed(&resolved[..])
+            }
+            None => written,
+        };
+        let (attrs, authored) = self.merged_attrs(node, written, content)?;
+        let result = if !self.shortcodes.escape_attrs() {
+            handler.call(content, self.attrs(&attrs, authored), self.ctx)
+        } else {
+            self.call_escaped(handler, &attrs, authored, content)
+        };
+        result.map_err(|error| RenderError::InvalidAttribute {
+            tag: node.render_raw(self.delimiters).into_owned(),
+            error,
+        })
+    }
+
+    /// The attributes the handler sees: those written in the tag (after
+    /// interpolation), minus
+    /// repeats the [`DuplicateAttrs`](crate::DuplicateAttrs) policy drops and
+    /// any dropped by its [`Defaults`](crate::Defaults), checked against its
+    /// schema, followed by defaults for the missing ones. Also returns how
+    /// many were written.
+    fn merged_attrs<'a, N: RenderNode>(
+        &'a self,
+        node: &'a N,
+        mut attrs: MergedAttrs<'a>,
+        content: Option<&str>,
+    ) -> Result<(MergedAttrs<'a>, usize), RenderError> {
+        let name = node.opening_name().unwrap_or_default();
+        let case = self.shortcodes.name_matching();
+        let tag = || node.render_raw(self.delimiters).into_owned();
+        if let Err(error) = self.shortcodes.duplicate_attrs().apply(&mut attrs, case) {
+            return Err(RenderError::InvalidAttribute { tag: tag(), error });
+        }
+        let defaults = self.shortcodes.defaults(name);
+        if let Some(defaults) = defaults {
+            if attrs.iter().any(|(key, _)| defaults.drops(key, case)) {
+                attrs.to_mut().retain(|(key, _)| !defaults.drops(key, case));
+            }
+        }
+        let authored = attrs.len();
+
+        if let Some(schema) = self.shortcodes.schema(name) {
+            let checked = self.attrs(&attrs, authored);
+            match schema.check(&checked, content.is_some()).into_iter().next() {
+                Some(Violation::Attr(error)) => {
+                    return Err(RenderError::InvalidAttribute { tag: tag(), error })
+                }
+                Some(Violation::Content) => {
+                    return Err(RenderError::ContentNotAllowed { tag: tag() })
+                }
+                None => {}
+            }
+            let missing = schema.defaults(&checked);
+            if !missing.is_empty() {
+                attrs.to_mut().extend(missing);
+            }
+        }
+        if let Some(defaults) = defaults {
+            let missing = defaults.missing(&self.attrs(&attrs, authored));
+            if !missing.is_empty() {
+                attrs.to_mut().extend(missing);
+            }
+        }
+        Ok((attrs, authored))
+    }
+
+    fn call_escaped(
+        &self,
+        handler: &Handler,
+        attrs: &[(&str, Option<&str>)],
+        authored: usize,
+        content: Option<&str>,
+    ) -> Result<String, AttrError> {
+        let escaped: Vec<(&str, Option<Cow<str>>)> = attrs
+            .iter()
+            .map(|(name, value)| (*name, value.map(escape_attr)))
+            .collect();
+        let attrs: Vec<(&str, Option<&str>)> = escaped
+            .iter()
+            .map(|(name, value)| (*name, value.as_deref()))
+            .collect();
+        handler.call(content, self.attrs(&attrs, authored), self.ctx)
+    }
+
+    fn attrs<'a>(
+        &self,
+        attrs: &'a [(&'a str, Option<&'a str>)],
+        authored: usize,
+    ) -> ShortcodeAttrs<'a> {
+        ShortcodeAttrs::new(attrs)
+            .with_case(self.shortcodes.name_matching())
+            .authored(authored)
+    }
+
+    /// Renders handler `output` as a document of its own and appends the
+    /// result. Recursion here is bounded by `max_reexpansion_depth`.
+    fn reexpand(&mut self, tag: String, output: &str) -> Result<(), RenderError> {
+        let syntax = self.shortcodes.syntax();
+        let renderer = Renderer::with_matching(
+            Parser::with_syntax(output, syntax),
+            syntax.dialect,
+            self.shortcodes.case_matching(),
+        );
+        let outer = std::mem::take(&mut self.output);
+
+        // Handler output is trusted, including the text around its shortcodes.
+        let mode = std::mem::replace(&mut self.mode, OutputMode::Raw);
+        self.reexpanding.push(tag);
+        let result = self.run(renderer.nodes());
+        self.reexpanding.pop();
+        self.mode = mode;
+
+        let rendered = std::mem::replace(&mut self.output, outer);
+        result?;
+        self.push(&rendered)
+    }
+
+    /// Appends source text, escaped as the output mode requires.
+    fn push_text(&mut self, text: &str) -> Result<(), RenderError> {
+        let mode = self.mode;
+        self.push(&mode.text(text))
+    }
+
+    fn push(&mut self, rendered: &str) -> Result<(), RenderError> {
+        self.output.push_str(rendered);
+        if self.output.len() > self.limits.max_output_bytes {
+            return Err(RenderError::OutputTooLarge {
+                limit: self.limits.max_output_bytes,
+            });
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shortcode::ShortcodeFn;
+
+    fn render(code: &Code, handlers: &[(&'static str, ShortcodeFn)]) -> String {
+        let mut shortcodes = Shortcode::new();
+        for (name, func) in handlers {
+            shortcodes.add(*name, *func);
+        }
+        code.render(&shortcodes, &Context::new()).unwrap()
+    }
 
     // Helper fn items for tests (ShortcodeFn is a function pointer, not a closure).
     fn handler_empty(_: Option<&str>, _: ShortcodeAttrs) -> String {
@@ -214,492 +639,492 @@ mod tests {
     #[test]
     fn test_tag_name_inline_self_close() {
         let token = Token::SelfClose("video");
-        let code = Code::Inline(&token);
+        let code = Code::Inline(token);
         assert_eq!(code.tag_name(), Some("video"));
     }
 
     #[test]
     fn test_tag_name_inline_self_close_attr() {
         let token = Token::SelfCloseAttr("video", vec![("id", Some("123"))]);
-        let code = Code::Inline(&token);
+        let code = Code::Inline(token);
         assert_eq!(code.tag_name(), Some("video"));
     }
 
     #[test]
     fn test_tag_name_inline_close_tag() {
         let token = Token::CloseTag("video");
-        let code = Code::Inline(&token);
+        let code = Code::Inline(token);
         assert_eq!(code.tag_name(), Some("video"));
     }
 
     #[test]
     fn test_tag_name_inline_text() {
         let token = Token::Text("hello");
-        let code = Code::Inline(&token);
+        let code = Code::Inline(token);
         assert_eq!(code.tag_name(), None);
     }
 
     #[test]
     fn test_tag_name_nested_self_close() {
         let token = Token::SelfClose("outer");
-        let code = Code::Nested(&token, vec![]);
+        let code = Code::Nested(token, vec![], None);
         assert_eq!(code.tag_name(), Some("outer"));
     }
 
     #[test]
     fn test_tag_name_nested_self_close_attr() {
         let token = Token::SelfCloseAttr("outer", vec![("x", Some("1"))]);
-        let code = Code::Nested(&token, vec![]);
+        let code = Code::Nested(token, vec![], None);
         assert_eq!(code.tag_name(), Some("outer"));
     }
 
     #[test]
     fn test_tag_name_nested_close_tag() {
         let token = Token::CloseTag("outer");
-        let code = Code::Nested(&token, vec![]);
+        let code = Code::Nested(token, vec![], None);
         assert_eq!(code.tag_name(), Some("outer"));
     }
 
     #[test]
     fn test_tag_name_nested_text() {
         let token = Token::Text("hello");
-        let code = Code::Nested(&token, vec![]);
+        let code = Code::Nested(token, vec![], None);
         assert_eq!(code.tag_name(), None);
     }
 
     #[test]
     fn test_render_inline_unknown_handler() {
         let token = Token::SelfClose("unknown");
-        let code = Code::Inline(&token);
-        assert_eq!(code.render(&[]), "[unknown]");
+        let code = Code::Inline(token);
+        assert_eq!(render(&code, &[]), "[unknown]");
     }
 
     #[test]
     fn test_render_inline_known_handler() {
         let token = Token::SelfClose("foo");
-        let code = Code::Inline(&token);
+        let code = Code::Inline(token);
         let handlers: &[(&str, ShortcodeFn)] = &[("foo", handler_foo)];
-        assert_eq!(code.render(handlers), "BAR");
+        assert_eq!(render(&code, handlers), "BAR");
     }
 
     #[test]
     fn test_render_inline_text() {
         let token = Token::Text("hello world");
-        let code = Code::Inline(&token);
-        assert_eq!(code.render(&[]), "hello world");
+        let code = Code::Inline(token);
+        assert_eq!(render(&code, &[]), "hello world");
     }
 
     #[test]
     fn test_render_inline_close_tag_no_handler() {
         let token = Token::CloseTag("foo");
-        let code = Code::Inline(&token);
-        assert_eq!(code.render(&[]), "[/foo]");
+        let code = Code::Inline(token);
+        assert_eq!(render(&code, &[]), "[/foo]");
     }
 
     #[test]
     fn test_render_inline_self_close_attr_no_handler() {
         let token = Token::SelfCloseAttr("video", vec![("id", Some("123"))]);
-        let code = Code::Inline(&token);
-        assert_eq!(code.render(&[]), r#"[video id="123"]"#);
+        let code = Code::Inline(token);
+        assert_eq!(render(&code, &[]), r#"[video id="123"]"#);
     }
 
     #[test]
     fn test_render_inline_self_close_flag_attr_no_handler() {
         let token = Token::SelfCloseAttr("video", vec![("autoplay", None)]);
-        let code = Code::Inline(&token);
-        assert_eq!(code.render(&[]), "[video autoplay]");
+        let code = Code::Inline(token);
+        assert_eq!(render(&code, &[]), "[video autoplay]");
     }
 
     #[test]
     fn test_render_inline_unknown_handler_with_attrs() {
         let token = Token::SelfCloseAttr("unknown", vec![("id", Some("123"))]);
-        let code = Code::Inline(&token);
-        assert_eq!(code.render(&[]), r#"[unknown id="123"]"#);
+        let code = Code::Inline(token);
+        assert_eq!(render(&code, &[]), r#"[unknown id="123"]"#);
     }
 
     #[test]
     fn test_render_nested_unknown_handler() {
         let token = Token::SelfClose("unknown");
-        let children = vec![Code::Inline(&Token::Text(" content "))];
-        let code = Code::Nested(&token, children);
-        assert_eq!(code.render(&[]), "[unknown] content [/unknown]");
+        let children = vec![Code::Inline(Token::Text(" content "))];
+        let code = Code::Nested(token, children, None);
+        assert_eq!(render(&code, &[]), "[unknown] content [/unknown]");
     }
 
     #[test]
     fn test_render_nested_known_handler_no_content() {
         let token = Token::SelfClose("foo");
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("foo", handler_foo_content)];
-        assert_eq!(code.render(handlers), "FOO()");
+        assert_eq!(render(&code, handlers), "FOO()");
     }
 
     #[test]
     fn test_render_nested_known_handler_with_content() {
         let token = Token::SelfClose("outer");
         let inner_token = Token::SelfClose("inner");
-        let inner_code = Code::Inline(&inner_token);
+        let inner_code = Code::Inline(inner_token);
         let children = vec![inner_code];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("outer", handler_outer), ("inner", handler_inner)];
-        assert_eq!(code.render(handlers), "<outer><inner/></outer>");
+        assert_eq!(render(&code, handlers), "<outer><inner/></outer>");
     }
 
     #[test]
     fn test_render_nested_known_handler_with_text_content() {
         let token = Token::SelfClose("outer");
         let text_token = Token::Text("hello");
-        let text_code = Code::Inline(&text_token);
+        let text_code = Code::Inline(text_token);
         let children = vec![text_code];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("outer", handler_outer)];
-        assert_eq!(code.render(handlers), "<outer>hello</outer>");
+        assert_eq!(render(&code, handlers), "<outer>hello</outer>");
     }
 
     #[test]
     fn test_render_nested_unknown_handler_with_text() {
         let token = Token::SelfClose("outer");
         let text_token = Token::Text("hello");
-        let text_code = Code::Inline(&text_token);
+        let text_code = Code::Inline(text_token);
         let children = vec![text_code];
-        let code = Code::Nested(&token, children);
-        assert_eq!(code.render(&[]), "[outer]hello[/outer]");
+        let code = Code::Nested(token, children, None);
+        assert_eq!(render(&code, &[]), "[outer]hello[/outer]");
     }
 
     #[test]
     fn test_render_nested_deeply() {
         let token_c = Token::SelfClose("c");
-        let code_c = Code::Inline(&token_c);
+        let code_c = Code::Inline(token_c);
         let token_b = Token::SelfClose("b");
-        let code_b = Code::Nested(&token_b, vec![code_c]);
+        let code_b = Code::Nested(token_b, vec![code_c], None);
         let token_a = Token::SelfClose("a");
-        let code_a = Code::Nested(&token_a, vec![code_b]);
+        let code_a = Code::Nested(token_a, vec![code_b], None);
         let handlers: &[(&str, ShortcodeFn)] = &[
             ("a", handler_a),
             ("b", handler_b),
             ("c", handler_c),
         ];
-        assert_eq!(code_a.render(handlers), "<a><b><c></c></b></a>");
+        assert_eq!(render(&code_a, handlers), "<a><b><c></c></b></a>");
     }
 
     #[test]
     fn test_render_nested_deeply_with_content() {
         let text_token = Token::Text(" hello ");
-        let text_code = Code::Inline(&text_token);
+        let text_code = Code::Inline(text_token);
         let token_c = Token::SelfClose("c");
-        let code_c = Code::Nested(&token_c, vec![text_code]);
+        let code_c = Code::Nested(token_c, vec![text_code], None);
         let token_b = Token::SelfClose("b");
-        let code_b = Code::Nested(&token_b, vec![code_c]);
+        let code_b = Code::Nested(token_b, vec![code_c], None);
         let token_a = Token::SelfClose("a");
-        let code_a = Code::Nested(&token_a, vec![code_b]);
+        let code_a = Code::Nested(token_a, vec![code_b], None);
         let handlers: &[(&str, ShortcodeFn)] = &[
             ("a", handler_a),
             ("b", handler_b),
             ("c", handler_c),
         ];
-        assert_eq!(code_a.render(handlers), "<a><b><c> hello </c></b></a>");
+        assert_eq!(render(&code_a, handlers), "<a><b><c> hello </c></b></a>");
     }
 
     #[test]
     fn test_render_nested_multiple_children() {
-        let text1 = Code::Inline(&Token::Text(" a "));
-        let text2 = Code::Inline(&Token::Text(" b "));
+        let text1 = Code::Inline(Token::Text(" a "));
+        let text2 = Code::Inline(Token::Text(" b "));
         let token = Token::SelfClose("outer");
-        let code = Code::Nested(&token, vec![text1, text2]);
+        let code = Code::Nested(token, vec![text1, text2], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("outer", handler_outer)];
-        assert_eq!(code.render(handlers), "<outer> a  b </outer>");
+        assert_eq!(render(&code, handlers), "<outer> a  b </outer>");
     }
 
     #[test]
     fn test_render_nested_handler_with_attrs() {
         let token = Token::SelfCloseAttr("video", vec![("id", Some("123"))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_video_id)];
-        assert_eq!(code.render(handlers), r#"<video id="123"/>"#);
+        assert_eq!(render(&code, handlers), r#"<video id="123"/>"#);
     }
 
     #[test]
     fn test_render_nested_handler_with_flag_attr() {
         let token = Token::SelfCloseAttr("video", vec![("autoplay", Some(""))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_video_autoplay)];
-        assert_eq!(code.render(handlers), "<video autoplay/>");
+        assert_eq!(render(&code, handlers), "<video autoplay/>");
     }
 
     #[test]
     fn test_render_nested_handler_with_missing_attr() {
         let token = Token::SelfCloseAttr("video", vec![("id", Some("123"))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_video_width)];
-        assert_eq!(code.render(handlers), r#"<video width="auto"/>"#);
+        assert_eq!(render(&code, handlers), r#"<video width="auto"/>"#);
     }
 
     #[test]
     fn test_render_nested_with_nested_unknown() {
         let inner_token = Token::SelfClose("inner");
-        let inner_code = Code::Inline(&inner_token);
+        let inner_code = Code::Inline(inner_token);
         let token = Token::SelfClose("outer");
-        let code = Code::Nested(&token, vec![inner_code]);
+        let code = Code::Nested(token, vec![inner_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("outer", handler_outer)];
-        assert_eq!(code.render(handlers), "<outer>[inner]</outer>");
+        assert_eq!(render(&code, handlers), "<outer>[inner]</outer>");
     }
 
     #[test]
     fn test_render_nested_with_handler_and_unknown_child() {
         let inner_token = Token::SelfClose("inner");
-        let inner_code = Code::Inline(&inner_token);
+        let inner_code = Code::Inline(inner_token);
         let text_token = Token::Text(" hello ");
-        let text_code = Code::Inline(&text_token);
+        let text_code = Code::Inline(text_token);
         let token = Token::SelfClose("outer");
-        let code = Code::Nested(&token, vec![inner_code, text_code]);
+        let code = Code::Nested(token, vec![inner_code, text_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("outer", handler_outer)];
-        assert_eq!(code.render(handlers), "<outer>[inner] hello </outer>");
+        assert_eq!(render(&code, handlers), "<outer>[inner] hello </outer>");
     }
 
     #[test]
     fn test_render_nested_empty_name() {
         let token = Token::SelfClose("");
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("", handler_foo)];
-        assert_eq!(code.render(handlers), "BAR");
+        assert_eq!(render(&code, handlers), "BAR");
     }
 
     #[test]
     fn test_render_nested_empty_name_no_handler() {
         let token = Token::SelfClose("");
-        let code = Code::Inline(&token);
-        assert_eq!(code.render(&[]), "[]");
+        let code = Code::Inline(token);
+        assert_eq!(render(&code, &[]), "[]");
     }
 
     #[test]
     fn test_render_nested_space_name() {
         let token = Token::SelfClose(" ");
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[(" ", handler_foo)];
-        assert_eq!(code.render(handlers), "BAR");
+        assert_eq!(render(&code, handlers), "BAR");
     }
 
     #[test]
     fn test_render_nested_space_name_no_handler() {
         let token = Token::SelfClose(" ");
-        let code = Code::Inline(&token);
-        assert_eq!(code.render(&[]), "[ ]");
+        let code = Code::Inline(token);
+        assert_eq!(render(&code, &[]), "[ ]");
     }
 
     #[test]
     fn test_render_nested_unicode_attr() {
         let token = Token::SelfCloseAttr("greet", vec![("name", Some("世界"))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("greet", handler_greet)];
-        assert_eq!(code.render(handlers), "<greet>Hello 世界!</greet>");
+        assert_eq!(render(&code, handlers), "<greet>Hello 世界!</greet>");
     }
 
     #[test]
     fn test_render_nested_unicode_content() {
         let text_token = Token::Text("世界!");
-        let text_code = Code::Inline(&text_token);
+        let text_code = Code::Inline(text_token);
         let token = Token::SelfClose("emoji");
-        let code = Code::Nested(&token, vec![text_code]);
+        let code = Code::Nested(token, vec![text_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("emoji", handler_emoji)];
-        assert_eq!(code.render(handlers), "<emoji>世界!</emoji>");
+        assert_eq!(render(&code, handlers), "<emoji>世界!</emoji>");
     }
 
     #[test]
     fn test_render_nested_equals_in_value() {
         let token = Token::SelfCloseAttr("video", vec![("url", Some("a=b&c=d"))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_video_url)];
-        assert_eq!(code.render(handlers), r#"<video url="a=b&c=d"/>"#);
+        assert_eq!(render(&code, handlers), r#"<video url="a=b&c=d"/>"#);
     }
 
     #[test]
     fn test_render_nested_duplicate_attrs() {
         let token = Token::SelfCloseAttr("video", vec![("id", Some("1")), ("id", Some("2"))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_video_id)];
-        assert_eq!(code.render(handlers), r#"<video id="1"/>"#);
+        assert_eq!(render(&code, handlers), r#"<video id="1"/>"#);
     }
 
     #[test]
     fn test_render_nested_html_in_attr() {
         let token = Token::SelfCloseAttr("video", vec![("html", Some("<div>"))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_video_html)];
-        assert_eq!(code.render(handlers), "<video><div></video>");
+        assert_eq!(render(&code, handlers), "<video><div></video>");
     }
 
     #[test]
     fn test_render_nested_empty_attr_value() {
         let token = Token::SelfCloseAttr("video", vec![("id", Some(""))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_video_id)];
-        assert_eq!(code.render(handlers), r#"<video id=""/>"#);
+        assert_eq!(render(&code, handlers), r#"<video id=""/>"#);
     }
 
     #[test]
     fn test_render_nested_newlines_in_content() {
         let text_token = Token::Text("\nhello\n");
-        let text_code = Code::Inline(&text_token);
+        let text_code = Code::Inline(text_token);
         let token = Token::SelfClose("tag");
-        let code = Code::Nested(&token, vec![text_code]);
+        let code = Code::Nested(token, vec![text_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("tag", handler_tag)];
-        assert_eq!(code.render(handlers), "<tag>\nhello\n</tag>");
+        assert_eq!(render(&code, handlers), "<tag>\nhello\n</tag>");
     }
 
     #[test]
     fn test_render_nested_tabs_in_content() {
         let text_token = Token::Text("\thello\t");
-        let text_code = Code::Inline(&text_token);
+        let text_code = Code::Inline(text_token);
         let token = Token::SelfClose("tag");
-        let code = Code::Nested(&token, vec![text_code]);
+        let code = Code::Nested(token, vec![text_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("tag", handler_tag)];
-        assert_eq!(code.render(handlers), "<tag>\thello\t</tag>");
+        assert_eq!(render(&code, handlers), "<tag>\thello\t</tag>");
     }
 
     #[test]
     fn test_render_nested_whitespace_content() {
         let text_token = Token::Text("   ");
-        let text_code = Code::Inline(&text_token);
+        let text_code = Code::Inline(text_token);
         let token = Token::SelfClose("tag");
-        let code = Code::Nested(&token, vec![text_code]);
+        let code = Code::Nested(token, vec![text_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("tag", handler_tag)];
-        assert_eq!(code.render(handlers), "<tag>   </tag>");
+        assert_eq!(render(&code, handlers), "<tag>   </tag>");
     }
 
     #[test]
     fn test_render_nested_bracket_in_text() {
         let text_token = Token::Text("a > b");
-        let text_code = Code::Inline(&text_token);
+        let text_code = Code::Inline(text_token);
         let token = Token::SelfClose("tag");
-        let code = Code::Nested(&token, vec![text_code]);
+        let code = Code::Nested(token, vec![text_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("tag", handler_tag)];
-        assert_eq!(code.render(handlers), "<tag>a > b</tag>");
+        assert_eq!(render(&code, handlers), "<tag>a > b</tag>");
     }
 
     #[test]
     fn test_render_nested_close_tag_in_children() {
         let close_token = Token::CloseTag("inner");
-        let close_code = Code::Inline(&close_token);
+        let close_code = Code::Inline(close_token);
         let token = Token::SelfClose("outer");
-        let code = Code::Nested(&token, vec![close_code]);
+        let code = Code::Nested(token, vec![close_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("outer", handler_outer)];
-        assert_eq!(code.render(handlers), "<outer>[/inner]</outer>");
+        assert_eq!(render(&code, handlers), "<outer>[/inner]</outer>");
     }
 
     #[test]
     fn test_render_nested_multiple_nested_children() {
         let inner1_token = Token::SelfClose("a");
-        let inner1_code = Code::Inline(&inner1_token);
+        let inner1_code = Code::Inline(inner1_token);
         let inner2_token = Token::SelfClose("b");
-        let inner2_code = Code::Inline(&inner2_token);
+        let inner2_code = Code::Inline(inner2_token);
         let token = Token::SelfClose("outer");
-        let code = Code::Nested(&token, vec![inner1_code, inner2_code]);
+        let code = Code::Nested(token, vec![inner1_code, inner2_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[
             ("outer", handler_outer),
             ("a", handler_foo),
             ("b", handler_foo),
         ];
-        assert_eq!(code.render(handlers), "<outer>BARBAR</outer>");
+        assert_eq!(render(&code, handlers), "<outer>BARBAR</outer>");
     }
 
     #[test]
     fn test_render_nested_deeply_with_multiple_children() {
-        let text1 = Code::Inline(&Token::Text(" x "));
-        let text2 = Code::Inline(&Token::Text(" y "));
+        let text1 = Code::Inline(Token::Text(" x "));
+        let text2 = Code::Inline(Token::Text(" y "));
         let inner_token = Token::SelfClose("b");
-        let inner_code = Code::Nested(&inner_token, vec![text1, text2]);
-        let text3 = Code::Inline(&Token::Text(" z "));
+        let inner_code = Code::Nested(inner_token, vec![text1, text2], None);
+        let text3 = Code::Inline(Token::Text(" z "));
         let outer_token = Token::SelfClose("a");
-        let outer_code = Code::Nested(&outer_token, vec![inner_code, text3]);
+        let outer_code = Code::Nested(outer_token, vec![inner_code, text3], None);
         let handlers: &[(&str, ShortcodeFn)] = &[
             ("a", handler_a),
             ("b", handler_b),
         ];
-        assert_eq!(outer_code.render(handlers), "<a><b> x  y </b> z </a>");
+        assert_eq!(render(&outer_code, handlers), "<a><b> x  y </b> z </a>");
     }
 
     #[test]
     fn test_render_nested_self_close_with_attr_no_handler() {
         let token = Token::SelfCloseAttr("video", vec![("id", Some("123")), ("autoplay", None)]);
-        let code = Code::Inline(&token);
-        assert_eq!(code.render(&[]), r#"[video id="123" autoplay]"#);
+        let code = Code::Inline(token);
+        assert_eq!(render(&code, &[]), r#"[video id="123" autoplay]"#);
     }
 
     #[test]
     fn test_render_nested_self_close_with_attr_handler() {
         let token = Token::SelfCloseAttr("video", vec![("id", Some("123")), ("autoplay", Some(""))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_video_id_autoplay)];
-        assert_eq!(code.render(handlers), r#"<video id="123" autoplay />"#);
+        assert_eq!(render(&code, handlers), r#"<video id="123" autoplay />"#);
     }
 
     #[test]
     fn test_render_nested_with_handler_and_unknown_nested() {
         let inner_token = Token::SelfClose("inner");
-        let inner_code = Code::Inline(&inner_token);
+        let inner_code = Code::Inline(inner_token);
         let text_token = Token::Text(" hello ");
-        let text_code = Code::Inline(&text_token);
+        let text_code = Code::Inline(text_token);
         let token = Token::SelfClose("outer");
-        let code = Code::Nested(&token, vec![inner_code, text_code]);
+        let code = Code::Nested(token, vec![inner_code, text_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("outer", handler_outer)];
-        assert_eq!(code.render(handlers), "<outer>[inner] hello </outer>");
+        assert_eq!(render(&code, handlers), "<outer>[inner] hello </outer>");
     }
 
     #[test]
     fn test_render_nested_deeply_nested_unknown() {
         let innermost = Token::SelfClose("c");
-        let innermost_code = Code::Inline(&innermost);
+        let innermost_code = Code::Inline(innermost);
         let middle = Token::SelfClose("b");
-        let middle_code = Code::Inline(&middle);
+        let middle_code = Code::Inline(middle);
         let outer = Token::SelfClose("a");
-        let outer_code = Code::Nested(&outer, vec![middle_code, innermost_code]);
+        let outer_code = Code::Nested(outer, vec![middle_code, innermost_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("a", handler_a)];
-        assert_eq!(outer_code.render(handlers), "<a>[b][c]</a>");
+        assert_eq!(render(&outer_code, handlers), "<a>[b][c]</a>");
     }
 
     #[test]
     fn test_render_inline_empty_name() {
         let token = Token::SelfClose("");
-        let code = Code::Inline(&token);
+        let code = Code::Inline(token);
         assert_eq!(code.tag_name(), Some(""));
     }
 
     #[test]
     fn test_render_inline_space_name() {
         let token = Token::SelfClose(" ");
-        let code = Code::Inline(&token);
+        let code = Code::Inline(token);
         assert_eq!(code.tag_name(), Some(" "));
     }
 
     #[test]
     fn test_render_inline_hyphen_name() {
         let token = Token::SelfClose("my-short-code");
-        let code = Code::Inline(&token);
+        let code = Code::Inline(token);
         assert_eq!(code.tag_name(), Some("my-short-code"));
     }
 
     #[test]
     fn test_render_inline_underscore_name() {
         let token = Token::SelfClose("my_shortcode");
-        let code = Code::Inline(&token);
+        let code = Code::Inline(token);
         assert_eq!(code.tag_name(), Some("my_shortcode"));
     }
 
     #[test]
     fn test_render_inline_number_name() {
         let token = Token::SelfClose("shortcode123");
-        let code = Code::Inline(&token);
+        let code = Code::Inline(token);
         assert_eq!(code.tag_name(), Some("shortcode123"));
     }
 
@@ -707,88 +1132,88 @@ mod tests {
     fn test_render_nested_hyphen_name() {
         let token = Token::SelfClose("my-short-code");
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("my-short-code", handler_foo)];
-        assert_eq!(code.render(handlers), "BAR");
+        assert_eq!(render(&code, handlers), "BAR");
     }
 
     #[test]
     fn test_render_nested_underscore_name() {
         let token = Token::SelfClose("my_shortcode");
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("my_shortcode", handler_foo)];
-        assert_eq!(code.render(handlers), "BAR");
+        assert_eq!(render(&code, handlers), "BAR");
     }
 
     #[test]
     fn test_render_nested_number_name() {
         let token = Token::SelfClose("shortcode123");
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("shortcode123", handler_foo)];
-        assert_eq!(code.render(handlers), "BAR");
+        assert_eq!(render(&code, handlers), "BAR");
     }
 
     #[test]
     fn test_render_nested_single_quote_attr() {
         let token = Token::SelfCloseAttr("video", vec![("id", Some("123"))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_video_id)];
-        assert_eq!(code.render(handlers), r#"<video id="123"/>"#);
+        assert_eq!(render(&code, handlers), r#"<video id="123"/>"#);
     }
 
     #[test]
     fn test_render_nested_ampersand_in_value() {
         let token = Token::SelfCloseAttr("video", vec![("src", Some("a&b"))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_video_src)];
-        assert_eq!(code.render(handlers), r#"<video src="a&b"/>"#);
+        assert_eq!(render(&code, handlers), r#"<video src="a&b"/>"#);
     }
 
     #[test]
     fn test_render_nested_empty_attr_key() {
         let token = Token::SelfCloseAttr("video", vec![("", Some("value"))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_val)];
-        assert_eq!(code.render(handlers), "VAL=value");
+        assert_eq!(render(&code, handlers), "VAL=value");
     }
 
     #[test]
     fn test_render_nested_only_flag_attrs() {
         let token = Token::SelfCloseAttr("video", vec![("full", None), ("autoplay", None), ("loop", None)]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_flags)];
-        assert_eq!(code.render(handlers), "flags=full,autoplay,loop");
+        assert_eq!(render(&code, handlers), "flags=full,autoplay,loop");
     }
 
     #[test]
     fn test_render_nested_mixed_spaces() {
         let token = Token::SelfCloseAttr("video", vec![("id", Some("123"))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_id)];
-        assert_eq!(code.render(handlers), "ID=123");
+        assert_eq!(render(&code, handlers), "ID=123");
     }
 
     #[test]
     fn test_render_nested_case_sensitive() {
         let token = Token::SelfClose("FOO");
-        let code = Code::Inline(&token);
+        let code = Code::Inline(token);
         let handlers: &[(&str, ShortcodeFn)] = &[("foo", handler_foo)];
-        assert_eq!(code.render(handlers), "[FOO]");
+        assert_eq!(render(&code, handlers), "[FOO]");
     }
 
     #[test]
     fn test_render_nested_empty_children() {
         let token = Token::SelfClose("tag");
-        let code = Code::Nested(&token, vec![]);
+        let code = Code::Nested(token, vec![], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("tag", handler_tag)];
-        assert_eq!(code.render(handlers), "<tag></tag>");
+        assert_eq!(render(&code, handlers), "<tag></tag>");
     }
 
     #[test]
@@ -797,30 +1222,30 @@ mod tests {
             .map(|i| format!("{} ", i))
             .collect();
         let tokens: Vec<Token> = strs.iter().map(|s| Token::Text(s.as_str())).collect();
-        let children: Vec<Code> = tokens.iter().map(|t| Code::Inline(t)).collect();
+        let children: Vec<Code> = tokens.into_iter().map(Code::Inline).collect();
         let token = Token::SelfClose("tag");
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("tag", handler_tag)];
-        assert_eq!(code.render(handlers), "<tag>0 1 2 3 4 5 6 7 8 9 </tag>");
+        assert_eq!(render(&code, handlers), "<tag>0 1 2 3 4 5 6 7 8 9 </tag>");
     }
 
     #[test]
     fn test_render_nested_deeply_interleaved() {
-        let text3 = Code::Inline(&Token::Text(" c "));
+        let text3 = Code::Inline(Token::Text(" c "));
         let inner_c = Token::SelfClose("c");
-        let code_c = Code::Nested(&inner_c, vec![text3]);
-        let text2 = Code::Inline(&Token::Text(" b "));
+        let code_c = Code::Nested(inner_c, vec![text3], None);
+        let text2 = Code::Inline(Token::Text(" b "));
         let inner_b = Token::SelfClose("b");
-        let code_b = Code::Nested(&inner_b, vec![code_c, text2]);
-        let text1 = Code::Inline(&Token::Text(" a "));
+        let code_b = Code::Nested(inner_b, vec![code_c, text2], None);
+        let text1 = Code::Inline(Token::Text(" a "));
         let inner_a = Token::SelfClose("a");
-        let code_a = Code::Nested(&inner_a, vec![code_b, text1]);
+        let code_a = Code::Nested(inner_a, vec![code_b, text1], None);
         let handlers: &[(&str, ShortcodeFn)] = &[
             ("a", handler_a_interleaved),
             ("b", handler_b_interleaved),
             ("c", handler_c_interleaved),
         ];
-        assert_eq!(code_a.render(handlers), "<a><b><c> c </c> b </b> a </a>");
+        assert_eq!(render(&code_a, handlers), "<a><b><c> c </c> b </b> a </a>");
     }
 
     #[test]
@@ -828,84 +1253,84 @@ mod tests {
         let long_val = "x".repeat(1000);
         let token = Token::SelfCloseAttr("video", vec![("id", Some(&long_val))]);
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("video", handler_len)];
-        assert_eq!(code.render(handlers), "len=1000");
+        assert_eq!(render(&code, handlers), "len=1000");
     }
 
     #[test]
     fn test_render_nested_unknown_deeply() {
         let z = Token::SelfClose("z");
-        let z_code = Code::Inline(&z);
+        let z_code = Code::Inline(z);
         let y = Token::SelfClose("y");
-        let y_code = Code::Inline(&y);
+        let y_code = Code::Inline(y);
         let x = Token::SelfClose("x");
-        let x_code = Code::Inline(&x);
-        assert_eq!(x_code.render(&[]), "[x]");
-        assert_eq!(y_code.render(&[]), "[y]");
-        assert_eq!(z_code.render(&[]), "[z]");
+        let x_code = Code::Inline(x);
+        assert_eq!(render(&x_code, &[]), "[x]");
+        assert_eq!(render(&y_code, &[]), "[y]");
+        assert_eq!(render(&z_code, &[]), "[z]");
     }
 
     #[test]
     fn test_render_nested_mixed_known_unknown() {
         let unknown = Token::SelfClose("z");
-        let unknown_code = Code::Inline(&unknown);
+        let unknown_code = Code::Inline(unknown);
         let known_token = Token::SelfClose("y");
-        let known_code = Code::Nested(&known_token, vec![unknown_code]);
+        let known_code = Code::Nested(known_token, vec![unknown_code], None);
         let outer_token = Token::SelfClose("x");
-        let outer_code = Code::Nested(&outer_token, vec![known_code]);
+        let outer_code = Code::Nested(outer_token, vec![known_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[
             ("x", handler_x),
             ("y", handler_y),
         ];
-        assert_eq!(outer_code.render(handlers), "<x><y>[z]</y></x>");
+        assert_eq!(render(&outer_code, handlers), "<x><y>[z]</y></x>");
     }
 
     #[test]
     fn test_render_nested_handler_receives_rendered_children() {
         let inner_token = Token::SelfClose("inner");
-        let inner_code = Code::Inline(&inner_token);
+        let inner_code = Code::Inline(inner_token);
         let token = Token::SelfClose("outer");
-        let code = Code::Nested(&token, vec![inner_code]);
+        let code = Code::Nested(token, vec![inner_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[
             ("outer", handler_outer_inner),
             ("inner", handler_inner_handled),
         ];
-        assert_eq!(code.render(handlers), "outer(inner_handled)-inner(handled)");
+        assert_eq!(render(&code, handlers), "outer(inner_handled)-inner(handled)");
     }
 
     #[test]
     fn test_render_nested_handler_receives_nested_rendered() {
         let innermost = Token::SelfClose("z");
-        let innermost_code = Code::Inline(&innermost);
+        let innermost_code = Code::Inline(innermost);
         let middle_token = Token::SelfClose("y");
-        let middle_code = Code::Nested(&middle_token, vec![innermost_code]);
+        let middle_code = Code::Nested(middle_token, vec![innermost_code], None);
         let outer_token = Token::SelfClose("x");
-        let outer_code = Code::Nested(&outer_token, vec![middle_code]);
+        let outer_code = Code::Nested(outer_token, vec![middle_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[
             ("x", handler_x_nested),
             ("y", handler_y_nested),
             ("z", handler_z),
         ];
-        assert_eq!(outer_code.render(handlers), "x(y(Z))");
+        assert_eq!(render(&outer_code, handlers), "x(y(Z))");
     }
 
     #[test]
     fn test_render_nested_empty_string_handler() {
         let token = Token::SelfClose("tag");
         let children: Vec<Code> = vec![];
-        let code = Code::Nested(&token, children);
+        let code = Code::Nested(token, children, None);
         let handlers: &[(&str, ShortcodeFn)] = &[("tag", handler_empty)];
-        assert_eq!(code.render(handlers), "");
+        assert_eq!(render(&code, handlers), "");
     }
 
     #[test]
     fn test_render_nested_handler_with_special_chars() {
         let text_token = Token::Text("<>&\"'");
-        let text_code = Code::Inline(&text_token);
+        let text_code = Code::Inline(text_token);
         let token = Token::SelfClose("tag");
-        let code = Code::Nested(&token, vec![text_code]);
+        let code = Code::Nested(token, vec![text_code], None);
         let handlers: &[(&str, ShortcodeFn)] = &[("tag", handler_tag)];
-        assert_eq!(code.render(handlers), "<tag><>&\"'</tag>");
+        assert_eq!(render(&code, handlers), "<tag><>&\"'</tag>");
     }
 }
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+/// Per-render values made available to context-aware handlers.
+///
+/// A `Context` is a plain string map. The same parsed input can be rendered
+/// against many contexts, e.g. one per request or per user.
+///
+/// Example:
+/// ```rust
+/// use shortcode_parser::{shortcode::Shortcode, Context};
+///
+/// let mut sc = Shortcode::new();
+/// sc.add_with_context("user", |_, _, ctx| ctx.get("user").unwrap_or("guest").to_string());
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("user", "ada");
+/// assert_eq!(sc.render_with("Hi [user]", &ctx), "Hi ada");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Context {
+    values: HashMap<String, String>,
+}
+
+impl Context {
+    /// Creates an empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `key` to `value`, replacing any previous value.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.values.insert(key.into(), value.into());
+    }
+
+    /// Looks up a value by key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_missing() {
+        let ctx = Context::new();
+        assert_eq!(ctx.get("user"), None);
+    }
+
+    #[test]
+    fn test_insert_replaces() {
+        let mut ctx = Context::new();
+        ctx.insert("user", "ada");
+        ctx.insert("user", "grace");
+        assert_eq!(ctx.get("user"), Some("grace"));
+    }
+}
@@ -0,0 +1,534 @@
+//! `serde` deserialization of [`ShortcodeAttrs`].
+//!
+//! Attributes deserialize as a map from name to value. Values are strings,
+//! so numbers, bools and unit enum variants are parsed from them the way
+//! the `get_*` accessors do.
+
+use crate::attrs::{self, AttrError, ShortcodeAttrs};
+use crate::syntax::CaseMatching;
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use std::collections::{hash_map, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+/// Deserializes `T` from `attrs`.
+pub(crate) fn from_attrs<'de, T>(attrs: &ShortcodeAttrs<'de>) -> Result<T, AttrError>
+where
+    T: de::Deserialize<'de>,
+{
+    T::deserialize(AttrsDeserializer::new(attrs)).map_err(|err| match err {
+        Error::Attr(err) => err,
+        Error::Message(message) => AttrError::Other { message },
+    })
+}
+
+/// An error while deserializing. Messages about a value become
+/// [`AttrError::Invalid`] once the attribute is known.
+#[derive(Debug)]
+enum Error {
+    Attr(AttrError),
+    Message(String),
+}
+
+impl Error {
+    /// Attributes a value error to `name`.
+    fn at(self, name: &str, value: Option<&str>) -> Self {
+        match self {
+            Error::Message(reason) => {
+                Error::Attr(AttrError::invalid(name, value.unwrap_or_default(), reason))
+            }
+            err => err,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Attr(err) => err.fmt(f),
+            Error::Message(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Error::Attr(AttrError::Missing {
+            name: field.to_string(),
+        })
+    }
+
+    fn unknown_field(field: &str, _expected: &'static [&'static str]) -> Self {
+        Error::Attr(AttrError::Unknown {
+            name: field.to_string(),
+        })
+    }
+
+    fn duplicate_field(field: &'static str) -> Self {
+        Error::Attr(AttrError::Duplicate {
+            name: field.to_string(),
+        })
+    }
+}
+
+/// An attribute name with every value given for it, in source order.
+type Entry<'de> = (&'de str, Vec<Option<&'de str>>);
+
+/// The attribute set as a map.
+struct AttrsDeserializer<'de> {
+    entries: Vec<Entry<'de>>,
+    case: CaseMatching,
+}
+
+impl<'de> AttrsDeserializer<'de> {
+    fn new(attrs: &ShortcodeAttrs<'de>) -> Self {
+        let case = attrs.case();
+        let mut entries: Vec<Entry<'de>> = Vec::new();
+        // Index in `entries` of each folded name.
+        let mut index: HashMap<_, usize> = HashMap::new();
+        for &(name, value) in attrs.iter() {
+            match index.entry(case.fold(name)) {
+                hash_map::Entry::Occupied(slot) => entries[*slot.get()].1.push(value),
+                hash_map::Entry::Vacant(slot) => {
+                    slot.insert(entries.len());
+                    entries.push((name, vec![value]));
+                }
+            }
+        }
+        Self { entries, case }
+    }
+
+    fn visit<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_map(Entries {
+            entries: self.entries.into_iter(),
+            fields,
+            case: self.case,
+            value: None,
+        })
+    }
+}
+
+impl<'de> de::Deserializer<'de> for AttrsDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.visit(&[], visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.visit(fields, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct seq tuple tuple_struct map enum
+        identifier ignored_any
+    }
+}
+
+/// Map access over the attributes. Names matching a struct field under
+/// the registry's [`CaseMatching`] are given as the field name.
+struct Entries<'de> {
+    entries: std::vec::IntoIter<Entry<'de>>,
+    fields: &'static [&'static str],
+    case: CaseMatching,
+    value: Option<ValueDeserializer<'de>>,
+}
+
+impl<'de> MapAccess<'de> for Entries<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some((name, values)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some(ValueDeserializer { name, values });
+        let key = self
+            .fields
+            .iter()
+            .find(|field| self.case.eq(field, name))
+            .map_or(name, |field| field);
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self.value.take().expect("a key was read first");
+        let (name, first) = (value.name, value.first());
+        seed.deserialize(value).map_err(|err| err.at(name, first))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+/// The values of one attribute. Single values are read from the first;
+/// sequences take every value, split on commas like
+/// [`ShortcodeAttrs::get_list`].
+struct ValueDeserializer<'de> {
+    name: &'de str,
+    values: Vec<Option<&'de str>>,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    fn first(&self) -> Option<&'de str> {
+        self.values.first().copied().flatten()
+    }
+
+    /// The first value; flags have none.
+    fn value(&self) -> Result<&'de str, Error> {
+        self.first().ok_or_else(|| {
+            Error::Attr(AttrError::MissingValue {
+                name: self.name.to_string(),
+            })
+        })
+    }
+
+    fn parse<T>(&self) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.value()?
+            .trim()
+            .parse()
+            .map_err(|err: T::Err| Error::Message(err.to_string()))
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    /// Strings for values and `true` for flags.
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.first() {
+            Some(value) => visitor.visit_borrowed_str(value),
+            None => visitor.visit_bool(true),
+        }
+    }
+
+    /// Reads yes/no values like [`ShortcodeAttrs::get_bool`].
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.first() {
+            None => visitor.visit_bool(true),
+            Some(value) => match attrs::parse_bool(value) {
+                Some(value) => visitor.visit_bool(value),
+                None => Err(Error::Message("expected true or false".to_string())),
+            },
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.value()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_bytes(self.value()?.as_bytes())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    /// A given attribute is always `Some`; missing ones are handled by the
+    /// caller.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let items: Vec<&'de str> = self
+            .values
+            .iter()
+            .flatten()
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect();
+        visitor.visit_seq(Items {
+            name: self.name,
+            items: items.into_iter(),
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    /// Unit variants, named by the trimmed value.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(BorrowedStrDeserializer::new(self.value()?.trim()))
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        map struct
+    }
+}
+
+/// Sequence access over the items of a list value.
+struct Items<'de> {
+    name: &'de str,
+    items: std::vec::IntoIter<&'de str>,
+}
+
+impl<'de> SeqAccess<'de> for Items<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let Some(item) = self.items.next() else {
+            return Ok(None);
+        };
+        let value = ValueDeserializer {
+            name: self.name,
+            values: vec![Some(item)],
+        };
+        seed.deserialize(value)
+            .map(Some)
+            .map_err(|err| err.at(self.name, Some(item)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Align {
+        Left,
+        Center,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Video<'a> {
+        id: u32,
+        title: &'a str,
+        autoplay: bool,
+        #[serde(default)]
+        muted: bool,
+        volume: Option<f32>,
+        align: Option<Align>,
+        #[serde(default)]
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn test_struct() {
+        let attrs = [
+            ("id", Some(" 7 ")),
+            ("title", Some("Intro")),
+            ("autoplay", None),
+            ("align", Some("center")),
+            ("tags", Some("a, b")),
+            ("tags", Some("c")),
+        ];
+        let video: Video = ShortcodeAttrs::new(&attrs).deserialize().unwrap();
+        assert_eq!(
+            video,
+            Video {
+                id: 7,
+                title: "Intro",
+                autoplay: true,
+                muted: false,
+                volume: None,
+                align: Some(Align::Center),
+                tags: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let deserialize = |attrs: &[(&str, Option<&str>)]| {
+            ShortcodeAttrs::new(attrs)
+                .deserialize::<Video>()
+                .unwrap_err()
+        };
+        assert_eq!(
+            deserialize(&[("title", Some("x")), ("autoplay", None)]),
+            AttrError::Missing {
+                name: "id".to_string()
+            }
+        );
+        assert_eq!(
+            deserialize(&[("id", None)]),
+            AttrError::MissingValue {
+                name: "id".to_string()
+            }
+        );
+        assert_eq!(
+            deserialize(&[
+                ("id", Some("1")),
+                ("tags", Some("x")),
+                ("align", Some("up"))
+            ])
+            .name(),
+            "align"
+        );
+        assert_eq!(
+            deserialize(&[("id", Some("x"))]),
+            AttrError::Invalid {
+                name: "id".to_string(),
+                value: "x".to_string(),
+                reason: "invalid digit found in string".to_string(),
+            }
+        );
+        assert!(matches!(
+            ShortcodeAttrs::new(&[]).deserialize::<u32>(),
+            Err(AttrError::Other { .. })
+        ));
+    }
+
+    #[test]
+    fn test_case_and_unknown() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Link {
+            href: String,
+        }
+
+        let attrs = [("HREF", Some("/a"))];
+        let link = ShortcodeAttrs::new(&attrs)
+            .with_case(CaseMatching::Ascii)
+            .deserialize::<Link>();
+        assert_eq!(
+            link,
+            Ok(Link {
+                href: "/a".to_string()
+            })
+        );
+        assert_eq!(
+            ShortcodeAttrs::new(&attrs).deserialize::<Link>(),
+            Err(AttrError::Unknown {
+                name: "HREF".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_many_attrs() {
+        // Each attribute would otherwise be compared with every earlier one.
+        let names: Vec<String> = (0..40_000).map(|i| format!("f{i}")).collect();
+        let attrs: Vec<(&str, Option<&str>)> = names
+            .iter()
+            .map(|name| (name.as_str(), Some("1")))
+            .collect();
+        let map = ShortcodeAttrs::new(&attrs)
+            .deserialize::<std::collections::HashMap<String, u32>>()
+            .unwrap();
+        assert_eq!(map.len(), 40_000);
+        assert_eq!(map["f39999"], 1);
+    }
+}
@@ -0,0 +1,398 @@
+use crate::attrs::{self, AttrError, DuplicateAttrs, ShortcodeAttrs};
+use crate::code::RenderNode;
+use crate::context::Context;
+use crate::interpolate::UndefinedVars;
+use crate::parser::Parser;
+use crate::renderer::Renderer;
+use crate::schema::Violation;
+use crate::shortcode::Shortcode;
+use crate::syntax::Delimiters;
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+/// A problem found in shortcode input, with where it occurs.
+///
+/// Returned by [`Shortcode::validate`](crate::shortcode::Shortcode::validate).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Byte range in the input, if the problem can be located there.
+    pub span: Option<Range<usize>>,
+}
+
+impl Diagnostic {
+    pub(crate) fn error(message: impl Into<String>, span: Option<Range<usize>>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            span,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)?;
+        if let Some(span) = &self.span {
+            write!(f, " at {}..{}", span.start, span.end)?;
+        }
+        Ok(())
+    }
+}
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Severity {
+    /// The input does not render as written.
+    Error,
+    /// The input renders, but probably not as intended.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// Reports repeated attributes on every tag in `content`, variables in the
+/// attributes of tags with a handler that are undefined in `ctx`, and
+/// checks each tag that has a schema, in source order.
+pub(crate) fn validate(shortcodes: &Shortcode, content: &str, ctx: &Context) -> Vec<Diagnostic> {
+    let syntax = shortcodes.syntax();
+    let renderer = Renderer::with_matching(
+        Parser::with_syntax(content, syntax),
+        syntax.dialect,
+        shortcodes.case_matching(),
+    );
+    let case = shortcodes.name_matching();
+    let locate = Locate {
+        source: content,
+        delimiters: &syntax.delimiters,
+    };
+
+    let mut diagnostics = Vec::new();
+    let mut pending = vec![renderer.nodes()];
+    while let Some(nodes) = pending.pop() {
+        for node in nodes {
+            if let Some(children) = node.children() {
+                pending.push(children);
+            }
+            let Some(name) = node.opening_name() else {
+                continue;
+            };
+            let interpolated;
+            let resolved: Vec<(&str, Option<&str>)>;
+            let mut attrs = node.attrs();
+            let policy = shortcodes.duplicate_attrs();
+            for index in attrs::repeated(&attrs, case) {
+                let error = AttrError::Duplicate {
+                    name: attrs[index].0.to_string(),
+                };
+                diagnostics.push(Diagnostic {
+                    severity: match policy {
+                        DuplicateAttrs::Error => Severity::Error,
+                        _ => Severity::Warning,
+                    },
+                    message: format!("{error} in `{name}`"),
+                    span: locate
+                        .attr(&attrs[index])
+                        .or_else(|| locate.tag(name, &attrs)),
+                });
+            }
+
+            let interpolation = shortcodes.interpolation();
+            if let Some(interpolation) = interpolation.filter(|_| shortcodes.lookup(name).is_some())
+            {
+                let (values, undefined) = interpolation.apply(&attrs, ctx);
+                for var in undefined {
+                    let span = attrs[var.attr]
+                        .1
+                        .and_then(|value| locate.offset(value))
+                        .map(|start| start + var.span.start..start + var.span.end);
+                    diagnostics.push(Diagnostic {
+                        severity: match interpolation.undefined_vars() {
+                            UndefinedVars::Error => Severity::Error,
+                            _ => Severity::Warning,
+                        },
+                        message: format!("undefined variable `{}` in `{name}`", var.name),
+                        span: span.or_else(|| locate.tag(name, &attrs)),
+                    });
+                }
+                interpolated = values;
+                resolved = interpolated
+                    .iter()
+                    .map(|(name, value)| (*name, value.as_deref()))
+                    .collect();
+                attrs = Cow::Borrowed(&resolved[..]);
+            }
+
+            let Some(schema) = shortcodes.schema(name) else {
+                continue;
+            };
+            // The schema sees what the handler would.
+            let _ = policy.apply(&mut attrs, case);
+            // Attributes hidden from the handler are not checked either.
+            if let Some(defaults) = shortcodes.defaults(name) {
+                attrs.to_mut().retain(|(key, _)| !defaults.drops(key, case));
+            }
+            let checked = ShortcodeAttrs::new(&attrs).with_case(case);
+            for violation in schema.check(&checked, node.children().is_some()) {
+                diagnostics.push(match violation {
+                    Violation::Attr(error) => {
+                        let span = match error {
+                            AttrError::Missing { .. } => None,
+                            _ => attrs
+                                .iter()
+                                .find(|(key, _)| case.eq(key, error.name()))
+                                .and_then(|attr| locate.attr(attr)),
+                        };
+                        let span = span.or_else(|| locate.tag(name, &attrs));
+                        Diagnostic::error(format!("{error} in `{name}`"), span)
+                    }
+                    Violation::Content => Diagnostic::error(
+                        format!("`{name}` does not take content"),
+                        locate.tag(name, &attrs),
+                    ),
+                });
+            }
+        }
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.as_ref().map(|span| span.start));
+    diagnostics
+}
+
+/// Finds tokens in the source they were parsed from.
+struct Locate<'s> {
+    source: &'s str,
+    delimiters: &'s Delimiters,
+}
+
+impl Locate<'_> {
+    /// Byte offset of `part` if it is a slice of the source.
+    fn offset(&self, part: &str) -> Option<usize> {
+        let start = (part.as_ptr() as usize).checked_sub(self.source.as_ptr() as usize)?;
+        (start + part.len() <= self.source.len()).then_some(start)
+    }
+
+    /// An attribute from its name through its value and closing quote.
+    fn attr(&self, (key, value): &(&str, Option<&str>)) -> Option<Range<usize>> {
+        let key = self.offset(key).map(|start| start..start + key.len());
+        let value = value
+            .and_then(|value| Some(self.offset(value)?..self.offset(value)? + value.len()))
+            .map(|span| match self.source[span.end..].chars().next() {
+                Some(quote @ ('"' | '\'')) => span.start..span.end + quote.len_utf8(),
+                _ => span,
+            });
+        match (key, value) {
+            (Some(key), Some(value)) => Some(key.start..value.end),
+            (key, value) => key.or(value),
+        }
+    }
+
+    /// The opening tag `name` with `attrs`, delimiters included.
+    fn tag(&self, name: &str, attrs: &[(&str, Option<&str>)]) -> Option<Range<usize>> {
+        let name_start = self.offset(name)?;
+        let start = self.source[..name_start].rfind(self.delimiters.open())?;
+        let after = attrs
+            .iter()
+            .filter_map(|attr| self.attr(attr))
+            .map(|span| span.end)
+            .max()
+            .unwrap_or(name_start + name.len());
+        let end = after + self.source[after..].find(self.delimiters.close())?;
+        Some(start..end + self.delimiters.close().len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{AttrSpec, AttrType, Schema};
+    use crate::syntax::{CaseMatching, Syntax};
+
+    fn registry() -> Shortcode {
+        let mut sc = Shortcode::new();
+        sc.add("video", |_, _| String::new());
+        sc.set_schema(
+            "video",
+            Schema::new()
+                .attr(AttrSpec::new("id", AttrType::Text).required())
+                .attr(AttrSpec::new("width", AttrType::Integer))
+                .content(false),
+        );
+        sc
+    }
+
+    #[test]
+    fn test_validate_spans() {
+        let input = "a [video id=\"x\" width=\"wide\"] b [video]c[/video] [other size=\"1\"]";
+        let diagnostics = registry().validate(input);
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.message.as_str(), &input[d.span.clone().unwrap()]))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    "attribute `width` has invalid value \"wide\": invalid digit found in string in `video`",
+                    "width=\"wide\""
+                ),
+                ("missing required attribute `id` in `video`", "[video]"),
+                ("`video` does not take content", "[video]"),
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+    }
+
+    #[test]
+    fn test_validate_nested_and_case() {
+        let mut sc = registry();
+        sc.add("box", |content, _| content.unwrap_or("").to_string());
+        sc.set_case_matching(CaseMatching::Ascii);
+        let input = "[box][VIDEO ID=\"1\" Height=\"2\"][/box]";
+        let diagnostics = sc.validate(input);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "error: unknown attribute `Height` in `VIDEO` at 19..29"
+        );
+    }
+
+    #[test]
+    fn test_validate_other_delimiters() {
+        let mut sc = registry();
+        let mut syntax = Syntax::new();
+        syntax.delimiters = Delimiters::HUGO.padded(true);
+        sc.set_syntax(syntax);
+        let input = "x {{< video width=\"3\" >}}";
+        let diagnostics = sc.validate(input);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            &input[diagnostics[0].span.clone().unwrap()],
+            "{{< video width=\"3\" >}}"
+        );
+    }
+
+    #[test]
+    fn test_validate_bbcode_value() {
+        let mut sc = Shortcode::new();
+        sc.set_syntax(Syntax::bbcode());
+        sc.add("size", |content, _| content.unwrap_or("").to_string());
+        sc.set_schema(
+            "size",
+            Schema::new().attr(AttrSpec::new("", AttrType::Integer).required()),
+        );
+        let input = "[size=big]x[/size]";
+        let diagnostics = sc.validate(input);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(&input[diagnostics[0].span.clone().unwrap()], "big");
+    }
+
+    #[test]
+    fn test_validate_duplicates() {
+        let mut sc = registry();
+        let input = "[video id=\"1\" id=\"2\"] [other a=\"1\" a b=\"2\"]";
+        let found = |sc: &Shortcode| {
+            sc.validate(input)
+                .into_iter()
+                .map(|d| (d.severity, d.message, &input[d.span.unwrap()]))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            found(&sc),
+            [
+                (
+                    Severity::Warning,
+                    "attribute `id` is given more than once in `video`".to_string(),
+                    "id=\"2\""
+                ),
+                (
+                    Severity::Warning,
+                    "attribute `a` is given more than once in `other`".to_string(),
+                    "a"
+                ),
+            ]
+        );
+
+        sc.set_duplicate_attrs(DuplicateAttrs::Error);
+        assert!(found(&sc)
+            .iter()
+            .all(|(severity, _, _)| *severity == Severity::Error));
+    }
+
+    #[test]
+    fn test_validate_interpolation() {
+        use crate::interpolate::Interpolation;
+
+        let mut sc = registry();
+        sc.set_interpolation(Interpolation::new().undefined(UndefinedVars::Error));
+        let mut ctx = Context::new();
+        ctx.insert("w", "640");
+        ctx.insert("h", "tall");
+
+        // The schema checks resolved values, and unknown tags are skipped.
+        let input = "[video id=$id width={w}] [video id=\"a\" width=$h] [other x=$y]";
+        let found: Vec<_> = sc
+            .validate_with(input, &ctx)
+            .into_iter()
+            .map(|d| (d.severity, d.message, &input[d.span.unwrap()]))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    Severity::Error,
+                    "undefined variable `id` in `video`".to_string(),
+                    "$id"
+                ),
+                (
+                    Severity::Error,
+                    "attribute `width` has invalid value \"tall\": invalid digit found in string in `video`".to_string(),
+                    "width"
+                ),
+            ]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let diagnostic = Diagnostic {
+            severity: Severity::Warning,
+            message: "attribute `id` is given more than once in `video`".to_string(),
+            span: Some(14..20),
+        };
+        let json = serde_json::to_string(&diagnostic).unwrap();
+        assert_eq!(
+            json,
+            r#"{"severity":"warning","message":"attribute `id` is given more than once in `video`","span":{"start":14,"end":20}}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Diagnostic>(&json).unwrap(),
+            diagnostic
+        );
+
+        let attrs = [("id", Some("1")), ("autoplay", None)];
+        assert_eq!(
+            serde_json::to_string(&ShortcodeAttrs::new(&attrs)).unwrap(),
+            r#"[["id","1"],["autoplay",null]]"#
+        );
+        let err = AttrError::Duplicate {
+            name: "id".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_string(&err).unwrap(),
+            r#"{"duplicate":{"name":"id"}}"#
+        );
+    }
+}
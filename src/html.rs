@@ -0,0 +1,290 @@
+//! HTML escaping helpers and trusted-markup typing.
+//!
+//! Handlers that build HTML from attribute values or content must escape
+//! them for the place they end up in: [`escape_text`] for element content,
+//! [`escape_attr`] for quoted attribute values and [`escape_url`] for `href`
+//! and `src` attributes. [`Markup`] keeps track of which parts are already
+//! safe.
+//!
+//! ```rust
+//! use shortcode_parser::html::{escape_attr, escape_url};
+//! use shortcode_parser::shortcode::Shortcode;
+//!
+//! let mut sc = Shortcode::new();
+//! sc.add("link", |content, attrs| {
+//!     format!(
+//!         "<a href=\"{}\" title=\"{}\">{}</a>",
+//!         escape_url(attrs.get("href").unwrap_or("")),
+//!         escape_attr(attrs.get("title").unwrap_or("")),
+//!         content.unwrap_or(""),
+//!     )
+//! });
+//!
+//! assert_eq!(
+//!     sc.render("[link href=\"javascript:alert(1)\" title='\"><script>']x[/link]"),
+//!     "<a href=\"\" title=\"&quot;&gt;&lt;script&gt;\">x</a>"
+//! );
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+
+/// How the renderer treats text that did not come from a handler.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Text between shortcodes is copied to the output as written.
+    #[default]
+    Raw,
+    /// Text between shortcodes, and unknown tags, are untrusted and
+    /// HTML-escaped. Handler output is trusted markup and inserted as is, so
+    /// the content passed to enclosing handlers is already escaped.
+    Html,
+}
+
+impl OutputMode {
+    /// Prepares untrusted source text for the output.
+    pub(crate) fn text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            OutputMode::Raw => Cow::Borrowed(text),
+            OutputMode::Html => escape_text(text),
+        }
+    }
+}
+
+/// Escapes `&`, `<` and `>` so `text` can be placed in element content.
+///
+/// ```rust
+/// use shortcode_parser::html::escape_text;
+/// assert_eq!(escape_text("a < b & c"), "a &lt; b &amp; c");
+/// assert_eq!(escape_text("plain"), "plain");
+/// ```
+pub fn escape_text(text: &str) -> Cow<'_, str> {
+    escape(text, |c| match c {
+        '&' => Some("&amp;"),
+        '<' => Some("&lt;"),
+        '>' => Some("&gt;"),
+        _ => None,
+    })
+}
+
+/// Escapes `text` for a single- or double-quoted attribute value.
+///
+/// ```rust
+/// use shortcode_parser::html::escape_attr;
+/// assert_eq!(escape_attr("\" onload=\"x"), "&quot; onload=&quot;x");
+/// assert_eq!(escape_attr("it's"), "it&#39;s");
+/// ```
+pub fn escape_attr(text: &str) -> Cow<'_, str> {
+    escape(text, |c| match c {
+        '&' => Some("&amp;"),
+        '<' => Some("&lt;"),
+        '>' => Some("&gt;"),
+        '"' => Some("&quot;"),
+        '\'' => Some("&#39;"),
+        _ => None,
+    })
+}
+
+/// URL schemes [`escape_url`] lets through. Relative URLs are always allowed.
+const ALLOWED_SCHEMES: &[&str] = &["http", "https", "mailto", "tel", "ftp"];
+
+/// Makes `url` safe for a quoted `href` or `src` attribute.
+///
+/// URLs with a scheme other than `http`, `https`, `mailto`, `tel` or `ftp`
+/// (e.g. `javascript:`) become empty. Spaces, quotes, angle brackets and
+/// non-ASCII characters are percent-encoded and `&` becomes `&amp;`, so the
+/// result needs no further escaping.
+///
+/// ```rust
+/// use shortcode_parser::html::escape_url;
+/// assert_eq!(escape_url("/search?q=a b&page=2"), "/search?q=a%20b&amp;page=2");
+/// assert_eq!(escape_url("https://example.com/ü"), "https://example.com/%C3%BC");
+/// assert_eq!(escape_url(" JavaScript:alert(1)"), "");
+/// ```
+pub fn escape_url(url: &str) -> Cow<'_, str> {
+    if let Some((scheme, _)) = url.split_once(':') {
+        // A `/`, `?` or `#` before the colon means it is part of a path.
+        let is_scheme = !scheme.contains(['/', '?', '#']);
+        // Browsers ignore whitespace and control characters in the scheme.
+        let scheme: String = scheme
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+            .collect();
+        if is_scheme
+            && !ALLOWED_SCHEMES
+                .iter()
+                .any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+        {
+            return Cow::Borrowed("");
+        }
+    }
+
+    let needs_escape =
+        |b: u8| !b.is_ascii_graphic() || matches!(b, b'"' | b'\'' | b'<' | b'>' | b'`' | b'\\');
+    if !url.bytes().any(|b| needs_escape(b) || b == b'&') {
+        return Cow::Borrowed(url);
+    }
+
+    let mut escaped = String::with_capacity(url.len() + 8);
+    for b in url.bytes() {
+        if b == b'&' {
+            escaped.push_str("&amp;");
+        } else if needs_escape(b) {
+            escaped.push_str(&format!("%{b:02X}"));
+        } else {
+            escaped.push(b as char);
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Replaces every character for which `entity` returns a replacement.
+fn escape(text: &str, entity: impl Fn(char) -> Option<&'static str>) -> Cow<'_, str> {
+    let Some(first) = text.find(|c| entity(c).is_some()) else {
+        return Cow::Borrowed(text);
+    };
+
+    let mut escaped = String::with_capacity(text.len() + 8);
+    escaped.push_str(&text[..first]);
+    for c in text[first..].chars() {
+        match entity(c) {
+            Some(replacement) => escaped.push_str(replacement),
+            None => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// A string of trusted HTML.
+///
+/// Untrusted text only enters through [`Markup::text`], [`Markup::push_text`]
+/// and [`Markup::push_attr`], which escape it, so a handler that builds its
+/// output as `Markup` keeps literal markup and author input apart.
+///
+/// ```rust
+/// use shortcode_parser::html::Markup;
+/// use shortcode_parser::shortcode::Shortcode;
+///
+/// let mut sc = Shortcode::new();
+/// sc.add("b", |_, attrs| {
+///     let mut html = Markup::trusted("<b title=\"");
+///     html.push_attr(attrs.get("title").unwrap_or(""));
+///     html.push_trusted("\">");
+///     html.push_text(attrs.get("text").unwrap_or(""));
+///     html.push_trusted("</b>");
+///     html.into()
+/// });
+///
+/// assert_eq!(
+///     sc.render("[b title='\"' text=\"<i>\"]"),
+///     "<b title=\"&quot;\">&lt;i&gt;</b>"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Markup(String);
+
+impl Markup {
+    /// Wraps markup that is already safe, e.g. a literal or a handler's own output.
+    pub fn trusted(html: impl Into<String>) -> Self {
+        Self(html.into())
+    }
+
+    /// Escapes untrusted `text` into markup.
+    pub fn text(text: &str) -> Self {
+        Self(escape_text(text).into_owned())
+    }
+
+    /// Appends trusted markup.
+    pub fn push_trusted(&mut self, html: &str) {
+        self.0.push_str(html);
+    }
+
+    /// Appends untrusted text as element content, escaping it.
+    pub fn push_text(&mut self, text: &str) {
+        self.0.push_str(&escape_text(text));
+    }
+
+    /// Appends an untrusted quoted attribute value, escaping it.
+    pub fn push_attr(&mut self, value: &str) {
+        self.0.push_str(&escape_attr(value));
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for Markup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<Markup> for String {
+    fn from(markup: Markup) -> Self {
+        markup.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_text_borrows_when_clean() {
+        assert!(matches!(escape_text("hello"), Cow::Borrowed("hello")));
+        assert!(matches!(escape_attr("it is"), Cow::Borrowed(_)));
+        assert!(matches!(escape_url("/a/b?c=d"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_escape_text() {
+        assert_eq!(escape_text("<script>&\"'"), "&lt;script&gt;&amp;\"'");
+        assert_eq!(escape_text("世界 <b>"), "世界 &lt;b&gt;");
+    }
+
+    #[test]
+    fn test_escape_attr() {
+        assert_eq!(
+            escape_attr("1\"/><script>alert('x')</script>"),
+            "1&quot;/&gt;&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;"
+        );
+    }
+
+    #[test]
+    fn test_escape_url_schemes() {
+        assert_eq!(escape_url("https://example.com"), "https://example.com");
+        assert_eq!(escape_url("mailto:a@b.c"), "mailto:a@b.c");
+        assert_eq!(escape_url("javascript:alert(1)"), "");
+        assert_eq!(escape_url("java\tscript:alert(1)"), "");
+        assert_eq!(escape_url("DATA:text/html,x"), "");
+        assert_eq!(escape_url("vbscript:x"), "");
+        // A colon after a path separator is not a scheme.
+        assert_eq!(escape_url("/a:b"), "/a:b");
+        assert_eq!(escape_url("?next=javascript:x"), "?next=javascript:x");
+    }
+
+    #[test]
+    fn test_escape_url_encodes() {
+        assert_eq!(
+            escape_url("/x\"onmouseover='y'"),
+            "/x%22onmouseover=%27y%27"
+        );
+        assert_eq!(escape_url("/<a>`\\"), "/%3Ca%3E%60%5C");
+        assert_eq!(escape_url("/a\nb"), "/a%0Ab");
+    }
+
+    #[test]
+    fn test_markup() {
+        let mut markup = Markup::text("<a>");
+        markup.push_trusted("<br>");
+        markup.push_text("&");
+        assert_eq!(markup.as_str(), "&lt;a&gt;<br>&amp;");
+        assert_eq!(markup.to_string(), "&lt;a&gt;<br>&amp;");
+        assert_eq!(String::from(markup), "&lt;a&gt;<br>&amp;");
+    }
+}
@@ -0,0 +1,372 @@
+use crate::context::Context;
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Looks up the variables named in attribute values.
+///
+/// Any `Fn(&str, &Context) -> Option<String>` closure is a resolver. The
+/// default, [`ContextResolver`], reads the render [`Context`].
+pub trait Resolver: Send + Sync {
+    /// The value of `name`, or `None` if it is undefined.
+    fn resolve(&self, name: &str, ctx: &Context) -> Option<String>;
+}
+
+impl<F> Resolver for F
+where
+    F: Fn(&str, &Context) -> Option<String> + Send + Sync,
+{
+    fn resolve(&self, name: &str, ctx: &Context) -> Option<String> {
+        self(name, ctx)
+    }
+}
+
+/// Resolves `$user.name` to the context value stored under `"user.name"`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContextResolver;
+
+impl Resolver for ContextResolver {
+    fn resolve(&self, name: &str, ctx: &Context) -> Option<String> {
+        ctx.get(name).map(str::to_string)
+    }
+}
+
+/// What an undefined variable expands to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UndefinedVars {
+    /// Nothing.
+    #[default]
+    Empty,
+    /// The placeholder as written.
+    Keep,
+    /// `try_render` fails with
+    /// [`RenderError::UndefinedVariable`](crate::RenderError::UndefinedVariable),
+    /// and `render` writes the tag back as source.
+    Error,
+}
+
+/// Replaces variables in attribute values before handlers see them.
+///
+/// Enable it with [`Shortcode::set_interpolation`](crate::shortcode::Shortcode::set_interpolation).
+/// A value may name variables as `{site_url}` anywhere in the text, or as
+/// `$user.name`: a `$` followed by identifiers joined with dots. `\{` and
+/// `\$` stand for a literal `{` and `$`; a brace or dollar sign that does
+/// not start a variable is kept as is, so JSON values pass through.
+///
+/// Resolved values are inserted verbatim. With
+/// [`Shortcode::set_escape_attrs`](crate::shortcode::Shortcode::set_escape_attrs)
+/// they are HTML-escaped along with the rest of the value.
+///
+/// ```rust
+/// use shortcode_parser::shortcode::Shortcode;
+/// use shortcode_parser::{Context, Interpolation};
+///
+/// let mut sc = Shortcode::new();
+/// sc.add("link", |_, attrs| format!("<a href=\"{}\">", attrs.get("href").unwrap()));
+/// sc.set_interpolation(Interpolation::new());
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("site_url", "https://example.com");
+/// assert_eq!(
+///     sc.render_with("[link href=\"{site_url}/about\"]", &ctx),
+///     "<a href=\"https://example.com/about\">"
+/// );
+/// ```
+#[derive(Clone)]
+pub struct Interpolation {
+    resolver: Arc<dyn Resolver>,
+    undefined: UndefinedVars,
+}
+
+impl fmt::Debug for Interpolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interpolation")
+            .field("undefined", &self.undefined)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Interpolation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpolation {
+    /// Interpolation from the render [`Context`], expanding undefined
+    /// variables to nothing.
+    pub fn new() -> Self {
+        Self {
+            resolver: Arc::new(ContextResolver),
+            undefined: UndefinedVars::Empty,
+        }
+    }
+
+    /// Looks variables up with `resolver` instead of the render context.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use shortcode_parser::{Context, Interpolation};
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("greet", |_, attrs| format!("Hi {}", attrs.get("name").unwrap()));
+    /// sc.set_interpolation(Interpolation::new().resolver(|name: &str, _: &Context| {
+    ///     (name == "user.name").then(|| "Ada".to_string())
+    /// }));
+    /// assert_eq!(sc.render("[greet name=$user.name]"), "Hi Ada");
+    /// ```
+    pub fn resolver(mut self, resolver: impl Resolver + 'static) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    /// Sets what undefined variables expand to.
+    pub fn undefined(mut self, undefined: UndefinedVars) -> Self {
+        self.undefined = undefined;
+        self
+    }
+
+    /// What undefined variables expand to.
+    pub fn undefined_vars(&self) -> UndefinedVars {
+        self.undefined
+    }
+
+    /// Interpolates every attribute value. Also returns the undefined
+    /// variables, in order.
+    pub(crate) fn apply<'v>(
+        &self,
+        attrs: &[(&'v str, Option<&'v str>)],
+        ctx: &Context,
+    ) -> (Vec<Interpolated<'v>>, Vec<UndefinedVar<'v>>) {
+        let mut undefined = Vec::new();
+        let attrs = attrs
+            .iter()
+            .enumerate()
+            .map(|(index, (name, value))| {
+                let value = value.map(|value| {
+                    self.expand(value, ctx, |var| {
+                        undefined.push(UndefinedVar { attr: index, ..var })
+                    })
+                });
+                (*name, value)
+            })
+            .collect();
+        (attrs, undefined)
+    }
+
+    /// Interpolates `value`, reporting undefined variables to `on_undefined`.
+    fn expand<'v>(
+        &self,
+        value: &'v str,
+        ctx: &Context,
+        mut on_undefined: impl FnMut(UndefinedVar<'v>),
+    ) -> Cow<'v, str> {
+        let parts = parts(value);
+        if let [Part::Text(text)] = parts.as_slice() {
+            return Cow::Borrowed(text);
+        }
+        let mut expanded = String::with_capacity(value.len());
+        for part in parts {
+            match part {
+                Part::Text(text) => expanded.push_str(text),
+                Part::Var { name, span } => match self.resolver.resolve(name, ctx) {
+                    Some(resolved) => expanded.push_str(&resolved),
+                    None => {
+                        if self.undefined == UndefinedVars::Keep {
+                            expanded.push_str(&value[span.clone()]);
+                        }
+                        on_undefined(UndefinedVar {
+                            attr: 0,
+                            name,
+                            span,
+                        });
+                    }
+                },
+            }
+        }
+        Cow::Owned(expanded)
+    }
+}
+
+/// An attribute pair with its value interpolated.
+pub(crate) type Interpolated<'v> = (&'v str, Option<Cow<'v, str>>);
+
+/// A variable the resolver did not know.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UndefinedVar<'v> {
+    /// Index of the attribute whose value names it.
+    pub(crate) attr: usize,
+    pub(crate) name: &'v str,
+    /// Where the placeholder is in the value.
+    pub(crate) span: Range<usize>,
+}
+
+/// A run of literal text or a variable in an attribute value.
+#[derive(Debug, PartialEq, Eq)]
+enum Part<'v> {
+    Text(&'v str),
+    Var { name: &'v str, span: Range<usize> },
+}
+
+/// Splits `value` into literal text and variables. Escapes become the
+/// single character they stand for.
+fn parts(value: &str) -> Vec<Part<'_>> {
+    let bytes = value.as_bytes();
+    let mut parts = Vec::new();
+    let mut text = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let found = match bytes[i] {
+            b'\\' if matches!(bytes.get(i + 1), Some(b'{' | b'$')) => {
+                Some((Part::Text(&value[i + 1..i + 2]), i + 2))
+            }
+            b'{' => {
+                let name = identifier(&value[i + 1..]);
+                let end = i + 1 + name.len();
+                (!name.is_empty() && bytes.get(end) == Some(&b'}')).then(|| {
+                    let span = i..end + 1;
+                    (Part::Var { name, span }, end + 1)
+                })
+            }
+            b'$' => {
+                let name = identifier(&value[i + 1..]);
+                let end = i + 1 + name.len();
+                (!name.is_empty()).then_some((Part::Var { name, span: i..end }, end))
+            }
+            _ => None,
+        };
+        match found {
+            Some((part, next)) => {
+                if text < i {
+                    parts.push(Part::Text(&value[text..i]));
+                }
+                parts.push(part);
+                i = next;
+                text = next;
+            }
+            None => i += 1,
+        }
+    }
+    if text < bytes.len() || parts.is_empty() {
+        parts.push(Part::Text(&value[text..]));
+    }
+    parts
+}
+
+/// The longest prefix of `text` made of identifiers joined with dots, as in
+/// `user.name`. Identifiers are ASCII letters, digits and `_`, not starting
+/// with a digit.
+fn identifier(text: &str) -> &str {
+    let bytes = text.as_bytes();
+    let mut end = 0;
+    let mut i = 0;
+    while matches!(bytes.get(i), Some(b) if b.is_ascii_alphabetic() || *b == b'_') {
+        i += bytes[i..]
+            .iter()
+            .take_while(|b| b.is_ascii_alphanumeric() || **b == b'_')
+            .count();
+        end = i;
+        if bytes.get(i) != Some(&b'.') {
+            break;
+        }
+        i += 1;
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> Context {
+        let mut ctx = Context::new();
+        ctx.insert("site_url", "https://x.test");
+        ctx.insert("user.name", "Ada");
+        ctx
+    }
+
+    #[test]
+    fn test_identifier() {
+        assert_eq!(identifier("user.name!"), "user.name");
+        assert_eq!(identifier("user."), "user");
+        assert_eq!(identifier("a_1.b2.c"), "a_1.b2.c");
+        assert_eq!(identifier("1a"), "");
+        assert_eq!(identifier(".a"), "");
+        assert_eq!(identifier(""), "");
+    }
+
+    #[test]
+    fn test_parts() {
+        assert_eq!(parts(""), [Part::Text("")]);
+        assert_eq!(parts("plain"), [Part::Text("plain")]);
+        assert_eq!(
+            parts("{a}/$b.c."),
+            [
+                Part::Var {
+                    name: "a",
+                    span: 0..3
+                },
+                Part::Text("/"),
+                Part::Var {
+                    name: "b.c",
+                    span: 4..8
+                },
+                Part::Text("."),
+            ]
+        );
+        assert_eq!(
+            parts("\\{a} \\$5 $5 \\n {\"k\":{\"j\":1}}"),
+            [
+                Part::Text("{"),
+                Part::Text("a} "),
+                Part::Text("$"),
+                Part::Text("5 $5 \\n {\"k\":{\"j\":1}}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand() {
+        let interpolation = Interpolation::new();
+        let mut undefined = Vec::new();
+        let mut expand = |value| {
+            interpolation
+                .expand(value, &ctx(), |var| undefined.push(var.name))
+                .into_owned()
+        };
+        assert_eq!(expand("{site_url}/about"), "https://x.test/about");
+        assert_eq!(expand("Hi $user.name!"), "Hi Ada!");
+        assert_eq!(expand("\\{site_url} costs \\$x"), "{site_url} costs $x");
+        assert_eq!(expand("[{nope}|$nope.x]"), "[|]");
+        assert_eq!(undefined, ["nope", "nope.x"]);
+        assert!(matches!(
+            interpolation.expand("no vars", &ctx(), |_| {}),
+            Cow::Borrowed("no vars")
+        ));
+    }
+
+    #[test]
+    fn test_apply_keep() {
+        let interpolation = Interpolation::new().undefined(UndefinedVars::Keep);
+        let (attrs, undefined) = interpolation.apply(
+            &[("flag", None), ("href", Some("{base}/$user.name"))],
+            &ctx(),
+        );
+        assert_eq!(
+            attrs,
+            [
+                ("flag", None),
+                ("href", Some(Cow::Owned("{base}/Ada".to_string())))
+            ]
+        );
+        assert_eq!(
+            undefined,
+            [UndefinedVar {
+                attr: 1,
+                name: "base",
+                span: 0..6
+            }]
+        );
+    }
+}
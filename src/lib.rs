@@ -1,9 +1,41 @@
+mod args;
+mod ast;
 mod attrs;
+pub mod bbcode;
+mod builder;
 mod code;
+mod context;
+#[cfg(feature = "serde")]
+mod de;
+mod diagnostics;
+pub mod html;
+mod interpolate;
+mod limits;
 mod parser;
+mod regions;
 mod renderer;
+mod scan;
+mod schema;
 pub mod shortcode;
+mod syntax;
+mod template;
 mod token;
 mod tokenizer;
 
-pub use attrs::ShortcodeAttrs;
+pub use args::ShortcodeArgs;
+pub use ast::{Children, Document, Node, SourceError, Tag};
+pub use attrs::{AttrError, DuplicateAttrs, ShortcodeAttrs};
+pub use builder::ShortcodeBuilder;
+pub use context::Context;
+pub use diagnostics::{Diagnostic, Severity};
+pub use interpolate::{ContextResolver, Interpolation, Resolver, UndefinedVars};
+pub use limits::{LimitFallback, RenderError, RenderLimits};
+pub use parser::Parser;
+pub use schema::{AttrSpec, AttrType, Defaults, Schema};
+pub use syntax::{CaseMatching, ContentMode, Delimiters, Dialect, HtmlRegions, Syntax};
+pub use template::CompiledTemplate;
+pub use token::Token;
+pub use tokenizer::{TokenSegment, Tokenizer};
+
+#[cfg(feature = "derive")]
+pub use shortcode_parser_derive::ShortcodeArgs;
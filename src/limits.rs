@@ -0,0 +1,212 @@
+use crate::attrs::AttrError;
+use std::error::Error;
+use std::fmt;
+
+/// Bounds applied while rendering, to contain hostile input and runaway handlers.
+///
+/// Every limit except `max_reexpansion_depth` defaults to unbounded. When a limit is exceeded, the
+/// `try_render*` methods return a [`RenderError`] and the `render*` methods
+/// return the configured [`LimitFallback`] instead.
+///
+/// A tag its handler or schema rejects does not exceed a limit: `render`
+/// writes that tag back as source and expands the rest of the input.
+///
+/// Example:
+/// ```rust
+/// use shortcode_parser::shortcode::Shortcode;
+/// use shortcode_parser::{LimitFallback, RenderError, RenderLimits};
+///
+/// let mut sc = Shortcode::new();
+/// sc.add("x", |content, _| content.unwrap_or("").to_string());
+/// sc.set_limits(RenderLimits {
+///     max_depth: 2,
+///     fallback: LimitFallback::Text("[content too complex]".to_string()),
+///     ..RenderLimits::default()
+/// });
+///
+/// assert_eq!(sc.render("[x][x]ok[/x][/x]"), "ok");
+/// assert_eq!(sc.render("[x][x][x]no[/x][/x][/x]"), "[content too complex]");
+/// assert_eq!(
+///     sc.try_render("[x][x][x]no[/x][/x][/x]"),
+///     Err(RenderError::DepthExceeded { limit: 2 })
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderLimits {
+    /// Maximum nesting depth of shortcode tags. A top-level tag has depth 1.
+    pub max_depth: usize,
+    /// Maximum number of handler calls in one render.
+    pub max_expansions: usize,
+    /// Maximum length in bytes of the rendered output, and of any single
+    /// handler's output.
+    pub max_output_bytes: usize,
+    /// Maximum number of times handler output may be re-parsed within a
+    /// single expansion chain. Only applies to handlers opted in with
+    /// [`Shortcode::set_reexpand`](crate::shortcode::Shortcode::set_reexpand).
+    /// Defaults to 16.
+    pub max_reexpansion_depth: usize,
+    /// What `render` produces when a limit is exceeded, or re-expansion
+    /// runs into a cycle.
+    pub fallback: LimitFallback,
+}
+
+impl Default for RenderLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: usize::MAX,
+            max_expansions: usize::MAX,
+            max_output_bytes: usize::MAX,
+            max_reexpansion_depth: 16,
+            fallback: LimitFallback::Source,
+        }
+    }
+}
+
+/// Output used by `render` in place of a render that exceeded a limit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LimitFallback {
+    /// The input as written, with no shortcodes expanded.
+    #[default]
+    Source,
+    /// A fixed replacement text.
+    Text(String),
+}
+
+/// Reason a render was aborted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+    /// Tags were nested deeper than `RenderLimits::max_depth`.
+    DepthExceeded { limit: usize },
+    /// More than `RenderLimits::max_expansions` handlers would have run.
+    ExpansionsExceeded { limit: usize },
+    /// Output grew beyond `RenderLimits::max_output_bytes`.
+    OutputTooLarge { limit: usize },
+    /// Handler output was re-parsed more than
+    /// `RenderLimits::max_reexpansion_depth` times in one chain.
+    ReexpansionDepthExceeded { limit: usize },
+    /// Re-expanding handler output led back to a tag that was already being
+    /// expanded with the same attributes and content.
+    ReexpansionCycle { tag: String },
+    /// A typed handler or the schema of `tag` rejected its attributes. See
+    /// [`Shortcode::add_typed`](crate::shortcode::Shortcode::add_typed) and
+    /// [`Shortcode::set_schema`](crate::shortcode::Shortcode::set_schema).
+    InvalidAttribute { tag: String, error: AttrError },
+    /// `tag` encloses content, but its schema does not allow any. See
+    /// [`Shortcode::set_schema`](crate::shortcode::Shortcode::set_schema).
+    ContentNotAllowed { tag: String },
+    /// An attribute of `tag` names a variable that is not defined. See
+    /// [`UndefinedVars::Error`](crate::UndefinedVars::Error).
+    UndefinedVariable { tag: String, name: String },
+}
+
+impl RenderError {
+    /// Whether the error concerns a single tag rather than the whole render.
+    /// `render` writes such a tag back as source instead of falling back.
+    pub(crate) fn is_tag_error(&self) -> bool {
+        matches!(
+            self,
+            RenderError::InvalidAttribute { .. }
+                | RenderError::ContentNotAllowed { .. }
+                | RenderError::UndefinedVariable { .. }
+        )
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::DepthExceeded { limit } => {
+                write!(f, "shortcodes nested deeper than {limit} levels")
+            }
+            RenderError::ExpansionsExceeded { limit } => {
+                write!(f, "more than {limit} shortcode expansions")
+            }
+            RenderError::OutputTooLarge { limit } => {
+                write!(f, "rendered output exceeds {limit} bytes")
+            }
+            RenderError::ReexpansionDepthExceeded { limit } => {
+                write!(f, "handler output re-expanded more than {limit} times")
+            }
+            RenderError::ReexpansionCycle { tag } => {
+                write!(f, "re-expanding {tag} leads back to itself")
+            }
+            RenderError::InvalidAttribute { tag, error } => write!(f, "{error} in {tag}"),
+            RenderError::ContentNotAllowed { tag } => write!(f, "{tag} does not take content"),
+            RenderError::UndefinedVariable { tag, name } => {
+                write!(f, "undefined variable `{name}` in {tag}")
+            }
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenderError::InvalidAttribute { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_unbounded() {
+        let limits = RenderLimits::default();
+        assert_eq!(limits.max_depth, usize::MAX);
+        assert_eq!(limits.max_expansions, usize::MAX);
+        assert_eq!(limits.max_output_bytes, usize::MAX);
+        assert_eq!(limits.max_reexpansion_depth, 16);
+        assert_eq!(limits.fallback, LimitFallback::Source);
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!(
+            RenderError::DepthExceeded { limit: 3 }.to_string(),
+            "shortcodes nested deeper than 3 levels"
+        );
+        assert_eq!(
+            RenderError::OutputTooLarge { limit: 10 }.to_string(),
+            "rendered output exceeds 10 bytes"
+        );
+        assert_eq!(
+            RenderError::ReexpansionCycle {
+                tag: "[a]".to_string()
+            }
+            .to_string(),
+            "re-expanding [a] leads back to itself"
+        );
+        let error = RenderError::InvalidAttribute {
+            tag: "[video]".to_string(),
+            error: AttrError::Missing {
+                name: "id".to_string(),
+            },
+        };
+        assert_eq!(
+            error.to_string(),
+            "missing required attribute `id` in [video]"
+        );
+        assert_eq!(
+            error.source().unwrap().to_string(),
+            "missing required attribute `id`"
+        );
+        assert_eq!(
+            RenderError::ContentNotAllowed {
+                tag: "[hr]".to_string()
+            }
+            .to_string(),
+            "[hr] does not take content"
+        );
+        assert_eq!(
+            RenderError::UndefinedVariable {
+                tag: "[a href=\"{url}\"]".to_string(),
+                name: "url".to_string()
+            }
+            .to_string(),
+            "undefined variable `url` in [a href=\"{url}\"]"
+        );
+    }
+}
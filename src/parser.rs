@@ -1,3 +1,4 @@
+use crate::syntax::{Dialect, Syntax};
 use crate::tokenizer::{TokenSegment, Tokenizer};
 use crate::token::Token;
 
@@ -5,19 +6,36 @@ use crate::token::Token;
 ///
 /// Handles tag name extraction (including `/` prefix for close tags)
 /// and attribute parsing (`key="value" flag` format).
+///
+/// The parser is an `Iterator` over `Token`s, pulling segments from the
+/// tokenizer lazily, so nothing is scanned until a token is requested.
+#[derive(Debug, Clone)]
 pub struct Parser<'a> {
-    segments: Vec<TokenSegment<'a>>,
+    content: &'a str,
+    segments: Tokenizer<'a>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(content: &'a str) -> Self {
-        let segments = Tokenizer::new(content).tokenize();
-        Self { segments }
+        Self {
+            content,
+            segments: Tokenizer::new(content),
+        }
+    }
+
+    /// Creates a parser that recognizes tags according to `syntax`.
+    pub fn with_syntax(content: &'a str, syntax: &'a Syntax) -> Self {
+        Self {
+            content,
+            segments: Tokenizer::with_syntax(content, syntax),
+        }
     }
 
-    /// Interpret all segments and return the token list.
+    /// Interpret the whole input and return the token list.
     pub fn parse(&self) -> Vec<Token<'a>> {
-        self.segments.iter().map(|seg| self.interpret(seg)).collect()
+        Tokenizer::with_syntax(self.content, self.segments.syntax())
+            .map(|seg| self.interpret(&seg))
+            .collect()
     }
 
     /// Interpret a single raw segment into a `Token`.
@@ -30,12 +48,27 @@ impl<'a> Parser<'a> {
 
     /// Interpret a raw tag byte slice into the appropriate `Token` variant.
     ///
-    /// The slice is the content between `[` and `]` (brackets excluded).
+    /// The slice is the content between `[` and `]` (brackets excluded),
+    /// trimmed if the delimiters are padded.
     /// - If it contains a space, the part before the space is the tag name
     ///   and the rest is parsed as attributes.
-    /// - If it contains no space and starts with `/`, it's a close tag.
+    /// - If it contains no space and starts with `/` (the close-tag marker),
+    ///   it's a close tag.
     /// - Otherwise it's a self-closing tag.
     fn parse_tag(&self, raw: &'a [u8]) -> Token<'a> {
+        let delimiters = &self.segments.syntax().delimiters;
+        let raw = if delimiters.is_padded() {
+            raw.trim_ascii()
+        } else {
+            raw
+        };
+
+        if self.segments.syntax().dialect == Dialect::BbCode {
+            if let Some(token) = self.parse_bbcode_value(raw) {
+                return token;
+            }
+        }
+
         // Find the first space to separate tag name from attributes.
         let space_pos = raw.iter().position(|&b| b == b' ');
 
@@ -49,7 +82,7 @@ impl<'a> Parser<'a> {
             // No attributes — check if it's a close tag.
             // SAFETY: raw is a subslice of valid UTF-8 content.
             let name = unsafe { std::str::from_utf8_unchecked(raw) };
-            if let Some(tag_name) = name.strip_prefix('/') {
+            if let Some(tag_name) = name.strip_prefix(delimiters.close_marker()) {
                 Token::CloseTag(tag_name)
             } else {
                 Token::SelfClose(name)
@@ -57,6 +90,34 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Interpret BBCode's `name=value` shorthand, giving the value an empty
+    /// attribute name. A quoted value may be followed by attributes; an
+    /// unquoted one runs to the end of the tag.
+    ///
+    /// Returns `None` if `=` does not come before the first space.
+    fn parse_bbcode_value(&self, raw: &'a [u8]) -> Option<Token<'a>> {
+        let eq = raw.iter().position(|&b| b == b' ' || b == b'=')?;
+        if raw[eq] != b'=' || eq == 0 {
+            return None;
+        }
+
+        // SAFETY: raw is a subslice of valid UTF-8 content, split at ASCII bytes.
+        let name = unsafe { std::str::from_utf8_unchecked(&raw[..eq]) };
+        let value = &raw[eq + 1..];
+        let (value, rest) = match value.first() {
+            Some(&quote @ (b'"' | b'\'')) => match value[1..].iter().position(|&b| b == quote) {
+                Some(end) => (&value[1..end + 1], &value[end + 2..]),
+                None => (value, &value[value.len()..]),
+            },
+            _ => (value, &value[value.len()..]),
+        };
+
+        // SAFETY: Same invariant.
+        let mut attrs = vec![("", Some(unsafe { std::str::from_utf8_unchecked(value) }))];
+        attrs.extend(self.parse_attr_value(rest));
+        Some(Token::SelfCloseAttr(name, attrs))
+    }
+
     /// Parse attribute name/value pairs from raw bytes.
     ///
     /// Format: `key="value" flag key2="value2"`
@@ -88,12 +149,26 @@ impl<'a> Parser<'a> {
                     let name = unsafe { std::str::from_utf8_unchecked(&attr_str[pos..i]) };
                     i += 1;
 
-                    // Skip any whitespace between `=` and the opening quote.
-                    while i < len && attr_str[i] != b'"' && attr_str[i] != b'\'' {
+                    // Skip any whitespace between `=` and the value.
+                    while i < len && attr_str[i] == b' ' {
                         i += 1;
                     }
-                    // Found opening quote — parse until closing quote.
-                    if i < len {
+                    if i < len && attr_str[i] != b'"' && attr_str[i] != b'\'' {
+                        // Unquoted value, as in `id=1` — runs to the next space.
+                        pos = i;
+                        while i < len && attr_str[i] != b' ' {
+                            i += 1;
+                        }
+                        // SAFETY: Split at ASCII spaces of valid UTF-8 content.
+                        let value = unsafe { std::str::from_utf8_unchecked(&attr_str[pos..i]) };
+                        attrs.push((name, Some(value)));
+                        pos = i;
+                    } else if i == len {
+                        // Nothing after `=`, as in a trailing `x=`: an empty value.
+                        attrs.push((name, Some("")));
+                        pos = i;
+                    } else {
+                        // Found opening quote — parse until closing quote.
                         let quote = attr_str[i];
                         i += 1;
                         pos = i;
@@ -130,10 +205,30 @@ impl<'a> Parser<'a> {
     }
 }
 
+impl<'a> Iterator for Parser<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let segment = self.segments.next()?;
+        Some(self.interpret(&segment))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_iter_stops_at_first_tag() {
+        let mut parser = Parser::new("Hello [video id=\"1\"] and [unclosed");
+        let first_tag = parser.find(|token| token.tag_name().is_some());
+        assert_eq!(
+            first_tag,
+            Some(Token::SelfCloseAttr("video", vec![("id", Some("1"))]))
+        );
+        assert_eq!(parser.next(), Some(Token::Text(" and ")));
+    }
+
     #[test]
     fn test_parse_empty() {
         let parser = Parser::new("");
@@ -200,6 +295,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_unquoted_values() {
+        let parser = Parser::new("[greet name=$user.name id= 7 title=\"a b\" x=]");
+        assert_eq!(
+            parser.parse(),
+            [Token::SelfCloseAttr(
+                "greet",
+                vec![
+                    ("name", Some("$user.name")),
+                    ("id", Some("7")),
+                    ("title", Some("a b")),
+                    ("x", Some(""))
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_trailing_equals() {
+        for input in ["[x a=]", "[x a= ]", "[x a=  ]"] {
+            assert_eq!(
+                Parser::new(input).parse(),
+                [Token::SelfCloseAttr("x", vec![("a", Some(""))])],
+                "{input}"
+            );
+        }
+        assert_eq!(
+            Parser::new("[x a= b=1 c]").parse(),
+            [Token::SelfCloseAttr(
+                "x",
+                vec![("a", Some("b=1")), ("c", None)]
+            )]
+        );
+    }
+
     #[test]
     fn test_parse_multiple() {
         let parser = Parser::new(
@@ -279,4 +409,61 @@ mod tests {
         assert_eq!(tokens[1], Token::Text(" text "));
         assert_eq!(tokens[2], Token::Text("[unclosed"));
     }
+
+    #[test]
+    fn test_padded_delimiters_and_close_marker() {
+        let syntax = crate::Syntax {
+            delimiters: crate::Delimiters::new("{%", "%}")
+                .padded(true)
+                .with_close_marker("end"),
+            ..crate::Syntax::new()
+        };
+        let tokens = Parser::with_syntax("{% if x %}y{%   endif %}", &syntax).parse();
+        assert_eq!(
+            tokens,
+            [
+                Token::SelfCloseAttr("if", vec![("x", None)]),
+                Token::Text("y"),
+                Token::CloseTag("if"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bbcode_value() {
+        let syntax = Syntax::bbcode();
+        let parse = |content| Parser::with_syntax(content, &syntax).parse();
+        assert_eq!(
+            parse("[url=https://x.test/?a=b]"),
+            [Token::SelfCloseAttr(
+                "url",
+                vec![("", Some("https://x.test/?a=b"))]
+            )]
+        );
+        assert_eq!(
+            parse("[quote=\"Jane Doe\" date=\"2024\"]"),
+            [Token::SelfCloseAttr(
+                "quote",
+                vec![("", Some("Jane Doe")), ("date", Some("2024"))]
+            )]
+        );
+        assert_eq!(
+            parse("[font=Times New Roman]"),
+            [Token::SelfCloseAttr(
+                "font",
+                vec![("", Some("Times New Roman"))]
+            )]
+        );
+        assert_eq!(
+            parse("[color=]"),
+            [Token::SelfCloseAttr("color", vec![("", Some(""))])]
+        );
+        assert_eq!(parse("[/url]"), [Token::CloseTag("url")]);
+        assert_eq!(
+            parse("[img width=\"1\"]"),
+            [Token::SelfCloseAttr("img", vec![("width", Some("1"))])]
+        );
+        // Without the dialect, the whole tag is the name.
+        assert_eq!(Parser::new("[url=x]").parse(), [Token::SelfClose("url=x")]);
+    }
 }
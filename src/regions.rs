@@ -0,0 +1,459 @@
+//! Regions of the input where shortcodes are not recognized.
+
+use crate::scan;
+use crate::syntax::HtmlRegions;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Skips the HTML construct starting with the `<` at `pos`: a comment, the
+/// markup of a tag, or a whole protected element. Returns the position just
+/// past it, or `None` if the `<` does not start one.
+///
+/// `unclosed` is where the markup of an earlier tag ran to the end of the
+/// input without a `>`; tags opening after it are taken to be unclosed too,
+/// so a run of unclosed tags is scanned once rather than once per tag.
+pub(crate) fn html(
+    content: &str,
+    pos: usize,
+    regions: &HtmlRegions,
+    unclosed: &mut usize,
+) -> Option<usize> {
+    let bytes = content.as_bytes();
+    let rest = &content[pos..];
+
+    if rest.starts_with("<!--") {
+        return Some(find_from(content, pos + 4, "-->").map_or(bytes.len(), |end| end + 3));
+    }
+
+    let closing = rest.starts_with("</");
+    let name_start = if closing { pos + 2 } else { pos + 1 };
+    if !bytes.get(name_start).is_some_and(u8::is_ascii_alphabetic) {
+        // A bare `<`, as in `a < b`.
+        return None;
+    }
+    let name_end = bytes[name_start..]
+        .iter()
+        .position(|b| !(b.is_ascii_alphanumeric() || *b == b'-' || *b == b':'))
+        .map_or(bytes.len(), |len| name_start + len);
+    let name = &content[name_start..name_end];
+
+    if name_end >= *unclosed {
+        return None;
+    }
+    let Some(tag_end) = tag_end(bytes, name_end) else {
+        *unclosed = name_end;
+        return None;
+    };
+    let self_closing = bytes[tag_end - 2] == b'/';
+    if closing || self_closing || !regions.is_protected(name) {
+        return Some(tag_end);
+    }
+
+    // Everything up to the matching close tag is the element's raw content.
+    let mut search = tag_end;
+    while let Some(close) = find_from(content, search, "</") {
+        let close_name = close + 2;
+        let after = close_name + name.len();
+        let name_matches = bytes
+            .get(close_name..after)
+            .is_some_and(|candidate| candidate.eq_ignore_ascii_case(name.as_bytes()));
+        if name_matches && bytes.get(after).is_none_or(|b| !b.is_ascii_alphanumeric()) {
+            return Some(tag_end_or_len(bytes, after));
+        }
+        search = close_name;
+    }
+    Some(bytes.len())
+}
+
+/// Byte ranges of a Markdown document that hold code or link syntax:
+/// fenced and indented code blocks, code spans, inline links and images,
+/// and backslash-escaped brackets. Ranges are sorted and disjoint.
+///
+/// This is a lightweight scan that follows the CommonMark rules for these
+/// constructs, not a full Markdown parser.
+pub(crate) fn markdown(content: &str) -> Vec<Range<usize>> {
+    let bytes = content.as_bytes();
+    let mut ranges = Vec::new();
+    let mut pos = 0;
+    // Whether an indented line here would start or continue a code block.
+    let mut code_can_start = true;
+    let mut in_list = false;
+    // Code spans and links do not extend past the current paragraph.
+    let mut paragraph = Paragraph::default();
+
+    while pos < bytes.len() {
+        let end = line_end(bytes, pos);
+        let line = &bytes[pos..end];
+        let (indent, text_start) = indentation(line);
+
+        if text_start == line.len() {
+            // Blank line.
+            code_can_start = true;
+            pos = next_line(bytes, end);
+            continue;
+        }
+
+        if indent >= 4 && code_can_start && !in_list {
+            ranges.push(pos..end);
+            pos = next_line(bytes, end);
+            continue;
+        }
+
+        if indent <= 3 {
+            if let Some(fence_end) = fence(bytes, pos + text_start) {
+                ranges.push(pos..fence_end);
+                pos = next_line(bytes, fence_end);
+                code_can_start = true;
+                continue;
+            }
+            if is_list_item(&line[text_start..]) {
+                in_list = true;
+            } else if indent == 0 && code_can_start {
+                in_list = false;
+            }
+        }
+        code_can_start = false;
+
+        if pos >= paragraph.end {
+            paragraph = Paragraph::new(bytes, pos, paragraph_end(bytes, end));
+        }
+        let resume = inline(bytes, pos + text_start, end, &paragraph, &mut ranges);
+        pos = next_line(bytes, resume);
+    }
+    ranges
+}
+
+/// The paragraph being scanned, with its brackets and parentheses matched
+/// in one pass so that each opener is not searched for separately.
+#[derive(Debug, Default)]
+struct Paragraph {
+    end: usize,
+    /// Position of the `]` or `)` closing each matched `[` or `(`.
+    closing: HashMap<usize, usize>,
+}
+
+impl Paragraph {
+    /// Matches delimiters in `bytes[start..end]`, allowing nesting and
+    /// backslash escapes.
+    fn new(bytes: &[u8], start: usize, end: usize) -> Self {
+        let mut closing = HashMap::new();
+        let (mut brackets, mut parens) = (Vec::new(), Vec::new());
+        let mut i = start;
+        while i < end {
+            match bytes[i] {
+                b'\\' => i += 1,
+                b'[' => brackets.push(i),
+                b'(' => parens.push(i),
+                b']' => closing.extend(brackets.pop().map(|open| (open, i))),
+                b')' => closing.extend(parens.pop().map(|open| (open, i))),
+                _ => {}
+            }
+            i += 1;
+        }
+        Self { end, closing }
+    }
+}
+
+/// Protects code spans, links and escapes in one line of prose, starting at
+/// `pos`. A construct may continue onto later lines of the paragraph;
+/// returns the end of the line the scan finished on.
+fn inline(
+    bytes: &[u8],
+    mut pos: usize,
+    mut end: usize,
+    paragraph: &Paragraph,
+    ranges: &mut Vec<Range<usize>>,
+) -> usize {
+    while pos < end {
+        let construct = match bytes[pos] {
+            b'\\' if bytes.get(pos + 1).is_some_and(|b| matches!(b, b'[' | b']')) => {
+                Some(pos..pos + 2)
+            }
+            b'\\' => {
+                pos += 2;
+                continue;
+            }
+            b'`' => code_span(bytes, pos, paragraph.end),
+            b'!' if bytes.get(pos + 1) == Some(&b'[') => {
+                link(bytes, pos + 1, paragraph).map(|link| pos..link.end)
+            }
+            b'[' => link(bytes, pos, paragraph),
+            _ => None,
+        };
+
+        match construct {
+            Some(range) => {
+                pos = range.end;
+                if pos > end {
+                    end = line_end(bytes, pos);
+                }
+                ranges.push(range);
+            }
+            None if bytes[pos] == b'`' => {
+                // An unmatched backtick run is literal text.
+                pos += bytes[pos..].iter().take_while(|&&b| b == b'`').count();
+            }
+            None => pos += 1,
+        }
+    }
+    end
+}
+
+/// A code span opened by the backtick run at `pos`: the closing run must have
+/// exactly the same length.
+fn code_span(bytes: &[u8], pos: usize, limit: usize) -> Option<Range<usize>> {
+    let run = bytes[pos..].iter().take_while(|&&b| b == b'`').count();
+    let mut i = pos + run;
+    while i < limit {
+        if bytes[i] == b'`' {
+            let closing = bytes[i..limit].iter().take_while(|&&b| b == b'`').count();
+            if closing == run {
+                return Some(pos..i + run);
+            }
+            i += closing;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// An inline link `[text](destination)` whose text opens at `pos`.
+fn link(bytes: &[u8], pos: usize, paragraph: &Paragraph) -> Option<Range<usize>> {
+    let text_end = *paragraph.closing.get(&pos)?;
+    if bytes.get(text_end + 1) != Some(&b'(') {
+        return None;
+    }
+    let destination_end = paragraph.closing.get(&(text_end + 1))?;
+    Some(pos..destination_end + 1)
+}
+
+/// A fenced code block whose opening fence starts at `fence_start`. Returns
+/// the end of its closing fence line, or of the input if it is never closed.
+fn fence(bytes: &[u8], fence_start: usize) -> Option<usize> {
+    let marker = *bytes
+        .get(fence_start)
+        .filter(|b| matches!(b, b'`' | b'~'))?;
+    let run = bytes[fence_start..]
+        .iter()
+        .take_while(|&&b| b == marker)
+        .count();
+    let info_end = line_end(bytes, fence_start);
+    if run < 3 || (marker == b'`' && bytes[fence_start + run..info_end].contains(&b'`')) {
+        return None;
+    }
+
+    let mut pos = next_line(bytes, info_end);
+    while pos < bytes.len() {
+        let end = line_end(bytes, pos);
+        let line = &bytes[pos..end];
+        let (indent, text_start) = indentation(line);
+        let closing = line[text_start..]
+            .iter()
+            .take_while(|&&b| b == marker)
+            .count();
+        if indent <= 3
+            && closing >= run
+            && line[text_start + closing..]
+                .iter()
+                .all(u8::is_ascii_whitespace)
+        {
+            return Some(end);
+        }
+        pos = next_line(bytes, end);
+    }
+    Some(bytes.len())
+}
+
+/// Whether a line (without its indentation) opens a list item.
+fn is_list_item(line: &[u8]) -> bool {
+    let marker_len = match line.first() {
+        Some(b'-' | b'*' | b'+') => 1,
+        Some(b'0'..=b'9') => {
+            let digits = line.iter().take_while(|b| b.is_ascii_digit()).count();
+            match line.get(digits) {
+                Some(b'.' | b')') => digits + 1,
+                _ => return false,
+            }
+        }
+        _ => return false,
+    };
+    line.get(marker_len)
+        .is_none_or(|b| *b == b' ' || *b == b'\t')
+}
+
+/// Indentation width in columns (tabs stop every 4) and the offset of the
+/// first non-whitespace byte, which is the line length for a blank line.
+fn indentation(line: &[u8]) -> (usize, usize) {
+    let mut columns = 0;
+    for (offset, b) in line.iter().enumerate() {
+        match b {
+            b' ' => columns += 1,
+            b'\t' => columns += 4 - columns % 4,
+            b'\r' => {}
+            _ => return (columns, offset),
+        }
+    }
+    (columns, line.len())
+}
+
+/// Start of the first blank line after `pos`, or the end of the input.
+fn paragraph_end(bytes: &[u8], mut pos: usize) -> usize {
+    while pos < bytes.len() {
+        let start = next_line(bytes, pos);
+        let end = line_end(bytes, start);
+        if indentation(&bytes[start..end]).1 == end - start {
+            return start;
+        }
+        pos = end;
+    }
+    bytes.len()
+}
+
+/// Position of the `\n` ending the line that contains `pos`, or the end of the input.
+fn line_end(bytes: &[u8], pos: usize) -> usize {
+    scan::find_byte(b'\n', &bytes[pos..]).map_or(bytes.len(), |offset| pos + offset)
+}
+
+/// Start of the line after the one ending at `end`.
+fn next_line(bytes: &[u8], end: usize) -> usize {
+    (end + 1).min(bytes.len())
+}
+
+/// Position just past the `>` closing a tag's markup, skipping quoted
+/// attribute values. `None` if the tag is never closed.
+fn tag_end(bytes: &[u8], from: usize) -> Option<usize> {
+    let mut i = from;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += 1;
+                }
+            }
+            b'>' => return Some(i + 1),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn tag_end_or_len(bytes: &[u8], from: usize) -> usize {
+    tag_end(bytes, from).unwrap_or(bytes.len())
+}
+
+fn find_from(content: &str, from: usize, needle: &str) -> Option<usize> {
+    content[from..].find(needle).map(|offset| from + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skip(content: &str) -> Option<usize> {
+        let mut unclosed = usize::MAX;
+        html(content, 0, &HtmlRegions::default(), &mut unclosed)
+    }
+
+    #[test]
+    fn test_comment() {
+        assert_eq!(skip("<!-- [x] -->after"), Some(12));
+        assert_eq!(skip("<!-- [x]"), Some(8));
+    }
+
+    #[test]
+    fn test_tag_markup() {
+        assert_eq!(skip("<a href=\"[x]\">[y]</a>"), Some(14));
+        assert_eq!(skip("<a title='>'>x"), Some(13));
+        assert_eq!(skip("</p>[x]"), Some(4));
+    }
+
+    #[test]
+    fn test_protected_element() {
+        let content = "<pre class=\"x\">[a]</PRE>[b]";
+        assert_eq!(skip(content), Some(content.len() - 3));
+        assert_eq!(skip("<code>[a]</codex></code>!"), Some(24));
+        assert_eq!(skip("<script>[a]"), Some(11));
+        assert_eq!(skip("<code/>[a]"), Some(7));
+    }
+
+    #[test]
+    fn test_not_a_tag() {
+        assert_eq!(skip("< b"), None);
+        assert_eq!(skip("<3 [x]"), None);
+        assert_eq!(skip("<b [x]"), None);
+    }
+
+    #[test]
+    fn test_unclosed_tags() {
+        let content = "<a [x] <b [y] <i>";
+        let mut unclosed = usize::MAX;
+        let regions = HtmlRegions::default();
+        assert_eq!(html(content, 0, &regions, &mut unclosed), Some(17));
+        assert_eq!(unclosed, usize::MAX);
+
+        let content = "<a title=\"[x] <b> [y]";
+        assert_eq!(html(content, 0, &regions, &mut unclosed), None);
+        assert_eq!(unclosed, 2);
+        // Later tags are not scanned again.
+        assert_eq!(html(content, 15, &regions, &mut unclosed), None);
+    }
+
+    fn protected(content: &str) -> Vec<&str> {
+        markdown(content)
+            .into_iter()
+            .map(|range| &content[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_markdown_fences() {
+        let content = "a [x]\n```rust\n[y]\n````\n~~~~\n[z]\n~~~\n~~~~\n[w]";
+        assert_eq!(
+            protected(content),
+            ["```rust\n[y]\n````", "~~~~\n[z]\n~~~\n~~~~"]
+        );
+        // An unclosed fence runs to the end of the input.
+        assert_eq!(protected("```\n[a]"), ["```\n[a]"]);
+        // Backticks in the info string mean it is not a fence.
+        assert_eq!(protected("``` a`b\n[a]"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_markdown_indented_code() {
+        let content = "para\n    [not code]\n\n    [code]\n\tmore\n\n- item\n\n    [item text]";
+        assert_eq!(protected(content), ["    [code]", "\tmore"]);
+    }
+
+    #[test]
+    fn test_markdown_code_spans() {
+        assert_eq!(protected("a `[x]` b ``[y]`z`` [w]"), ["`[x]`", "``[y]`z``"]);
+        assert_eq!(protected("a `[x]\nstill` b"), ["`[x]\nstill`"]);
+        // Spans end at a blank line, and unmatched runs are literal.
+        assert_eq!(protected("a `[x]\n\nb` [y]"), Vec::<&str>::new());
+        assert_eq!(protected("``a` [x]"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_markdown_links() {
+        assert_eq!(
+            protected("[x] [text](url) ![alt [y]](a_(b).png) [z] (w)"),
+            ["[text](url)", "![alt [y]](a_(b).png)"]
+        );
+        // An escaped bracket is literal; an escaped backslash is not.
+        assert_eq!(protected(r"\[x] \\[y]"), [r"\["]);
+    }
+
+    #[test]
+    fn test_markdown_many_unmatched_openers() {
+        // Each opener would otherwise search to the end of the paragraph.
+        for unit in ["[a ", "[a]("] {
+            let content = format!("{}[b](c)", unit.repeat(40_000));
+            let ranges = markdown(&content);
+            assert_eq!(ranges.len(), 1, "{unit}");
+            assert_eq!(&content[ranges[0].clone()], "[b](c)");
+        }
+    }
+}
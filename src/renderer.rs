@@ -1,75 +1,220 @@
+use crate::ast::Node;
 use crate::code::Code;
-use crate::shortcode::ShortcodeFn;
+use crate::syntax::{CaseMatching, Dialect};
 use crate::token::Token;
+use std::borrow::Cow;
+use std::collections::HashMap;
 
 pub struct Renderer<'a> {
     items: Vec<Code<'a>>,
 }
 
 impl<'a> Renderer<'a> {
-    pub fn new(tokens: &'a [Token<'a>]) -> Self {
-        let mut items: Vec<Code<'a>> = vec![];
+    /// Builds the render tree in a single pass over `tokens`.
+    ///
+    /// Accepts any token iterator, so a `Parser` can be fed in directly
+    /// without collecting its output first.
+    pub fn new(tokens: impl IntoIterator<Item = Token<'a>>) -> Self {
+        Self::with_matching(tokens, Dialect::Shortcode, CaseMatching::Exact)
+    }
+
+    /// Builds the render tree, pairing tags by the rules of `dialect` and
+    /// comparing names with `case`.
+    pub fn with_matching(
+        tokens: impl IntoIterator<Item = Token<'a>>,
+        dialect: Dialect,
+        case: CaseMatching,
+    ) -> Self {
+        let mut tree = Tree {
+            items: vec![],
+            open: HashMap::new(),
+            lists: vec![],
+            case: case.for_dialect(dialect),
+        };
 
         for token in tokens {
             match token {
-                 Token::CloseTag(name) => {
-                    // Collect items popped while searching for matching opener.
-                    let mut popped = vec![];
-                    let mut matched: Option<Code<'a>> = None;
-
-                    while let Some(code) = items.pop() {
-                        if let Some(tag_name) = code.tag_name() {
-                            if *name == tag_name {
-                                matched = Some(code);
-                                break;
-                            }
-                        }
-                        popped.push(code);
+                Token::CloseTag(name) if tree.is_open(name) => {
+                    if dialect == Dialect::BbCode && is_bbcode_list(name) {
+                        tree.close_list_item();
                     }
-
-                    if let Some(matched_code) = matched {
-                        // Found matching opener — build nested node.
-                        let mut children = vec![];
-                        for code in popped.into_iter().rev() {
-                            children.push(code);
-                        }
-                        // Extract the token from the matched code.
-                        match matched_code {
-                            Code::Nested(token, _) | Code::Inline(token) => {
-                                items.push(Code::Nested(token, children));
-                            }
+                    tree.close(name, true);
+                }
+                // No matching opener — render close tag raw.
+                Token::CloseTag(_) => tree.items.push(Code::Inline(token)),
+                _ => {
+                    if let Some(name) = token.tag_name() {
+                        if dialect == Dialect::BbCode && name == BBCODE_LIST_ITEM {
+                            tree.close_list_item();
                         }
-                    } else {
-                        // No matching opener — restore stack and render close tag raw.
-                        for code in popped.into_iter().rev() {
-                            items.push(code);
+                        if dialect == Dialect::BbCode
+                            && (name == BBCODE_LIST_ITEM || is_bbcode_list(name))
+                        {
+                            tree.lists.push(tree.items.len());
                         }
-                        items.push(Code::Inline(token));
+                        *tree.open.entry(tree.key(name)).or_default() += 1;
                     }
+                    tree.items.push(Code::Inline(token));
                 }
-                _ => items.push(Code::Inline(token)),
             }
         }
+        if dialect == Dialect::BbCode {
+            tree.close_list_item();
+        }
 
-        Self { items }
+        Self {
+            items: std::mem::take(&mut tree.items),
+        }
     }
 
-    pub fn render(&self, codes: &[(&str, ShortcodeFn)]) -> String {
-        self.items.iter().map(|code| code.render(codes)).collect()
+    /// The top-level nodes of the tree.
+    pub(crate) fn nodes(&self) -> &[Code<'a>] {
+        &self.items
+    }
+
+    /// Detaches the tree from the input it was parsed from.
+    pub(crate) fn into_nodes(mut self) -> Vec<Node> {
+        std::mem::take(&mut self.items)
+            .into_iter()
+            .map(Node::from)
+            .collect()
+    }
+}
+
+/// BBCode's list item tag, closed implicitly.
+const BBCODE_LIST_ITEM: &str = "*";
+
+/// BBCode tags whose items are `[*]`.
+const BBCODE_LISTS: &[&str] = &["list", "ul", "ol"];
+
+fn is_bbcode_list(name: &str) -> bool {
+    BBCODE_LISTS
+        .iter()
+        .any(|list| list.eq_ignore_ascii_case(name))
+}
+
+/// A tree being built: finished nodes and still-open tags share `items`.
+struct Tree<'a> {
+    items: Vec<Code<'a>>,
+    // Unclosed openers per name, so a close tag without one skips the
+    // search instead of scanning the whole stack.
+    open: HashMap<Cow<'a, str>, usize>,
+    // Indices in `items` of open BBCode lists and `[*]` items, innermost
+    // last, so finding the current list item does not scan `items`.
+    lists: Vec<usize>,
+    case: CaseMatching,
+}
+
+impl<'a> Tree<'a> {
+    /// The key counting open tags named `name`.
+    fn key(&self, name: &'a str) -> Cow<'a, str> {
+        self.case.fold(name)
+    }
+
+    fn is_open(&self, name: &'a str) -> bool {
+        self.open
+            .get(&self.key(name))
+            .is_some_and(|&count| count > 0)
+    }
+
+    /// Pairs the closest open `name` with the items after it. It must be
+    /// open. If `explicit`, `name` is as written in a closing tag.
+    fn close(&mut self, name: &'a str, explicit: bool) {
+        // Collect items popped while searching for matching opener.
+        let mut popped = vec![];
+        let matched = loop {
+            let code = self.items.pop().expect("an open tag is on the stack");
+            // Only a still-open tag can pair; a closed `Nested` already has its match.
+            if let Code::Inline(opener) = &code {
+                if let Some(tag_name) = opener.tag_name() {
+                    if let Some(count) = self.open.get_mut(&self.key(tag_name)) {
+                        *count -= 1;
+                    }
+                    if self.case.eq(name, tag_name) {
+                        break code;
+                    }
+                }
+            }
+            popped.push(code);
+        };
+
+        // Found matching opener — build nested node. Openers
+        // popped on the way stay self-closing children.
+        popped.reverse();
+        // Lists and items opened at or after the matched opener are closed now.
+        let at = self.items.len();
+        while self.lists.last().is_some_and(|&index| index >= at) {
+            self.lists.pop();
+        }
+        match matched {
+            Code::Nested(token, ..) | Code::Inline(token) => {
+                self.items
+                    .push(Code::Nested(token, popped, explicit.then_some(name)));
+            }
+        }
+    }
+
+    /// Closes the open `[*]` of the innermost open list, if it has one.
+    fn close_list_item(&mut self) {
+        let item = self
+            .lists
+            .last()
+            .and_then(|&index| match &self.items[index] {
+                Code::Inline(opener) => opener.tag_name(),
+                Code::Nested(..) => None,
+            });
+        if item == Some(BBCODE_LIST_ITEM) {
+            self.close(BBCODE_LIST_ITEM, false);
+        }
+    }
+}
+
+impl Drop for Renderer<'_> {
+    /// Tears the tree down iteratively; the derived drop glue would recurse
+    /// once per nesting level.
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.items);
+        while let Some(code) = stack.pop() {
+            if let Code::Nested(_, children, _) = code {
+                stack.extend(children);
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::code::render_nodes;
+    use crate::context::Context;
+    use crate::limits::RenderError;
+    use crate::parser::Parser;
+    use crate::shortcode::{Shortcode, ShortcodeFn};
+
+    impl Renderer<'_> {
+        fn render(&self, shortcodes: &Shortcode, ctx: &Context) -> Result<String, RenderError> {
+            render_nodes(&self.items, shortcodes, ctx, true)
+        }
+    }
+
+    fn registry(codes: &[(&'static str, ShortcodeFn)]) -> Shortcode {
+        let mut shortcodes = Shortcode::new();
+        for (name, func) in codes {
+            shortcodes.add(*name, *func);
+        }
+        shortcodes
+    }
 
     #[test]
     fn test_render_empty_content() {
         let tokens = vec![Token::Text("Hello world")];
 
-        let renderer = Renderer::new(&tokens);
+        let renderer = Renderer::new(tokens);
 
-        assert_eq!(renderer.render(&[]), "Hello world");
+        assert_eq!(
+            renderer.render(&registry(&[]), &Context::new()).unwrap(),
+            "Hello world"
+        );
     }
 
     #[test]
@@ -80,8 +225,11 @@ mod tests {
             Token::Text(" after"),
         ];
 
-        let renderer = Renderer::new(&tokens);
-        assert_eq!(renderer.render(&[]), "before [/unknown] after");
+        let renderer = Renderer::new(tokens);
+        assert_eq!(
+            renderer.render(&registry(&[]), &Context::new()).unwrap(),
+            "before [/unknown] after"
+        );
     }
 
     #[test]
@@ -94,8 +242,11 @@ mod tests {
 
         let codes: &[(&str, ShortcodeFn)] = &[("foo", |_, _| "<foo/>".to_string())];
 
-        let renderer = Renderer::new(&tokens);
-        assert_eq!(renderer.render(codes), "<foo/> [/unknown]");
+        let renderer = Renderer::new(tokens);
+        assert_eq!(
+            renderer.render(&registry(codes), &Context::new()).unwrap(),
+            "<foo/> [/unknown]"
+        );
     }
 
     #[test]
@@ -107,7 +258,155 @@ mod tests {
 
         let codes: &[(&str, ShortcodeFn)] = &[("outer", |_, _| "<outer/>".to_string())];
 
-        let renderer = Renderer::new(&tokens);
-        assert_eq!(renderer.render(codes), "<outer/>[/inner]");
+        let renderer = Renderer::new(tokens);
+        assert_eq!(
+            renderer.render(&registry(codes), &Context::new()).unwrap(),
+            "<outer/>[/inner]"
+        );
+    }
+
+    #[test]
+    fn test_render_same_name_nested() {
+        let tokens = vec![
+            Token::SelfClose("b"),
+            Token::SelfClose("b"),
+            Token::Text("x"),
+            Token::CloseTag("b"),
+            Token::CloseTag("b"),
+        ];
+
+        let codes: &[(&str, ShortcodeFn)] = &[("b", |content, _| {
+            format!("<b>{}</b>", content.unwrap_or(""))
+        })];
+
+        let renderer = Renderer::new(tokens);
+        assert_eq!(
+            renderer.render(&registry(codes), &Context::new()).unwrap(),
+            "<b><b>x</b></b>"
+        );
+    }
+
+    const DEEP: usize = 100_000;
+
+    #[test]
+    fn test_render_deeply_nested() {
+        let content = format!("{}x{}", "[b]".repeat(DEEP), "[/b]".repeat(DEEP));
+        let codes: &[(&str, ShortcodeFn)] =
+            &[("b", |content, _| content.unwrap_or("").to_string())];
+
+        let renderer = Renderer::new(Parser::new(&content));
+        assert_eq!(
+            renderer.render(&registry(codes), &Context::new()).unwrap(),
+            "x"
+        );
+    }
+
+    #[test]
+    fn test_render_deeply_nested_unknown() {
+        let content = format!("{}x{}", "[u]".repeat(DEEP), "[/u]".repeat(DEEP));
+
+        let renderer = Renderer::new(Parser::new(&content));
+        assert_eq!(
+            renderer.render(&registry(&[]), &Context::new()).unwrap(),
+            content
+        );
+    }
+
+    #[test]
+    fn test_render_many_unmatched_close_tags() {
+        let content = format!("{}{}", "[a]".repeat(DEEP), "[/b]".repeat(DEEP));
+
+        let renderer = Renderer::new(Parser::new(&content));
+        assert_eq!(
+            renderer.render(&registry(&[]), &Context::new()).unwrap(),
+            content
+        );
+    }
+
+    #[test]
+    fn test_bbcode_pairs_ignoring_case() {
+        let codes: &[(&str, ShortcodeFn)] = &[("note", |content, _| {
+            format!("<aside>{}</aside>", content.unwrap_or(""))
+        })];
+        let mut shortcodes = registry(codes);
+        shortcodes.set_syntax(crate::Syntax::bbcode());
+
+        let content = "[Note]a[NOTE]b[/note][/nOtE] [*]c";
+        let renderer = Renderer::with_matching(
+            Parser::with_syntax(content, shortcodes.syntax()),
+            Dialect::BbCode,
+            CaseMatching::Exact,
+        );
+        assert_eq!(
+            renderer.render(&shortcodes, &Context::new()).unwrap(),
+            "<aside>a<aside>b</aside></aside> [*]c"
+        );
+
+        // The default dialect matches names exactly.
+        let renderer = Renderer::new(Parser::new("[Note]a[/note]"));
+        assert_eq!(
+            renderer.render(&registry(codes), &Context::new()).unwrap(),
+            "[Note]a[/note]"
+        );
+    }
+
+    #[test]
+    fn test_bbcode_many_list_items() {
+        // Each `[*]` would otherwise search back through every earlier item.
+        let syntax = crate::Syntax::bbcode();
+        let content = "[*][/*]".repeat(100_000);
+        let renderer = Renderer::with_matching(
+            Parser::with_syntax(&content, &syntax),
+            Dialect::BbCode,
+            CaseMatching::Exact,
+        );
+        assert_eq!(renderer.nodes().len(), 100_000);
+
+        let content = format!("[list]{}[/list]", "[*]x".repeat(100_000));
+        let renderer = Renderer::with_matching(
+            Parser::with_syntax(&content, &syntax),
+            Dialect::BbCode,
+            CaseMatching::Exact,
+        );
+        match renderer.nodes() {
+            [Code::Nested(_, items, _)] => {
+                assert_eq!(items.len(), 100_000);
+                assert!(items
+                    .iter()
+                    .all(|item| matches!(item, Code::Nested(_, text, _) if text.len() == 1)));
+            }
+            nodes => panic!("expected one list, got {} nodes", nodes.len()),
+        }
+    }
+
+    #[test]
+    fn test_pairs_with_case_matching() {
+        let codes: &[(&str, ShortcodeFn)] = &[("ärger", |content, _| {
+            format!("<b>{}</b>", content.unwrap_or(""))
+        })];
+        let mut shortcodes = registry(codes);
+        shortcodes.set_case_matching(CaseMatching::Unicode);
+
+        let renderer = Renderer::with_matching(
+            Parser::new("[Ärger]x[/äRGER] [ärger]y[/ÄRGER]"),
+            Dialect::Shortcode,
+            CaseMatching::Unicode,
+        );
+        assert_eq!(
+            renderer.render(&shortcodes, &Context::new()).unwrap(),
+            "<b>x</b> <b>y</b>"
+        );
+
+        // ASCII matching leaves non-ASCII letters distinct.
+        shortcodes.set_case_matching(CaseMatching::Ascii);
+        let renderer = Renderer::with_matching(
+            Parser::new("[ärger]x[/Ärger]"),
+            Dialect::Shortcode,
+            CaseMatching::Ascii,
+        );
+        assert_eq!(
+            renderer.render(&shortcodes, &Context::new()).unwrap(),
+            "<b></b>x[/Ärger]"
+        );
     }
 }
@@ -0,0 +1,104 @@
+//! Fast byte search used by the tokenizer.
+//!
+//! With the `memchr` feature enabled the search is delegated to the `memchr`
+//! crate, which uses SIMD where the target supports it. Without it, a portable
+//! word-at-a-time scan checks `usize`-sized chunks before falling back to a
+//! byte loop, which is still considerably faster than walking every byte on
+//! long runs of prose.
+
+/// Returns the index of the first `needle` in `haystack`, if any.
+#[cfg(feature = "memchr")]
+#[inline]
+pub(crate) fn find_byte(needle: u8, haystack: &[u8]) -> Option<usize> {
+    memchr::memchr(needle, haystack)
+}
+
+/// Returns the index of the first `needle` in `haystack`, if any.
+#[cfg(not(feature = "memchr"))]
+#[inline]
+pub(crate) fn find_byte(needle: u8, haystack: &[u8]) -> Option<usize> {
+    swar::find_byte(needle, haystack)
+}
+
+#[cfg_attr(feature = "memchr", allow(dead_code))]
+mod swar {
+    const WORD: usize = std::mem::size_of::<usize>();
+    const LO: usize = usize::from_ne_bytes([0x01; WORD]);
+    const HI: usize = usize::from_ne_bytes([0x80; WORD]);
+
+    /// True if any byte of `word` is zero.
+    #[inline]
+    fn has_zero_byte(word: usize) -> bool {
+        word.wrapping_sub(LO) & !word & HI != 0
+    }
+
+    pub(crate) fn find_byte(needle: u8, haystack: &[u8]) -> Option<usize> {
+        // Short gaps (e.g. `[row][col]`) are common, so check the first word
+        // byte by byte before paying for the word setup.
+        let head = haystack.len().min(WORD);
+        if let Some(i) = haystack[..head].iter().position(|&b| b == needle) {
+            return Some(i);
+        }
+
+        let repeated = LO * needle as usize;
+        let mut offset = head;
+
+        // Skip whole words that cannot contain `needle`. The first word that
+        // might is re-scanned byte by byte below.
+        for chunk in haystack[head..].chunks_exact(WORD) {
+            let word = usize::from_ne_bytes(chunk.try_into().unwrap());
+            if has_zero_byte(word ^ repeated) {
+                break;
+            }
+            offset += WORD;
+        }
+
+        haystack[offset..]
+            .iter()
+            .position(|&b| b == needle)
+            .map(|i| offset + i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive(needle: u8, haystack: &[u8]) -> Option<usize> {
+        haystack.iter().position(|&b| b == needle)
+    }
+
+    #[test]
+    fn test_find_byte_empty() {
+        assert_eq!(find_byte(b'[', b""), None);
+        assert_eq!(swar::find_byte(b'[', b""), None);
+    }
+
+    #[test]
+    fn test_find_byte_every_position() {
+        // Cover needles before, inside and after whole-word chunks.
+        for len in 0..40 {
+            for at in 0..len {
+                let mut haystack = vec![b'a'; len];
+                haystack[at] = b'[';
+                assert_eq!(swar::find_byte(b'[', &haystack), Some(at));
+                assert_eq!(find_byte(b'[', &haystack), Some(at));
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_byte_returns_first_match() {
+        let haystack = b"some prose ] then [a] and [b]";
+        assert_eq!(swar::find_byte(b'[', haystack), naive(b'[', haystack));
+        assert_eq!(swar::find_byte(b']', haystack), naive(b']', haystack));
+    }
+
+    #[test]
+    fn test_find_byte_high_bytes() {
+        // Multi-byte UTF-8 must not produce false positives.
+        let haystack = "こんにちは世界、[emoji]".as_bytes();
+        assert_eq!(swar::find_byte(b'[', haystack), naive(b'[', haystack));
+        assert_eq!(swar::find_byte(0xff, haystack), None);
+    }
+}
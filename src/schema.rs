@@ -0,0 +1,422 @@
+use crate::attrs::{AttrError, ShortcodeAttrs};
+use crate::syntax::CaseMatching;
+use std::fmt;
+
+/// The attributes and content a shortcode accepts, enforced before its
+/// handler is called.
+///
+/// Attach one with [`Shortcode::set_schema`](crate::shortcode::Shortcode::set_schema).
+/// Attributes not declared with [`attr`](Self::attr) are rejected unless
+/// [`allow_unknown`](Self::allow_unknown) is set. The BBCode value, as in
+/// `[url=https://example.com]`, is the attribute named `""`.
+///
+/// ```rust
+/// use shortcode_parser::shortcode::Shortcode;
+/// use shortcode_parser::{AttrSpec, AttrType, Schema};
+///
+/// let mut sc = Shortcode::new();
+/// sc.add("video", |_, attrs| {
+///     format!("{}@{}", attrs.get("id").unwrap(), attrs.get("width").unwrap())
+/// });
+/// sc.set_schema(
+///     "video",
+///     Schema::new()
+///         .attr(AttrSpec::new("id", AttrType::Text).required())
+///         .attr(AttrSpec::new("width", AttrType::Integer).default("640"))
+///         .content(false),
+/// );
+///
+/// assert_eq!(sc.render("[video id=\"a\"]"), "a@640");
+/// assert_eq!(
+///     sc.try_render("[video width=\"1\"]").unwrap_err().to_string(),
+///     "missing required attribute `id` in [video width=\"1\"]"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    attrs: Vec<AttrSpec>,
+    content: bool,
+    allow_unknown: bool,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Schema {
+    /// A schema with no attributes that allows content.
+    pub fn new() -> Self {
+        Self {
+            attrs: Vec::new(),
+            content: true,
+            allow_unknown: false,
+        }
+    }
+
+    /// Declares an attribute. Declaring a name again replaces it.
+    pub fn attr(mut self, spec: AttrSpec) -> Self {
+        self.attrs.retain(|attr| attr.name != spec.name);
+        self.attrs.push(spec);
+        self
+    }
+
+    /// Whether the tag may enclose content, as in `[note]text[/note]`.
+    pub fn content(mut self, allowed: bool) -> Self {
+        self.content = allowed;
+        self
+    }
+
+    /// Whether attributes that were not declared are passed through.
+    pub fn allow_unknown(mut self, allowed: bool) -> Self {
+        self.allow_unknown = allowed;
+        self
+    }
+
+    /// The declared attributes, in declaration order.
+    pub fn attrs(&self) -> &[AttrSpec] {
+        &self.attrs
+    }
+
+    /// Every way `attrs` and the presence of content break this schema.
+    pub(crate) fn check(&self, attrs: &ShortcodeAttrs, has_content: bool) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        if !self.allow_unknown {
+            for (name, _) in attrs.iter() {
+                if !self
+                    .attrs
+                    .iter()
+                    .any(|spec| attrs.case().eq(name, &spec.name))
+                {
+                    violations.push(Violation::Attr(AttrError::Unknown {
+                        name: name.to_string(),
+                    }));
+                }
+            }
+        }
+        for spec in &self.attrs {
+            if !attrs.has(&spec.name) {
+                if spec.required {
+                    violations.push(Violation::Attr(AttrError::Missing {
+                        name: spec.name.clone(),
+                    }));
+                }
+            } else if let Err(error) = spec.kind.check(attrs, &spec.name) {
+                violations.push(Violation::Attr(error));
+            }
+        }
+        if has_content && !self.content {
+            violations.push(Violation::Content);
+        }
+        violations
+    }
+
+    /// Defaults of the declared attributes missing from `attrs`.
+    pub(crate) fn defaults<'s>(
+        &'s self,
+        attrs: &ShortcodeAttrs,
+    ) -> Vec<(&'s str, Option<&'s str>)> {
+        self.attrs
+            .iter()
+            .filter(|spec| !attrs.has(&spec.name))
+            .filter_map(|spec| Some((spec.name.as_str(), Some(spec.default.as_deref()?))))
+            .collect()
+    }
+}
+
+/// One attribute of a [`Schema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttrSpec {
+    name: String,
+    kind: AttrType,
+    required: bool,
+    default: Option<String>,
+}
+
+impl AttrSpec {
+    /// An optional attribute `name` whose value must be of `kind`.
+    pub fn new(name: impl Into<String>, kind: AttrType) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            required: false,
+            default: None,
+        }
+    }
+
+    /// Rejects tags without this attribute.
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// The value the handler sees when the attribute is missing.
+    pub fn default(mut self, value: impl Into<String>) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> &AttrType {
+        &self.kind
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    pub fn default_value(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+}
+
+/// The values an attribute accepts, read as by the typed getters on
+/// [`ShortcodeAttrs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrType {
+    /// Any value. A flag without a value is rejected.
+    Text,
+    /// A whole number, as `i64`.
+    Integer,
+    /// A decimal number, as `f64`.
+    Number,
+    /// A flag or a boolean word; see [`ShortcodeAttrs::get_bool`].
+    Bool,
+    /// See [`ShortcodeAttrs::get_duration`].
+    Duration,
+    /// See [`ShortcodeAttrs::get_size`].
+    Size,
+    /// Exactly one of the listed values.
+    OneOf(Vec<String>),
+}
+
+impl AttrType {
+    /// [`AttrType::OneOf`] the given values.
+    ///
+    /// ```rust
+    /// use shortcode_parser::AttrType;
+    ///
+    /// assert_eq!(
+    ///     AttrType::one_of(["left", "right"]),
+    ///     AttrType::OneOf(vec!["left".to_string(), "right".to_string()])
+    /// );
+    /// ```
+    pub fn one_of<S: Into<String>>(values: impl IntoIterator<Item = S>) -> Self {
+        AttrType::OneOf(values.into_iter().map(Into::into).collect())
+    }
+
+    fn check(&self, attrs: &ShortcodeAttrs, name: &str) -> Result<(), AttrError> {
+        match self {
+            AttrType::Text => attrs.get_required::<String>(name).map(drop),
+            AttrType::Integer => attrs.get_parsed::<i64>(name).map(drop),
+            AttrType::Number => attrs.get_parsed::<f64>(name).map(drop),
+            AttrType::Bool => attrs.get_bool(name).map(drop),
+            AttrType::Duration => attrs.get_duration(name).map(drop),
+            AttrType::Size => attrs.get_size(name).map(drop),
+            AttrType::OneOf(values) => {
+                let value: String = attrs.get_required(name)?;
+                if values.contains(&value) {
+                    Ok(())
+                } else {
+                    Err(AttrError::invalid(name, &value, OneOf(values)))
+                }
+            }
+        }
+    }
+}
+
+/// Formats the allowed values for an error message.
+struct OneOf<'a>(&'a [String]);
+
+impl fmt::Display for OneOf<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("expected one of ")?;
+        for (i, value) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "`{value}`")?;
+        }
+        Ok(())
+    }
+}
+
+/// Attribute values a handler sees when the author leaves them out, like
+/// WordPress's `shortcode_atts`.
+///
+/// Registered with [`Shortcode::add_with_defaults`](crate::shortcode::Shortcode::add_with_defaults).
+/// Handlers can tell defaults from written values with
+/// [`ShortcodeAttrs::is_default`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Defaults {
+    values: Vec<(String, String)>,
+    drop_unknown: bool,
+}
+
+impl Defaults {
+    /// Defaults for the given names. A name given twice keeps its last value.
+    pub fn new<K, V>(values: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let mut defaults = Self::default();
+        for (name, value) in values {
+            let name = name.into();
+            defaults.values.retain(|(key, _)| *key != name);
+            defaults.values.push((name, value.into()));
+        }
+        defaults
+    }
+
+    /// Whether attributes without a default are hidden from the handler,
+    /// as `shortcode_atts` does.
+    pub fn drop_unknown(mut self, drop: bool) -> Self {
+        self.drop_unknown = drop;
+        self
+    }
+
+    /// The name and default of each attribute, in the order given.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Whether `name` would be dropped from what the handler sees.
+    pub(crate) fn drops(&self, name: &str, case: CaseMatching) -> bool {
+        self.drop_unknown && !self.values.iter().any(|(key, _)| case.eq(key, name))
+    }
+
+    /// Defaults for the names missing from `attrs`.
+    pub(crate) fn missing<'s>(&'s self, attrs: &ShortcodeAttrs) -> Vec<(&'s str, Option<&'s str>)> {
+        self.values
+            .iter()
+            .filter(|(name, _)| !attrs.has(name))
+            .map(|(name, value)| (name.as_str(), Some(value.as_str())))
+            .collect()
+    }
+}
+
+/// A way a tag breaks its [`Schema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Violation {
+    Attr(AttrError),
+    /// The tag encloses content but the schema does not allow it.
+    Content,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Schema {
+        Schema::new()
+            .attr(AttrSpec::new("id", AttrType::Integer).required())
+            .attr(AttrSpec::new("align", AttrType::one_of(["left", "right"])).default("left"))
+            .attr(AttrSpec::new("autoplay", AttrType::Bool))
+            .content(false)
+    }
+
+    #[test]
+    fn test_check_valid() {
+        let attrs = [("id", Some("7")), ("autoplay", None)];
+        assert_eq!(schema().check(&ShortcodeAttrs::new(&attrs), false), []);
+    }
+
+    #[test]
+    fn test_check_reports_every_violation() {
+        let attrs = [("align", Some("up")), ("size", Some("2"))];
+        assert_eq!(
+            schema().check(&ShortcodeAttrs::new(&attrs), true),
+            [
+                Violation::Attr(AttrError::Unknown {
+                    name: "size".to_string()
+                }),
+                Violation::Attr(AttrError::Missing {
+                    name: "id".to_string()
+                }),
+                Violation::Attr(AttrError::Invalid {
+                    name: "align".to_string(),
+                    value: "up".to_string(),
+                    reason: "expected one of `left`, `right`".to_string(),
+                }),
+                Violation::Content,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_types() {
+        let schema = Schema::new()
+            .attr(AttrSpec::new("title", AttrType::Text))
+            .attr(AttrSpec::new("ratio", AttrType::Number))
+            .attr(AttrSpec::new("delay", AttrType::Duration))
+            .attr(AttrSpec::new("max", AttrType::Size));
+        let attrs = [
+            ("title", None),
+            ("ratio", Some("1.5")),
+            ("delay", Some("soon")),
+            ("max", Some("2KiB")),
+        ];
+        let names: Vec<_> = schema
+            .check(&ShortcodeAttrs::new(&attrs), false)
+            .into_iter()
+            .map(|violation| match violation {
+                Violation::Attr(error) => error.name().to_string(),
+                Violation::Content => String::new(),
+            })
+            .collect();
+        assert_eq!(names, ["title", "delay"]);
+    }
+
+    #[test]
+    fn test_check_case_and_unknown() {
+        let attrs = [("ID", Some("1")), ("extra", None)];
+        let attrs = ShortcodeAttrs::new(&attrs).with_case(CaseMatching::Ascii);
+        assert_eq!(schema().allow_unknown(true).check(&attrs, false), []);
+    }
+
+    #[test]
+    fn test_defaults() {
+        let attrs = [("id", Some("1"))];
+        assert_eq!(
+            schema().defaults(&ShortcodeAttrs::new(&attrs)),
+            [("align", Some("left"))]
+        );
+        let attrs = [("align", Some("right"))];
+        assert_eq!(schema().defaults(&ShortcodeAttrs::new(&attrs)), []);
+    }
+
+    #[test]
+    fn test_attr_replaces() {
+        let schema = Schema::new()
+            .attr(AttrSpec::new("a", AttrType::Text))
+            .attr(AttrSpec::new("a", AttrType::Bool).required());
+        assert_eq!(schema.attrs().len(), 1);
+        assert_eq!(schema.attrs()[0].kind(), &AttrType::Bool);
+        assert!(schema.attrs()[0].is_required());
+    }
+
+    #[test]
+    fn test_defaults_table() {
+        let defaults = Defaults::new([("width", "640"), ("height", "360"), ("width", "800")]);
+        assert_eq!(
+            defaults.iter().collect::<Vec<_>>(),
+            [("height", "360"), ("width", "800")]
+        );
+        let attrs = [("Height", Some("1"))];
+        let attrs = ShortcodeAttrs::new(&attrs).with_case(CaseMatching::Ascii);
+        assert_eq!(defaults.missing(&attrs), [("width", Some("800"))]);
+
+        assert!(!defaults.drops("class", CaseMatching::Exact));
+        let defaults = defaults.drop_unknown(true);
+        assert!(defaults.drops("class", CaseMatching::Exact));
+        assert!(defaults.drops("WIDTH", CaseMatching::Exact));
+        assert!(!defaults.drops("WIDTH", CaseMatching::Ascii));
+    }
+}
@@ -15,10 +15,24 @@
 //! assert_eq!(sc.render("[hello]"), "Hello, world!");
 //! ```
 
-use crate::attrs::ShortcodeAttrs;
+use crate::args::ShortcodeArgs;
+use crate::attrs::{AttrError, DuplicateAttrs, ShortcodeAttrs};
+use crate::code::render_nodes;
+use crate::context::Context;
+use crate::diagnostics::{self, Diagnostic};
+use crate::html::OutputMode;
+use crate::interpolate::Interpolation;
+use crate::limits::{LimitFallback, RenderError, RenderLimits};
 use crate::parser::Parser;
 use crate::renderer::Renderer;
+use crate::schema::{Defaults, Schema};
+use crate::syntax::{CaseMatching, Syntax};
+use crate::token::Token;
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
 
 /// Function signature for a shortcode handler.
 ///
@@ -32,13 +46,50 @@ use std::borrow::Cow;
 ///   `Some("v")`.
 ///
 /// Return value should be the rendered replacement string for the shortcode.
+///
+/// Any closure with this signature that is `Send + Sync + 'static` can be
+/// registered, not only plain functions.
 pub type ShortcodeFn = fn(Option<&str>, ShortcodeAttrs) -> String;
 
+/// Function signature for a handler that also reads the render [`Context`].
+///
+/// Registered with [`Shortcode::add_with_context`]. When rendering without a
+/// context (e.g. [`Shortcode::render`]) the handler receives an empty one.
+pub type ContextFn = fn(Option<&str>, ShortcodeAttrs, &Context) -> String;
+
+type PlainHandler = dyn Fn(Option<&str>, ShortcodeAttrs) -> String + Send + Sync;
+type ContextHandler = dyn Fn(Option<&str>, ShortcodeAttrs, &Context) -> String + Send + Sync;
+type TypedHandler = dyn Fn(Option<&str>, ShortcodeAttrs) -> Result<String, AttrError> + Send + Sync;
+
+/// A registered handler of any flavor.
+#[derive(Clone)]
+pub(crate) enum Handler {
+    Plain(Arc<PlainHandler>),
+    WithContext(Arc<ContextHandler>),
+    /// Parses the attributes first; see [`Shortcode::add_typed`].
+    Typed(Arc<TypedHandler>),
+}
+
+impl Handler {
+    pub(crate) fn call(
+        &self,
+        content: Option<&str>,
+        attrs: ShortcodeAttrs,
+        ctx: &Context,
+    ) -> Result<String, AttrError> {
+        match self {
+            Handler::Plain(func) => Ok(func(content, attrs)),
+            Handler::WithContext(func) => Ok(func(content, attrs, ctx)),
+            Handler::Typed(func) => func(content, attrs),
+        }
+    }
+}
+
 /// A registry of shortcode handlers keyed by their tag names.
 ///
-/// The lifetime parameter `'a` ties the lifetime of stored tag names to the
-/// lifetime of the `Shortcode` instance. Each tag name maps to a function with
-/// the `ShortcodeFn` signature.
+/// Tag names are owned and handlers are `Send + Sync`, so a registry built at
+/// startup can be shared across threads, e.g. behind an `Arc` or as a
+/// [`FrozenShortcode`].
 ///
 /// Example:
 /// ```rust
@@ -49,12 +100,38 @@ pub type ShortcodeFn = fn(Option<&str>, ShortcodeAttrs) -> String;
 /// assert!(sc.has("test"));
 /// assert_eq!(sc.render("[test]"), "ok");
 /// ```
-#[derive(Debug)]
-pub struct Shortcode<'a> {
-    items: Vec<(&'a str, ShortcodeFn)>,
+#[derive(Clone)]
+pub struct Shortcode {
+    items: HashMap<String, Handler>,
+    /// Registered names by their folded form under the case matching in
+    /// effect, so lookups that are not exact need not scan `items`.
+    folded: HashMap<String, String>,
+    limits: RenderLimits,
+    /// Names whose handler output is parsed again for shortcodes.
+    reexpand: HashSet<String>,
+    reexpand_all: bool,
+    /// Attributes and content accepted by each name; see [`Shortcode::set_schema`].
+    schemas: HashMap<String, Schema>,
+    /// Values merged into the attributes of handlers registered with
+    /// [`Shortcode::add_with_defaults`].
+    defaults: HashMap<String, Defaults>,
+    output_mode: OutputMode,
+    escape_attrs: bool,
+    syntax: Syntax,
+    case_matching: CaseMatching,
+    duplicate_attrs: DuplicateAttrs,
+    interpolation: Option<Interpolation>,
+}
+
+impl fmt::Debug for Shortcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<_> = self.names().collect();
+        names.sort_unstable();
+        f.debug_struct("Shortcode").field("names", &names).finish()
+    }
 }
 
-impl<'a> Default for Shortcode<'a> {
+impl Default for Shortcode {
     /// Creates a default, empty shortcode registry.
     ///
     /// This is equivalent to calling [`Shortcode::new`].
@@ -63,7 +140,7 @@ impl<'a> Default for Shortcode<'a> {
     }
 }
 
-impl<'a> Shortcode<'a> {
+impl Shortcode {
     /// Creates a new, empty shortcode registry.
     ///
     /// Example:
@@ -73,7 +150,21 @@ impl<'a> Shortcode<'a> {
     /// assert_eq!(sc.render("plain"), "plain");
     /// ```
     pub fn new() -> Self {
-        Self { items: vec![] }
+        Self {
+            items: HashMap::new(),
+            folded: HashMap::new(),
+            limits: RenderLimits::default(),
+            reexpand: HashSet::new(),
+            reexpand_all: false,
+            schemas: HashMap::new(),
+            defaults: HashMap::new(),
+            output_mode: OutputMode::Raw,
+            escape_attrs: false,
+            syntax: Syntax::new(),
+            case_matching: CaseMatching::Exact,
+            duplicate_attrs: DuplicateAttrs::CollectAll,
+            interpolation: None,
+        }
     }
 
     /// Registers a handler function under the given shortcode `name`.
@@ -86,12 +177,349 @@ impl<'a> Shortcode<'a> {
     /// let mut sc = Shortcode::new();
     /// sc.add("upper", |content, _| content.unwrap_or("").to_uppercase());
     /// assert_eq!(sc.render("[upper]hi[/upper]"), "HI");
+    ///
+    /// sc.add("upper", |content, _| content.unwrap_or("").to_lowercase());
+    /// assert_eq!(sc.render("[upper]HI[/upper]"), "hi");
+    /// ```
+    pub fn add<F>(&mut self, name: impl Into<String>, func: F)
+    where
+        F: Fn(Option<&str>, ShortcodeAttrs) -> String + Send + Sync + 'static,
+    {
+        let name = name.into();
+        self.defaults.remove(&name);
+        self.insert_handler(name, Handler::Plain(Arc::new(func)));
+    }
+
+    /// Like [`Shortcode::add`], but attributes the author leaves out take
+    /// their value from `defaults`.
+    ///
+    /// The handler sees the merged attributes, written values first, and
+    /// can tell them apart with [`ShortcodeAttrs::is_default`]. With
+    /// [`Defaults::drop_unknown`], attributes without a default are hidden.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use shortcode_parser::Defaults;
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add_with_defaults(
+    ///     "video",
+    ///     Defaults::new([("width", "640"), ("height", "360")]).drop_unknown(true),
+    ///     |_, attrs| {
+    ///         let pairs: Vec<_> = attrs.iter().map(|(k, v)| format!("{k}={}", v.unwrap())).collect();
+    ///         pairs.join(" ")
+    ///     },
+    /// );
+    /// assert_eq!(
+    ///     sc.render("[video width=\"1280\" onload=\"x\"]"),
+    ///     "width=1280 height=360"
+    /// );
+    /// ```
+    pub fn add_with_defaults<F>(&mut self, name: impl Into<String>, defaults: Defaults, func: F)
+    where
+        F: Fn(Option<&str>, ShortcodeAttrs) -> String + Send + Sync + 'static,
+    {
+        let name = name.into();
+        self.defaults.insert(name.clone(), defaults);
+        self.insert_handler(name, Handler::Plain(Arc::new(func)));
+    }
+
+    /// Registers a handler that also receives the render [`Context`].
+    ///
+    /// Example:
+    /// ```rust
+    /// use shortcode_parser::{shortcode::Shortcode, Context};
+    /// let mut sc = Shortcode::new();
+    /// sc.add_with_context("site", |_, _, ctx| ctx.get("site").unwrap_or("").to_string());
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.insert("site", "example.com");
+    /// assert_eq!(sc.render_with("[site]", &ctx), "example.com");
+    /// assert_eq!(sc.render("[site]"), "");
+    /// ```
+    pub fn add_with_context<F>(&mut self, name: impl Into<String>, func: F)
+    where
+        F: Fn(Option<&str>, ShortcodeAttrs, &Context) -> String + Send + Sync + 'static,
+    {
+        let name = name.into();
+        self.defaults.remove(&name);
+        self.insert_handler(name, Handler::WithContext(Arc::new(func)));
+    }
+
+    /// Registers a handler that receives its attributes parsed into `T`.
+    ///
+    /// If the attributes do not parse, the handler is not called:
+    /// `try_render` fails with [`RenderError::InvalidAttribute`], and
+    /// `render` writes the tag back as source.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use shortcode_parser::{AttrError, ShortcodeArgs, ShortcodeAttrs};
+    ///
+    /// struct Video {
+    ///     id: String,
+    ///     width: u32,
+    /// }
+    ///
+    /// impl ShortcodeArgs for Video {
+    ///     fn from_attrs(attrs: &ShortcodeAttrs) -> Result<Self, AttrError> {
+    ///         attrs.deny_unknown(&["id", "width"])?;
+    ///         Ok(Video {
+    ///             id: attrs.get_required("id")?,
+    ///             width: attrs.get_or("width", 640)?,
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add_typed("video", |_, video: Video| {
+    ///     format!("<video src=\"{}.mp4\" width=\"{}\">", video.id, video.width)
+    /// });
+    /// assert_eq!(sc.render("[video id=\"a\"]"), "<video src=\"a.mp4\" width=\"640\">");
+    /// assert_eq!(
+    ///     sc.try_render("[video]").unwrap_err().to_string(),
+    ///     "missing required attribute `id` in [video]"
+    /// );
+    /// ```
+    pub fn add_typed<T, F>(&mut self, name: impl Into<String>, func: F)
+    where
+        T: ShortcodeArgs + 'static,
+        F: Fn(Option<&str>, T) -> String + Send + Sync + 'static,
+    {
+        let name = name.into();
+        self.defaults.remove(&name);
+        self.insert_handler(
+            name,
+            Handler::Typed(Arc::new(move |content, attrs| {
+                Ok(func(content, T::from_attrs(&attrs)?))
+            })),
+        );
+    }
+
+    /// Unregisters the handler for `name`. Returns `true` if one was registered.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// let mut sc = Shortcode::new();
+    /// sc.add("x", |_, _| "X".to_string());
+    /// assert!(sc.remove("x"));
+    /// assert!(!sc.remove("x"));
+    /// assert_eq!(sc.render("[x]"), "[x]");
+    /// ```
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.reexpand.remove(name);
+        self.schemas.remove(name);
+        self.defaults.remove(name);
+        let removed = self.items.remove(name).is_some();
+        if removed {
+            self.index_names();
+        }
+        removed
+    }
+
+    /// Iterates over the registered tag names, in no particular order.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// let mut sc = Shortcode::new();
+    /// sc.add("b", |_, _| "".to_string());
+    /// sc.add("a", |_, _| "".to_string());
+    /// let mut names: Vec<_> = sc.names().collect();
+    /// names.sort();
+    /// assert_eq!(names, ["a", "b"]);
+    /// ```
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.items.keys().map(String::as_str)
+    }
+
+    /// Moves every handler from `other` into this registry.
+    ///
+    /// Handlers in `other` replace handlers registered here under the same
+    /// name, along with their [`Shortcode::set_reexpand`] setting.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// let mut base = Shortcode::new();
+    /// base.add("a", |_, _| "A".to_string());
+    /// base.add("b", |_, _| "B".to_string());
+    ///
+    /// let mut theme = Shortcode::new();
+    /// theme.add("b", |_, _| "themed B".to_string());
+    ///
+    /// base.merge(theme);
+    /// assert_eq!(base.render("[a] [b]"), "A themed B");
+    /// ```
+    pub fn merge(&mut self, other: Shortcode) {
+        for name in other.items.keys() {
+            if other.reexpand.contains(name) {
+                self.reexpand.insert(name.clone());
+            } else {
+                self.reexpand.remove(name);
+            }
+            self.schemas.remove(name);
+            self.defaults.remove(name);
+        }
+        self.schemas.extend(other.schemas);
+        self.defaults.extend(other.defaults);
+        self.items.extend(other.items);
+        self.index_names();
+    }
+
+    /// Chooses whether the output of the handler for `name` is parsed again,
+    /// so shortcodes it emits are expanded too.
+    ///
+    /// Re-expansion stops with [`RenderError::ReexpansionCycle`] when a tag
+    /// would expand itself again with the same attributes and content, and
+    /// with [`RenderError::ReexpansionDepthExceeded`] past
+    /// [`RenderLimits::max_reexpansion_depth`].
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// let mut sc = Shortcode::new();
+    /// sc.add("year", |_, _| "2024".to_string());
+    /// sc.add("footer", |_, _| "(c) [year]".to_string());
+    /// assert_eq!(sc.render("[footer]"), "(c) [year]");
+    ///
+    /// sc.set_reexpand("footer", true);
+    /// assert_eq!(sc.render("[footer]"), "(c) 2024");
+    /// ```
+    pub fn set_reexpand(&mut self, name: impl Into<String>, enabled: bool) {
+        let name = self.registered_name(name.into());
+        if enabled {
+            self.reexpand.insert(name);
+        } else {
+            self.reexpand.remove(&name);
+        }
+    }
+
+    /// Re-expands the output of every handler, as if [`Shortcode::set_reexpand`]
+    /// were enabled for all of them.
+    pub fn set_reexpand_all(&mut self, enabled: bool) {
+        self.reexpand_all = enabled;
+    }
+
+    /// Whether the output of the handler for `name` is parsed again.
+    pub(crate) fn reexpands(&self, name: &str) -> bool {
+        self.reexpand_all
+            || self
+                .lookup_entry(name)
+                .is_some_and(|(key, _)| self.reexpand.contains(key))
+    }
+
+    /// Checks every occurrence of `name` against `schema` before its handler
+    /// is called, replacing any schema set before.
+    ///
+    /// A tag that breaks its schema fails the render with
+    /// [`RenderError::InvalidAttribute`] or [`RenderError::ContentNotAllowed`].
+    /// Defaults of missing attributes are passed to the handler as if they
+    /// had been written. Use [`Shortcode::validate`] to list every problem
+    /// in an input instead.
+    ///
+    /// Under [`CaseMatching::Ascii`] or [`CaseMatching::Unicode`], `name`
+    /// may be written in any case if the handler is registered first.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use shortcode_parser::{AttrSpec, AttrType, RenderError, Schema};
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("hr", |_, attrs| format!("<hr class=\"{}\">", attrs.get("style").unwrap()));
+    /// sc.set_schema(
+    ///     "hr",
+    ///     Schema::new()
+    ///         .attr(AttrSpec::new("style", AttrType::one_of(["thin", "thick"])).default("thin"))
+    ///         .content(false),
+    /// );
+    /// assert_eq!(sc.render("[hr]"), "<hr class=\"thin\">");
+    /// assert_eq!(
+    ///     sc.try_render("[hr]x[/hr]"),
+    ///     Err(RenderError::ContentNotAllowed { tag: "[hr]".to_string() })
+    /// );
+    /// ```
+    pub fn set_schema(&mut self, name: impl Into<String>, schema: Schema) {
+        let name = self.registered_name(name.into());
+        self.schemas.insert(name, schema);
+    }
+
+    /// The name of the handler `name` finds under the case matching, so
+    /// settings stored for it apply however the name is written. Names
+    /// without a handler are kept as given.
+    fn registered_name(&self, name: String) -> String {
+        match self.lookup_entry(&name) {
+            Some((key, _)) => key.clone(),
+            None => name,
+        }
+    }
+
+    /// The schema checked for `name`, if its handler has one.
+    pub fn schema(&self, name: &str) -> Option<&Schema> {
+        self.lookup_entry(name)
+            .and_then(|(key, _)| self.schemas.get(key))
+    }
+
+    /// The defaults registered with the handler for `name`.
+    pub(crate) fn defaults(&self, name: &str) -> Option<&Defaults> {
+        self.lookup_entry(name)
+            .and_then(|(key, _)| self.defaults.get(key))
+    }
+
+    /// Reports every tag in `content` that breaks its schema, in source
+    /// order, without calling any handler.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use shortcode_parser::{AttrSpec, AttrType, Schema};
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("video", |_, _| String::new());
+    /// sc.set_schema("video", Schema::new().attr(AttrSpec::new("id", AttrType::Integer)));
+    ///
+    /// let input = "[video id=\"x\" loop]";
+    /// let messages: Vec<_> = sc
+    ///     .validate(input)
+    ///     .into_iter()
+    ///     .map(|d| (d.message, &input[d.span.unwrap()]))
+    ///     .collect();
+    /// assert_eq!(
+    ///     messages,
+    ///     [
+    ///         (
+    ///             "attribute `id` has invalid value \"x\": invalid digit found in string in `video`".to_string(),
+    ///             "id=\"x\""
+    ///         ),
+    ///         ("unknown attribute `loop` in `video`".to_string(), "loop"),
+    ///     ]
+    /// );
+    /// ```
+    pub fn validate(&self, content: &str) -> Vec<Diagnostic> {
+        self.validate_with(content, &Context::default())
+    }
+
+    /// Like [`Shortcode::validate`], but resolves [interpolated](Shortcode::set_interpolation)
+    /// variables against `ctx`, reporting those that are undefined.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use shortcode_parser::{Context, Interpolation};
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("link", |_, _| String::new());
+    /// sc.set_interpolation(Interpolation::new());
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.insert("site_url", "https://example.com");
+    /// let input = "[link href=\"{site_url}/{lang}/about\"]";
+    /// let diagnostics = sc.validate_with(input, &ctx);
+    /// assert_eq!(diagnostics.len(), 1);
+    /// assert_eq!(diagnostics[0].message, "undefined variable `lang` in `link`");
+    /// assert_eq!(&input[diagnostics[0].span.clone().unwrap()], "{lang}");
     /// ```
-    pub fn add(&mut self, name: &'a str, func: ShortcodeFn) {
-        self.items.push((name, func));
+    pub fn validate_with(&self, content: &str, ctx: &Context) -> Vec<Diagnostic> {
+        diagnostics::validate(self, content, ctx)
     }
 
-    /// Returns `true` if a handler is registered under `name`.
+    /// Returns `true` if a handler is registered under `name`, compared
+    /// with the [`CaseMatching`] in effect.
     ///
     /// ```rust
     /// use shortcode_parser::shortcode::Shortcode;
@@ -101,11 +529,14 @@ impl<'a> Shortcode<'a> {
     /// assert!(!sc.has("y"));
     /// ```
     pub fn has(&self, name: &str) -> bool {
-        self.items.iter().any(|(n, _)| *n == name)
+        self.lookup_entry(name).is_some()
     }
 
     /// Retrieves the handler function registered under `name`, if any.
     ///
+    /// Handlers registered with [`Shortcode::add_with_context`] are not
+    /// returned here.
+    ///
     /// ```rust
     /// use shortcode_parser::{shortcode::Shortcode, ShortcodeAttrs};
     /// let mut sc = Shortcode::new();
@@ -113,8 +544,67 @@ impl<'a> Shortcode<'a> {
     /// let f = sc.get("ping").expect("handler");
     /// assert_eq!(f(None, ShortcodeAttrs::new(&[])), "pong");
     /// ```
-    pub fn get(&self, name: &str) -> Option<&ShortcodeFn> {
-        self.items.iter().find(|(n, _)| *n == name).map(|(_, f)| f)
+    pub fn get(&self, name: &str) -> Option<&PlainHandler> {
+        match self.lookup(name)? {
+            Handler::Plain(func) => Some(func.as_ref()),
+            Handler::WithContext(_) | Handler::Typed(_) => None,
+        }
+    }
+
+    /// Finds the handler registered under `name`, of either flavor.
+    pub(crate) fn lookup(&self, name: &str) -> Option<&Handler> {
+        self.lookup_entry(name).map(|(_, handler)| handler)
+    }
+
+    /// Finds the registered name and handler for `name`, preferring an
+    /// exact match over one allowed by the case matching. Among names that
+    /// differ only in case, the first in byte order wins.
+    fn lookup_entry(&self, name: &str) -> Option<(&String, &Handler)> {
+        self.items
+            .get_key_value(name)
+            .or_else(|| match self.name_matching() {
+                CaseMatching::Exact => None,
+                case => self
+                    .items
+                    .get_key_value(self.folded.get(case.fold(name).as_ref())?),
+            })
+    }
+
+    fn insert_handler(&mut self, name: String, handler: Handler) {
+        let case = self.name_matching();
+        index_name(&mut self.folded, case, &name);
+        self.items.insert(name, handler);
+    }
+
+    /// Rebuilds `folded` after names are removed or the case matching changes.
+    fn index_names(&mut self) {
+        let case = self.name_matching();
+        self.folded.clear();
+        for name in self.items.keys() {
+            index_name(&mut self.folded, case, name);
+        }
+    }
+
+    /// Turns the registry into an immutable, cheaply clonable handle.
+    ///
+    /// Clones share the same handlers, so every worker thread can hold one
+    /// and render concurrently without locking.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use std::thread;
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("hi", |_, _| "hello".to_string());
+    /// let registry = sc.freeze();
+    ///
+    /// let worker = registry.clone();
+    /// let handle = thread::spawn(move || worker.render("[hi]").into_owned());
+    /// assert_eq!(handle.join().unwrap(), "hello");
+    /// assert_eq!(registry.render("[hi]"), "hello");
+    /// ```
+    pub fn freeze(self) -> FrozenShortcode {
+        FrozenShortcode(Arc::new(self))
     }
 
     /// Parses `content` and renders it by replacing all registered shortcodes.
@@ -137,21 +627,282 @@ impl<'a> Shortcode<'a> {
     /// assert_eq!(sc.render("plain text"), "plain text");
     /// ```
     pub fn render<'b>(&self, content: &'b str) -> Cow<'b, str> {
-        let parser = Parser::new(content);
-        let tokens = parser.parse();
+        self.render_with(content, &Context::default())
+    }
+
+    /// Like [`Shortcode::render`], but passes `ctx` to context-aware handlers.
+    pub fn render_with<'b>(&self, content: &'b str, ctx: &Context) -> Cow<'b, str> {
+        match self.render_tree(content, ctx, false) {
+            Ok(rendered) => rendered,
+            Err(_) => match &self.limits.fallback {
+                LimitFallback::Source => self.output_mode.text(content),
+                LimitFallback::Text(text) => Cow::Owned(text.clone()),
+            },
+        }
+    }
+
+    /// Like [`Shortcode::render`], but reports an exceeded [`RenderLimits`]
+    /// bound as an error instead of returning the fallback, and fails on a
+    /// tag its handler or schema rejects instead of writing it back.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use shortcode_parser::{RenderError, RenderLimits};
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("x", |_, _| "x".repeat(100));
+    /// sc.set_limits(RenderLimits {
+    ///     max_output_bytes: 150,
+    ///     ..RenderLimits::default()
+    /// });
+    /// assert!(sc.try_render("[x]").is_ok());
+    /// assert_eq!(
+    ///     sc.try_render("[x][x]"),
+    ///     Err(RenderError::OutputTooLarge { limit: 150 })
+    /// );
+    /// ```
+    pub fn try_render<'b>(&self, content: &'b str) -> Result<Cow<'b, str>, RenderError> {
+        self.try_render_with(content, &Context::default())
+    }
+
+    /// Like [`Shortcode::try_render`], but passes `ctx` to context-aware handlers.
+    pub fn try_render_with<'b>(
+        &self,
+        content: &'b str,
+        ctx: &Context,
+    ) -> Result<Cow<'b, str>, RenderError> {
+        self.render_tree(content, ctx, true)
+    }
+
+    /// Renders `content`; see [`render_nodes`] for `strict`.
+    fn render_tree<'b>(
+        &self,
+        content: &'b str,
+        ctx: &Context,
+        strict: bool,
+    ) -> Result<Cow<'b, str>, RenderError> {
+        let mut tokens = Parser::with_syntax(content, &self.syntax).peekable();
+
+        // A single text token spanning the whole input means there are no tags.
+        if matches!(tokens.peek(), Some(Token::Text(text)) if text.len() == content.len()) {
+            if content.len() > self.limits.max_output_bytes {
+                return Err(RenderError::OutputTooLarge {
+                    limit: self.limits.max_output_bytes,
+                });
+            }
+            return Ok(self.output_mode.text(content));
+        }
+
+        let renderer = Renderer::with_matching(tokens, self.syntax.dialect, self.case_matching);
+        Ok(Cow::Owned(render_nodes(
+            renderer.nodes(),
+            self,
+            ctx,
+            strict,
+        )?))
+    }
+
+    /// Sets how shortcodes are recognized in rendered content. See [`Syntax`].
+    pub fn set_syntax(&mut self, syntax: Syntax) {
+        self.syntax = syntax;
+        self.index_names();
+    }
+
+    /// How shortcodes are recognized in rendered content.
+    pub fn syntax(&self) -> &Syntax {
+        &self.syntax
+    }
+
+    /// Chooses whether text outside shortcodes is trusted. See [`OutputMode`].
+    ///
+    /// ```rust
+    /// use shortcode_parser::html::OutputMode;
+    /// use shortcode_parser::shortcode::Shortcode;
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("br", |_, _| "<br>".to_string());
+    /// sc.set_output_mode(OutputMode::Html);
+    /// assert_eq!(sc.render("<i>hi</i>[br]"), "&lt;i&gt;hi&lt;/i&gt;<br>");
+    /// ```
+    pub fn set_output_mode(&mut self, mode: OutputMode) {
+        self.output_mode = mode;
+    }
+
+    /// How text outside shortcodes is treated.
+    pub fn output_mode(&self) -> OutputMode {
+        self.output_mode
+    }
+
+    /// Chooses whether attribute values are HTML-escaped with
+    /// [`escape_attr`](crate::html::escape_attr) before handlers see them.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("video", |_, attrs| format!("<video id=\"{}\">", attrs.get("id").unwrap_or("")));
+    /// sc.set_escape_attrs(true);
+    /// assert_eq!(
+    ///     sc.render("[video id='\"><script>']"),
+    ///     "<video id=\"&quot;&gt;&lt;script&gt;\">"
+    /// );
+    /// ```
+    pub fn set_escape_attrs(&mut self, enabled: bool) {
+        self.escape_attrs = enabled;
+    }
+
+    /// Whether attribute values are HTML-escaped before handlers see them.
+    pub fn escape_attrs(&self) -> bool {
+        self.escape_attrs
+    }
+
+    /// Sets how tag names and attribute keys are compared. See [`CaseMatching`].
+    pub fn set_case_matching(&mut self, case: CaseMatching) {
+        self.case_matching = case;
+        self.index_names();
+    }
+
+    /// How tag names and attribute keys are compared.
+    pub fn case_matching(&self) -> CaseMatching {
+        self.case_matching
+    }
+
+    /// Sets what happens to an attribute given more than once. See
+    /// [`DuplicateAttrs`].
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use shortcode_parser::DuplicateAttrs;
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("id", |_, attrs| attrs.get_all("id").join(","));
+    /// assert_eq!(sc.render("[id id=\"1\" id=\"2\"]"), "1,2");
+    ///
+    /// sc.set_duplicate_attrs(DuplicateAttrs::LastWins);
+    /// assert_eq!(sc.render("[id id=\"1\" id=\"2\"]"), "2");
+    ///
+    /// sc.set_duplicate_attrs(DuplicateAttrs::Error);
+    /// assert_eq!(
+    ///     sc.try_render("[id id=\"1\" id=\"2\"]").unwrap_err().to_string(),
+    ///     "attribute `id` is given more than once in [id id=\"1\" id=\"2\"]"
+    /// );
+    /// ```
+    pub fn set_duplicate_attrs(&mut self, policy: DuplicateAttrs) {
+        self.duplicate_attrs = policy;
+    }
+
+    /// What happens to an attribute given more than once.
+    pub fn duplicate_attrs(&self) -> DuplicateAttrs {
+        self.duplicate_attrs
+    }
+
+    /// Turns on variable interpolation in attribute values, or off with
+    /// `None`. See [`Interpolation`].
+    ///
+    /// Variables are resolved against the render [`Context`], so the same
+    /// input can render differently per request.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use shortcode_parser::{Context, Interpolation};
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("greet", |_, attrs| format!("Hi {}", attrs.get("name").unwrap()));
+    /// sc.set_interpolation(Interpolation::new());
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.insert("user.name", "Ada");
+    /// assert_eq!(sc.render_with("[greet name=$user.name]", &ctx), "Hi Ada");
+    /// assert_eq!(sc.render("[greet name=\"$user.name\"]"), "Hi ");
+    ///
+    /// sc.set_interpolation(None);
+    /// assert_eq!(sc.render_with("[greet name=$user.name]", &ctx), "Hi $user.name");
+    /// ```
+    pub fn set_interpolation(&mut self, interpolation: impl Into<Option<Interpolation>>) {
+        self.interpolation = interpolation.into();
+    }
+
+    /// The interpolation applied to attribute values, if any.
+    pub fn interpolation(&self) -> Option<&Interpolation> {
+        self.interpolation.as_ref()
+    }
+
+    /// The case matching in effect, which for BBCode ignores at least ASCII case.
+    pub(crate) fn name_matching(&self) -> CaseMatching {
+        self.case_matching.for_dialect(self.syntax.dialect)
+    }
+
+    /// Sets the bounds applied to every render through this registry.
+    pub fn set_limits(&mut self, limits: RenderLimits) {
+        self.limits = limits;
+    }
+
+    /// The bounds applied to every render through this registry.
+    pub fn limits(&self) -> &RenderLimits {
+        &self.limits
+    }
+
+    /// Returns `true` if `content` contains an opening `name` tag and a
+    /// handler is registered for it.
+    ///
+    /// Scanning stops at the first matching tag, so the rest of the input is
+    /// never tokenized.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// let mut sc = Shortcode::new();
+    /// sc.add("gallery", |_, _| "".to_string());
+    /// assert!(sc.has_shortcode("Photos: [gallery ids=\"1,2\"]", "gallery"));
+    /// assert!(!sc.has_shortcode("Photos: [/gallery]", "gallery"));
+    /// assert!(!sc.has_shortcode("[video]", "video"));
+    /// ```
+    pub fn has_shortcode(&self, content: &str, name: &str) -> bool {
+        self.has(name)
+            && Parser::with_syntax(content, &self.syntax).any(|token| {
+                !matches!(token, Token::CloseTag(_))
+                    && token
+                        .tag_name()
+                        .is_some_and(|tag| self.name_matching().eq(tag, name))
+            })
+    }
+}
 
-        // Only one token and it's not a tag
-        if tokens.len() == 1 && tokens[0].tag_name().is_none() {
-            return Cow::Borrowed(content);
+/// Records `name` under its folded form. Among names that differ only in
+/// case, the first in byte order is kept.
+fn index_name(folded: &mut HashMap<String, String>, case: CaseMatching, name: &str) {
+    if case == CaseMatching::Exact {
+        return;
+    }
+    match folded.get_mut(case.fold(name).as_ref()) {
+        Some(key) if name < key.as_str() => *key = name.to_string(),
+        Some(_) => {}
+        None => {
+            folded.insert(case.fold(name).into_owned(), name.to_string());
         }
+    }
+}
+
+/// An immutable, shareable shortcode registry created by [`Shortcode::freeze`].
+///
+/// Cloning is an `Arc` clone. All read-only [`Shortcode`] methods, including
+/// rendering, are available through `Deref`.
+#[derive(Debug, Clone, Default)]
+pub struct FrozenShortcode(Arc<Shortcode>);
+
+impl Deref for FrozenShortcode {
+    type Target = Shortcode;
 
-        Cow::Owned(Renderer::new(&tokens).render(&self.items))
+    fn deref(&self) -> &Shortcode {
+        &self.0
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::html::OutputMode;
+    use crate::CompiledTemplate;
+    use crate::Delimiters;
 
     #[test]
     fn test_shortcode() {
@@ -229,6 +980,758 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_has_shortcode() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("test", |_, _| "Hello world".to_string());
+        assert!(shortcode.has_shortcode("a [test] b [unclosed", "test"));
+        assert!(shortcode.has_shortcode("[test name=\"x\"]", "test"));
+        assert!(!shortcode.has_shortcode("no tags here", "test"));
+        assert!(!shortcode.has_shortcode("[other]", "other"));
+    }
+
+    #[test]
+    fn test_render_with_context() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add_with_context("user", |content, _, ctx| {
+            format!(
+                "{}{}",
+                content.unwrap_or(""),
+                ctx.get("user").unwrap_or("guest")
+            )
+        });
+        let mut ctx = Context::new();
+        ctx.insert("user", "ada");
+        assert_eq!(shortcode.render_with("[user]Hi [/user]", &ctx), "Hi ada");
+        assert_eq!(shortcode.render("[user]Hi [/user]"), "Hi guest");
+        assert!(shortcode.has("user"));
+        assert!(shortcode.get("user").is_none());
+    }
+
+    #[test]
+    fn test_add_replaces_existing() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("test", |_, _| "first".to_string());
+        shortcode.add("test", |_, _| "second".to_string());
+        assert_eq!(shortcode.render("[test]"), "second");
+        assert_eq!(shortcode.names().count(), 1);
+    }
+
+    #[test]
+    fn test_add_with_context_replaces_plain() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("test", |_, _| "plain".to_string());
+        shortcode.add_with_context("test", |_, _, _| "context".to_string());
+        assert_eq!(shortcode.render("[test]"), "context");
+        assert!(shortcode.get("test").is_none());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("test", |_, _| "Hello world".to_string());
+        assert!(shortcode.remove("test"));
+        assert!(!shortcode.has("test"));
+        assert!(!shortcode.remove("test"));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("a", |_, _| "A".to_string());
+        shortcode.add("b", |_, _| "B".to_string());
+        let mut other = Shortcode::new();
+        other.add("b", |_, _| "B2".to_string());
+        other.add("c", |_, _| "C".to_string());
+
+        shortcode.merge(other);
+        let mut names: Vec<_> = shortcode.names().collect();
+        names.sort_unstable();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!(shortcode.render("[a][b][c]"), "AB2C");
+    }
+
+    #[test]
+    fn test_many_handlers() {
+        let mut shortcode = Shortcode::new();
+        for i in 0..200 {
+            shortcode.add(format!("sc{i}"), move |_, _| i.to_string());
+        }
+        assert_eq!(shortcode.render("[sc0] [sc150] [sc199]"), "0 150 199");
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("x", |content, _| content.unwrap_or("").to_string());
+        shortcode.set_limits(RenderLimits {
+            max_depth: 3,
+            ..RenderLimits::default()
+        });
+        let ok = "[x][x][x]a[/x][/x][/x]";
+        let deep = "[x][x][x][x]a[/x][/x][/x][/x]";
+        assert_eq!(shortcode.try_render(ok), Ok(Cow::Borrowed("a")));
+        assert_eq!(
+            shortcode.try_render(deep),
+            Err(RenderError::DepthExceeded { limit: 3 })
+        );
+        // Unknown tags count towards depth too.
+        assert!(shortcode
+            .try_render("[y][y][y][x]a[/x][/y][/y][/y]")
+            .is_err());
+        assert_eq!(shortcode.render(deep), deep);
+    }
+
+    #[test]
+    fn test_expansion_limit() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("x", |_, _| "X".to_string());
+        shortcode.set_limits(RenderLimits {
+            max_expansions: 2,
+            fallback: LimitFallback::Text("too many".to_string()),
+            ..RenderLimits::default()
+        });
+        assert_eq!(shortcode.render("[x][x][y]"), "XX[y]");
+        assert_eq!(
+            shortcode.try_render("[x][x][x]"),
+            Err(RenderError::ExpansionsExceeded { limit: 2 })
+        );
+        assert_eq!(shortcode.render("[x][x][x]"), "too many");
+    }
+
+    #[test]
+    fn test_output_limit() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("big", |_, _| "x".repeat(1_000_000));
+        shortcode.add("wrap", |content, _| content.unwrap_or("").to_string());
+        shortcode.set_limits(RenderLimits {
+            max_output_bytes: 1_000,
+            ..RenderLimits::default()
+        });
+        let err = Err(RenderError::OutputTooLarge { limit: 1_000 });
+        assert_eq!(shortcode.try_render("[big]"), err);
+        assert_eq!(shortcode.try_render("[wrap][big][/wrap]"), err);
+        assert_eq!(shortcode.try_render(&"a".repeat(1_001)), err);
+        assert_eq!(shortcode.render("[big]"), "[big]");
+    }
+
+    #[test]
+    fn test_reexpand_opt_in() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("year", |_, _| "2024".to_string());
+        shortcode.add("snippet", |_, attrs| match attrs.get("name") {
+            Some("footer") => "(c) [year] [snippet name=\"credits\"]".to_string(),
+            Some("credits") => "by [b]us[/b]".to_string(),
+            _ => String::new(),
+        });
+        shortcode.add("b", |content, _| {
+            format!("<b>{}</b>", content.unwrap_or(""))
+        });
+
+        let content = "[snippet name=\"footer\"]";
+        assert_eq!(
+            shortcode.render(content),
+            "(c) [year] [snippet name=\"credits\"]"
+        );
+
+        shortcode.set_reexpand("snippet", true);
+        assert_eq!(shortcode.render(content), "(c) 2024 by <b>us</b>");
+
+        shortcode.set_reexpand("snippet", false);
+        shortcode.set_reexpand_all(true);
+        assert_eq!(shortcode.render(content), "(c) 2024 by <b>us</b>");
+    }
+
+    #[test]
+    fn test_reexpand_enclosing_content() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("wrap", |content, _| {
+            format!("[b]{}[/b]", content.unwrap_or(""))
+        });
+        shortcode.add("b", |content, _| {
+            format!("<b>{}</b>", content.unwrap_or(""))
+        });
+        shortcode.set_reexpand("wrap", true);
+        assert_eq!(shortcode.render("[wrap]hi[/wrap]"), "<b>hi</b>");
+    }
+
+    #[test]
+    fn test_reexpand_cycle() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("a", |_, _| "[b]".to_string());
+        shortcode.add("b", |_, _| "[a]".to_string());
+        shortcode.add("self", |_, _| "again [self]".to_string());
+        shortcode.set_reexpand_all(true);
+
+        assert_eq!(
+            shortcode.try_render("x [a]"),
+            Err(RenderError::ReexpansionCycle {
+                tag: "[a]".to_string()
+            })
+        );
+        assert_eq!(
+            shortcode.try_render("[self]"),
+            Err(RenderError::ReexpansionCycle {
+                tag: "[self]".to_string()
+            })
+        );
+        assert_eq!(shortcode.render("x [a]"), "x [a]");
+
+        // Without re-expansion of `b`, its output is left as text.
+        shortcode.set_reexpand_all(false);
+        shortcode.set_reexpand("a", true);
+        assert_eq!(shortcode.render("[a]"), "[a]");
+    }
+
+    #[test]
+    fn test_reexpand_depth_limit() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("count", |_, attrs| {
+            let n: usize = attrs.get("n").unwrap_or("0").parse().unwrap();
+            format!("{n} [count n=\"{}\"]", n + 1)
+        });
+        shortcode.set_reexpand("count", true);
+        shortcode.set_limits(RenderLimits {
+            max_reexpansion_depth: 3,
+            ..RenderLimits::default()
+        });
+        assert_eq!(
+            shortcode.try_render("[count]"),
+            Err(RenderError::ReexpansionDepthExceeded { limit: 3 })
+        );
+
+        shortcode.add("count", |_, attrs| {
+            let n: usize = attrs.get("n").unwrap_or("0").parse().unwrap();
+            if n < 2 {
+                format!("{n} [count n=\"{}\"]", n + 1)
+            } else {
+                n.to_string()
+            }
+        });
+        assert_eq!(shortcode.render("[count]"), "0 1 2");
+    }
+
+    #[test]
+    fn test_reexpand_remove_and_merge() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("one", |_, _| "1".to_string());
+        shortcode.add("a", |_, _| "[one]".to_string());
+        shortcode.set_reexpand("a", true);
+        assert!(shortcode.remove("a"));
+        shortcode.add("a", |_, _| "[one]".to_string());
+        assert_eq!(shortcode.render("[a]"), "[one]");
+
+        let mut other = Shortcode::new();
+        other.add("a", |_, _| "[one]!".to_string());
+        other.set_reexpand("a", true);
+        shortcode.merge(other);
+        assert_eq!(shortcode.render("[a]"), "1!");
+    }
+
+    #[test]
+    fn test_html_output_mode() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("b", |content, _| {
+            format!("<b>{}</b>", content.unwrap_or(""))
+        });
+        shortcode.add("footer", |_, _| "<p>[b]&copy;[/b]</p>".to_string());
+        shortcode.set_output_mode(OutputMode::Html);
+
+        assert_eq!(shortcode.render("a < b"), "a &lt; b");
+        assert_eq!(shortcode.render("plain"), Cow::Borrowed("plain"));
+        assert_eq!(
+            shortcode.render("[b]<script>[/b] [u x=\"<\"]&[/u] [/i]"),
+            "<b>&lt;script&gt;</b> [u x=\"&lt;\"]&amp;[/u] [/i]"
+        );
+
+        // Re-expanded handler output stays trusted.
+        shortcode.set_reexpand("footer", true);
+        assert_eq!(shortcode.render("<[footer]"), "&lt;<p><b>&copy;</b></p>");
+
+        // The source fallback is escaped too.
+        shortcode.set_limits(RenderLimits {
+            max_expansions: 0,
+            ..RenderLimits::default()
+        });
+        assert_eq!(shortcode.render("<i>[b]x[/b]"), "&lt;i&gt;[b]x[/b]");
+    }
+
+    #[test]
+    fn test_escape_attrs() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("video", |_, attrs| {
+            let flags = attrs.iter().filter(|(_, value)| value.is_none()).count();
+            format!(
+                "<video id=\"{}\" flags={flags}>",
+                attrs.get("id").unwrap_or("")
+            )
+        });
+        let content = "[video id='1\" onerror=\"alert(1)' autoplay]";
+
+        assert_eq!(
+            shortcode.render(content),
+            "<video id=\"1\" onerror=\"alert(1)\" flags=1>"
+        );
+        shortcode.set_escape_attrs(true);
+        assert_eq!(
+            shortcode.render(content),
+            "<video id=\"1&quot; onerror=&quot;alert(1)\" flags=1>"
+        );
+    }
+
+    #[test]
+    fn test_html_syntax() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("year", |_, _| "2024".to_string());
+        shortcode.add("note", |content, _| {
+            format!("<aside>{}</aside>", content.unwrap_or(""))
+        });
+        shortcode.set_syntax(Syntax::html());
+
+        let content =
+            "<p title=\"[year]\">[note]<code>[year]</code> [year][/note]</p><!--[year]-->";
+        assert_eq!(
+            shortcode.render(content),
+            "<p title=\"[year]\"><aside><code>[year]</code> 2024</aside></p><!--[year]-->"
+        );
+        assert!(!shortcode.has_shortcode("<pre>[year]</pre>", "year"));
+        assert_eq!(
+            shortcode.render("<style>a[x]{}</style>"),
+            "<style>a[x]{}</style>"
+        );
+
+        let template = CompiledTemplate::with_syntax(content, shortcode.syntax());
+        assert_eq!(
+            template.render(&shortcode, &Context::new()),
+            shortcode.render(content)
+        );
+    }
+
+    #[test]
+    fn test_markdown_syntax() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("note", |content, _| {
+            format!("<aside>{}</aside>", content.unwrap_or(""))
+        });
+        shortcode.set_syntax(Syntax::markdown());
+
+        let content = "# Usage\n\nWrap text in `[note]`:\n\n```text\n[note]hi[/note]\n```\n\n[note]See [the docs](https://x/[note]) and `[/note]`.[/note]";
+        assert_eq!(
+            shortcode.render(content),
+            "# Usage\n\nWrap text in `[note]`:\n\n```text\n[note]hi[/note]\n```\n\n<aside>See [the docs](https://x/[note]) and `[/note]`.</aside>"
+        );
+        assert!(!shortcode.has_shortcode("`[note]`", "note"));
+    }
+
+    struct Size(u32);
+
+    impl ShortcodeArgs for Size {
+        fn from_attrs(attrs: &ShortcodeAttrs) -> Result<Self, AttrError> {
+            Ok(Size(attrs.get_required("px")?))
+        }
+    }
+
+    #[test]
+    fn test_add_typed() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add_typed("size", |content, size: Size| {
+            format!("<span size=\"{}\">{}</span>", size.0, content.unwrap_or(""))
+        });
+        assert!(shortcode.has("size"));
+        assert!(shortcode.get("size").is_none());
+        assert_eq!(
+            shortcode.render("[size px=\"3\"]a[/size]"),
+            "<span size=\"3\">a</span>"
+        );
+
+        // Escaped attributes are parsed too, and failures name the tag.
+        shortcode.set_escape_attrs(true);
+        assert_eq!(
+            shortcode.try_render("x [size px=\"<\"]"),
+            Err(RenderError::InvalidAttribute {
+                tag: "[size px=\"<\"]".to_string(),
+                error: AttrError::Invalid {
+                    name: "px".to_string(),
+                    value: "&lt;".to_string(),
+                    reason: "invalid digit found in string".to_string(),
+                },
+            })
+        );
+        shortcode.set_limits(RenderLimits {
+            fallback: LimitFallback::Text("?".to_string()),
+            ..RenderLimits::default()
+        });
+        // Only the rejected tag is written back, not the whole input.
+        assert_eq!(
+            shortcode.render("x [size] [size px=\"2\"]b[/size]"),
+            "x [size] <span size=\"2\">b</span>"
+        );
+    }
+
+    #[test]
+    fn test_rejected_tags_are_written_back() {
+        use crate::schema::Schema;
+
+        let mut shortcode = Shortcode::new();
+        shortcode.add("b", |content, _| {
+            format!("<b>{}</b>", content.unwrap_or(""))
+        });
+        shortcode.add("hr", |_, _| "<hr>".to_string());
+        shortcode.set_schema("hr", Schema::new().content(false));
+        shortcode.set_interpolation(Interpolation::new().undefined(crate::UndefinedVars::Error));
+        shortcode.set_limits(RenderLimits {
+            max_depth: 3,
+            fallback: LimitFallback::Text("?".to_string()),
+            ..RenderLimits::default()
+        });
+
+        let input = "[hr]x [b]y[/b][/hr] [b title=\"$missing\"]z[/b] [hr]";
+        assert_eq!(
+            shortcode.render(input),
+            "[hr]x <b>y</b>[/hr] [b title=\"$missing\"]z[/b] <hr>"
+        );
+        assert_eq!(
+            shortcode.try_render(input),
+            Err(RenderError::ContentNotAllowed {
+                tag: "[hr]".to_string()
+            })
+        );
+        let template = CompiledTemplate::new(input);
+        assert_eq!(
+            template.render(&shortcode, &Context::new()),
+            shortcode.render(input)
+        );
+
+        // Exceeded limits still replace the whole input.
+        assert_eq!(shortcode.render("[b][b][b][b]x[/b][/b][/b][/b]"), "?");
+    }
+
+    #[test]
+    fn test_schema() {
+        use crate::schema::{AttrSpec, AttrType, Schema};
+
+        let mut shortcode = Shortcode::new();
+        shortcode.add_typed("size", |_, size: Size| size.0.to_string());
+        shortcode.set_schema(
+            "size",
+            Schema::new()
+                .attr(AttrSpec::new("px", AttrType::Integer).default("<16>"))
+                .content(false),
+        );
+        // Defaults reach the handler, escaped like written values.
+        assert_eq!(
+            shortcode.try_render("[size px=\"2\"]"),
+            Ok(Cow::Owned("2".to_string()))
+        );
+        shortcode.set_escape_attrs(true);
+        assert_eq!(
+            shortcode.try_render("[size]").unwrap_err().to_string(),
+            "attribute `px` has invalid value \"&lt;16&gt;\": invalid digit found in string in [size]"
+        );
+
+        // The schema is checked before the handler runs.
+        assert_eq!(
+            shortcode.try_render("[size px=\"2\" em=\"1\"]"),
+            Err(RenderError::InvalidAttribute {
+                tag: "[size px=\"2\" em=\"1\"]".to_string(),
+                error: AttrError::Unknown {
+                    name: "em".to_string()
+                },
+            })
+        );
+        assert_eq!(
+            shortcode.try_render("[size px=\"2\"]x[/size]"),
+            Err(RenderError::ContentNotAllowed {
+                tag: "[size px=\"2\"]".to_string()
+            })
+        );
+
+        let mut other = Shortcode::new();
+        other.add("size", |_, _| "other".to_string());
+        shortcode.merge(other);
+        assert!(shortcode.schema("size").is_none());
+        assert_eq!(shortcode.render("[size em=\"1\"]x[/size]"), "other");
+
+        shortcode.set_schema("size", Schema::new());
+        assert!(shortcode.remove("size"));
+        shortcode.add("size", |_, _| String::new());
+        assert!(shortcode.schema("size").is_none());
+    }
+
+    #[test]
+    fn test_add_with_defaults() {
+        use crate::schema::{AttrSpec, AttrType, Defaults, Schema};
+
+        let mut shortcode = Shortcode::new();
+        let describe = |_: Option<&str>, attrs: ShortcodeAttrs| {
+            attrs
+                .iter()
+                .map(|(name, value)| {
+                    let source = if attrs.is_default(name) {
+                        "default"
+                    } else {
+                        "author"
+                    };
+                    format!("{name}={}({source})", value.unwrap_or(""))
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        shortcode.add_with_defaults(
+            "video",
+            Defaults::new([("width", "640"), ("title", "<untitled>")]),
+            describe,
+        );
+        assert_eq!(
+            shortcode.render("[video WIDTH=\"1\" loop]"),
+            "WIDTH=1(author) loop=(author) width=640(default) title=<untitled>(default)"
+        );
+
+        // Defaults are escaped and matched like written attributes.
+        shortcode.set_case_matching(CaseMatching::Ascii);
+        shortcode.set_escape_attrs(true);
+        assert_eq!(
+            shortcode.render("[video WIDTH=\"1\"]"),
+            "WIDTH=1(author) title=&lt;untitled&gt;(default)"
+        );
+
+        // Dropped attributes are not checked by the schema, whose defaults
+        // count as defaults too.
+        shortcode.add_with_defaults(
+            "video",
+            Defaults::new([("width", "640")]).drop_unknown(true),
+            describe,
+        );
+        shortcode.set_schema(
+            "video",
+            Schema::new()
+                .attr(AttrSpec::new("width", AttrType::Integer))
+                .attr(AttrSpec::new("ratio", AttrType::Number).default("1.5")),
+        );
+        let input = "[video loop]";
+        assert!(shortcode.validate(input).is_empty());
+        assert_eq!(
+            shortcode.render(input),
+            "ratio=1.5(default) width=640(default)"
+        );
+
+        // Registering the name again forgets its defaults.
+        shortcode.add("video", describe);
+        assert_eq!(shortcode.render("[video]"), "ratio=1.5(default)");
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_json_attrs() {
+        let mut sc = Shortcode::new();
+        sc.add("chart", |_, attrs| {
+            let data: Vec<u32> = attrs.get_json("data").unwrap().unwrap_or_default();
+            let options: serde_json::Value = attrs.get_json("options").unwrap().unwrap();
+            format!("{data:?} {}", options["stacked"])
+        });
+        assert_eq!(
+            sc.render("[chart data='[1,2,3]' options='{\"stacked\":true}'] after"),
+            "[1, 2, 3] true after"
+        );
+    }
+
+    #[test]
+    fn test_interpolation() {
+        use crate::interpolate::{Interpolation, UndefinedVars};
+
+        let mut shortcode = Shortcode::new();
+        shortcode.add("link", |content, attrs| {
+            format!(
+                "<a href=\"{}\">{}</a>",
+                attrs.get("href").unwrap_or(""),
+                content.unwrap_or("")
+            )
+        });
+        shortcode.set_interpolation(Interpolation::new().undefined(UndefinedVars::Keep));
+        shortcode.set_escape_attrs(true);
+        let template = CompiledTemplate::new("[link href=\"{base}/$page\"]$page[/link]");
+
+        // Resolved values are escaped with the rest; content is left alone.
+        let mut ctx = Context::new();
+        ctx.insert("base", "/a&b");
+        assert_eq!(
+            template.try_render(&shortcode, &ctx),
+            Ok("<a href=\"/a&amp;b/$page\">$page</a>".to_string())
+        );
+
+        shortcode.set_interpolation(Interpolation::new().undefined(UndefinedVars::Error));
+        assert_eq!(
+            template.try_render(&shortcode, &ctx),
+            Err(RenderError::UndefinedVariable {
+                tag: "[link href=\"{base}/$page\"]".to_string(),
+                name: "page".to_string(),
+            })
+        );
+        ctx.insert("page", "x");
+        assert_eq!(
+            template.try_render(&shortcode, &ctx),
+            Ok("<a href=\"/a&amp;b/x\">$page</a>".to_string())
+        );
+    }
+
+    #[test]
+    fn test_case_matching() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("video", |_, attrs| {
+            format!("<video {}>", attrs.get("id").unwrap_or("?"))
+        });
+        shortcode.add("Note", |content, _| {
+            format!("<aside>{}</aside>", content.unwrap_or(""))
+        });
+        shortcode.add("note", |content, _| {
+            format!("<p>{}</p>", content.unwrap_or(""))
+        });
+        assert_eq!(shortcode.case_matching(), CaseMatching::Exact);
+        assert_eq!(shortcode.render("[VIDEO ID=\"1\"]"), "[VIDEO ID=\"1\"]");
+
+        shortcode.set_case_matching(CaseMatching::Ascii);
+        assert_eq!(
+            shortcode.render("[Video ID=\"1\"] [VIDEO id=\"2\"]"),
+            "<video 1> <video 2>"
+        );
+        // An exact match wins over a case-insensitive one.
+        assert_eq!(
+            shortcode.render("[Note]a[/NOTE] [note]b[/Note] [NOTE]c[/note]"),
+            "<aside>a</aside> <p>b</p> <aside>c</aside>"
+        );
+        // Tags left as source keep their closing tag as written.
+        assert_eq!(shortcode.render("[Foo]x[/FOO]"), "[Foo]x[/FOO]");
+        assert!(shortcode.has_shortcode("[VIDEO]", "video"));
+        assert!(shortcode.has_shortcode("[video]", "VIDEO"));
+        assert!(shortcode.has("VIDEO"));
+        assert!(shortcode.get("VIDEO").is_some());
+    }
+
+    #[test]
+    fn test_case_matching_settings() {
+        use crate::schema::{AttrSpec, AttrType, Schema};
+
+        let mut shortcode = Shortcode::new();
+        shortcode.set_case_matching(CaseMatching::Ascii);
+        shortcode.add("video", |_, attrs| {
+            format!("<video {}>", attrs.get("id").unwrap_or("?"))
+        });
+        shortcode.add("footer", |_, _| "[video id=\"1\"]".to_string());
+        shortcode.set_schema(
+            "Video",
+            Schema::new().attr(AttrSpec::new("id", AttrType::Integer).required()),
+        );
+        shortcode.set_reexpand("FOOTER", true);
+
+        assert!(shortcode.schema("VIDEO").is_some());
+        assert!(matches!(
+            shortcode.try_render("[video]"),
+            Err(RenderError::InvalidAttribute { .. })
+        ));
+        assert_eq!(shortcode.render("[Footer]"), "<video 1>");
+        assert_eq!(shortcode.render("[Video]x[/VIDEO]"), "[Video]x[/VIDEO]");
+    }
+
+    #[test]
+    fn test_case_matching_index() {
+        let mut shortcode = Shortcode::new();
+        shortcode.set_case_matching(CaseMatching::Unicode);
+        shortcode.add("note", |_, _| "lower".to_string());
+        shortcode.add("Note", |_, _| "title".to_string());
+        assert_eq!(shortcode.render("[NOTE]"), "title");
+
+        assert!(shortcode.remove("Note"));
+        assert_eq!(shortcode.render("[NOTE]"), "lower");
+
+        let mut theme = Shortcode::new();
+        theme.add("ÉTÉ", |_, _| "summer".to_string());
+        shortcode.merge(theme);
+        assert_eq!(shortcode.render("[été] [Note]"), "summer lower");
+
+        shortcode.set_case_matching(CaseMatching::Exact);
+        assert_eq!(shortcode.render("[été] [Note]"), "[été] [Note]");
+        shortcode.set_syntax(Syntax::bbcode());
+        assert_eq!(shortcode.render("[été] [Note]"), "[été] lower");
+    }
+
+    #[test]
+    fn test_case_matching_reexpand() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("year", |_, _| "2024".to_string());
+        shortcode.add("footer", |_, _| "(c) [YEAR]".to_string());
+        shortcode.set_reexpand("footer", true);
+        shortcode.set_case_matching(CaseMatching::Unicode);
+        assert_eq!(shortcode.render("[Footer]"), "(c) 2024");
+    }
+
+    #[test]
+    fn test_custom_delimiters() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("b", |content, _| {
+            format!("<b>{}</b>", content.unwrap_or(""))
+        });
+        shortcode.set_syntax(Syntax {
+            delimiters: Delimiters::DOUBLE_ANGLE,
+            ..Syntax::new()
+        });
+        assert_eq!(
+            shortcode.render("<<b>>x<</b>> [b] <<u id=\"1\">>y<</u>> <</i>>"),
+            "<b>x</b> [b] <<u id=\"1\">>y<</u>> <</i>>"
+        );
+
+        shortcode.set_syntax(Syntax {
+            delimiters: Delimiters::HUGO_MARKDOWN,
+            ..Syntax::new()
+        });
+        assert_eq!(
+            shortcode.render("{{% b %}}x{{% /b %}} {{%u%}}y{{%/u%}}"),
+            "<b>x</b> {{% u %}}y{{% /u %}}"
+        );
+        assert!(shortcode.has_shortcode("{{% b %}}", "b"));
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<Shortcode>();
+        assert_send_sync::<FrozenShortcode>();
+    }
+
+    #[test]
+    fn test_closure_handler_with_owned_name() {
+        let prefix = String::from(">> ");
+        let name = String::from("quote");
+        let mut shortcode = Shortcode::new();
+        shortcode.add(name, move |content, _| {
+            format!("{}{}", prefix, content.unwrap_or(""))
+        });
+        assert_eq!(shortcode.render("[quote]hi[/quote]"), ">> hi");
+    }
+
+    #[test]
+    fn test_frozen_renders_across_threads() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("n", |_, attrs| attrs.get("v").unwrap_or("").to_string());
+        let frozen = shortcode.freeze();
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let registry = frozen.clone();
+                std::thread::spawn(move || registry.render(&format!("[n v=\"{i}\"]")).into_owned())
+            })
+            .collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results, ["0", "1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_stray_close_tag_not_handled() {
+        let mut shortcode = Shortcode::new();
+        shortcode.add("test", |_, _| "Hello world".to_string());
+        assert_eq!(shortcode.render("a [/test] b"), "a [/test] b");
+        assert_eq!(shortcode.render("[/test][/test]"), "[/test][/test]");
+    }
+
     #[test]
     fn test_nested_shortcodes() {
         let mut shortcode = Shortcode::new();
@@ -0,0 +1,439 @@
+//! Options controlling where and how shortcodes are recognized.
+//!
+//! A [`Syntax`] is passed to [`Tokenizer::with_syntax`](crate::Tokenizer::with_syntax)
+//! and [`Parser::with_syntax`](crate::Parser::with_syntax), or set on a
+//! registry with [`Shortcode::set_syntax`](crate::shortcode::Shortcode::set_syntax).
+//! The default recognizes `[name]` tags anywhere in plain text.
+
+use std::borrow::Cow;
+
+/// How shortcodes are recognized in the input.
+///
+/// Example:
+/// ```rust
+/// use shortcode_parser::shortcode::Shortcode;
+/// use shortcode_parser::Syntax;
+///
+/// let mut sc = Shortcode::new();
+/// sc.add("year", |_, _| "2024".to_string());
+/// sc.set_syntax(Syntax::html());
+///
+/// assert_eq!(
+///     sc.render("<p>[year]</p><code>[year]</code>"),
+///     "<p>2024</p><code>[year]</code>"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Syntax {
+    /// The kind of document shortcodes are embedded in.
+    pub content: ContentMode,
+    /// The strings that open and close a tag.
+    pub delimiters: Delimiters,
+    /// The grammar of the tags themselves.
+    pub dialect: Dialect,
+}
+
+/// The syntax used when none is given.
+pub(crate) static DEFAULT_SYNTAX: Syntax = Syntax::new();
+
+impl Syntax {
+    /// Bracket shortcodes in plain text.
+    pub const fn new() -> Self {
+        Self {
+            content: ContentMode::Plain,
+            delimiters: Delimiters::BRACKETS,
+            dialect: Dialect::Shortcode,
+        }
+    }
+
+    /// BBCode in plain text. See [`Dialect::BbCode`].
+    pub fn bbcode() -> Self {
+        Self {
+            dialect: Dialect::BbCode,
+            ..Self::new()
+        }
+    }
+
+    /// Bracket shortcodes in Markdown prose. See [`ContentMode::Markdown`].
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use shortcode_parser::Syntax;
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("year", |_, _| "2024".to_string());
+    /// sc.set_syntax(Syntax::markdown());
+    ///
+    /// let doc = "Write `[year]` to get [year].\n\n```\n[year]\n```\n[docs](/year)";
+    /// assert_eq!(
+    ///     sc.render(doc),
+    ///     "Write `[year]` to get 2024.\n\n```\n[year]\n```\n[docs](/year)"
+    /// );
+    /// ```
+    pub fn markdown() -> Self {
+        Self {
+            content: ContentMode::Markdown,
+            ..Self::new()
+        }
+    }
+
+    /// Bracket shortcodes in HTML, skipping the default [`HtmlRegions`].
+    pub fn html() -> Self {
+        Self {
+            content: ContentMode::Html(HtmlRegions::default()),
+            ..Self::new()
+        }
+    }
+}
+
+/// The strings that open and close a tag, and the marker that starts the
+/// name of a closing tag.
+///
+/// Example:
+/// ```rust
+/// use shortcode_parser::shortcode::Shortcode;
+/// use shortcode_parser::{Delimiters, Syntax};
+///
+/// let mut sc = Shortcode::new();
+/// sc.add("b", |content, _| format!("<b>{}</b>", content.unwrap_or("")));
+/// sc.set_syntax(Syntax {
+///     delimiters: Delimiters::HUGO,
+///     ..Syntax::new()
+/// });
+/// assert_eq!(sc.render("{{< b >}}hi{{< /b >}} [b]"), "<b>hi</b> [b]");
+///
+/// // Liquid-style block tags close with `{% endname %}`.
+/// sc.set_syntax(Syntax {
+///     delimiters: Delimiters::new("{%", "%}").padded(true).with_close_marker("end"),
+///     ..Syntax::new()
+/// });
+/// assert_eq!(sc.render("{% b %}hi{% endb %}"), "<b>hi</b>");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delimiters {
+    open: Cow<'static, str>,
+    close: Cow<'static, str>,
+    close_marker: Cow<'static, str>,
+    padded: bool,
+}
+
+impl Delimiters {
+    /// `[name]` and `[/name]`.
+    pub const BRACKETS: Self = Self::fixed("[", "]", false);
+    /// Hugo's `{{< name >}}` and `{{< /name >}}`.
+    pub const HUGO: Self = Self::fixed("{{<", ">}}", true);
+    /// Hugo's `{{% name %}}` and `{{% /name %}}`.
+    pub const HUGO_MARKDOWN: Self = Self::fixed("{{%", "%}}", true);
+    /// `<<name>>` and `<</name>>`.
+    pub const DOUBLE_ANGLE: Self = Self::fixed("<<", ">>", false);
+
+    const fn fixed(open: &'static str, close: &'static str, padded: bool) -> Self {
+        Self {
+            open: Cow::Borrowed(open),
+            close: Cow::Borrowed(close),
+            close_marker: Cow::Borrowed("/"),
+            padded,
+        }
+    }
+
+    /// Tags written as `{open}name{close}`, closed by `{open}/name{close}`.
+    ///
+    /// # Panics
+    ///
+    /// If `open` or `close` is empty.
+    pub fn new(open: impl Into<Cow<'static, str>>, close: impl Into<Cow<'static, str>>) -> Self {
+        let (open, close) = (open.into(), close.into());
+        assert!(
+            !open.is_empty() && !close.is_empty(),
+            "shortcode delimiters must not be empty"
+        );
+        Self {
+            open,
+            close,
+            close_marker: Cow::Borrowed("/"),
+            padded: false,
+        }
+    }
+
+    /// Uses `marker` instead of `/` to start the name of a closing tag.
+    ///
+    /// A tag whose name starts with the marker is always a closing tag.
+    ///
+    /// # Panics
+    ///
+    /// If `marker` is empty.
+    pub fn with_close_marker(mut self, marker: impl Into<Cow<'static, str>>) -> Self {
+        let marker = marker.into();
+        assert!(!marker.is_empty(), "close-tag marker must not be empty");
+        self.close_marker = marker;
+        self
+    }
+
+    /// Whether whitespace just inside the delimiters is ignored, and written
+    /// back when a tag is rendered verbatim, as in `{{< name >}}`.
+    pub fn padded(mut self, padded: bool) -> Self {
+        self.padded = padded;
+        self
+    }
+
+    pub fn open(&self) -> &str {
+        &self.open
+    }
+
+    pub fn close(&self) -> &str {
+        &self.close
+    }
+
+    pub fn close_marker(&self) -> &str {
+        &self.close_marker
+    }
+
+    pub fn is_padded(&self) -> bool {
+        self.padded
+    }
+
+    /// Writes a tag with the given inner text.
+    pub(crate) fn wrap(&self, inner: &str) -> String {
+        if self.padded {
+            format!("{} {} {}", self.open, inner, self.close)
+        } else {
+            format!("{}{}{}", self.open, inner, self.close)
+        }
+    }
+}
+
+impl Default for Delimiters {
+    /// [`Delimiters::BRACKETS`].
+    fn default() -> Self {
+        Self::BRACKETS
+    }
+}
+
+/// The grammar of tags.
+///
+/// Example:
+/// ```rust
+/// use shortcode_parser::{Parser, Syntax, Token};
+///
+/// let syntax = Syntax::bbcode();
+/// let tokens = Parser::with_syntax("[url=https://example.com]", &syntax).parse();
+/// assert_eq!(
+///     tokens,
+///     [Token::SelfCloseAttr("url", vec![("", Some("https://example.com"))])]
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// `[name key="value" flag]`, with names matched exactly.
+    #[default]
+    Shortcode,
+    /// BBCode:
+    ///
+    /// - `[name=value]` gives the tag a value, read with
+    ///   [`ShortcodeAttrs::value`](crate::ShortcodeAttrs::value). It may be
+    ///   quoted, and is otherwise the rest of the tag.
+    /// - `[*]` list items end at the next `[*]` or at the end of the
+    ///   enclosing `[list]`, `[ul]` or `[ol]`. An unregistered `[*]` is
+    ///   written back without a closing tag.
+    /// - Tag names and attribute keys are matched ignoring at least ASCII
+    ///   case. See [`CaseMatching`].
+    /// - Tags inside `[code]...[/code]` are not recognized.
+    ///
+    /// Handlers for the common tags are in [`bbcode`](crate::bbcode).
+    BbCode,
+}
+
+/// How tag names and attribute keys are compared.
+///
+/// Applies to finding handlers, pairing opening and closing tags, and
+/// [`ShortcodeAttrs::get`](crate::ShortcodeAttrs::get). A handler registered
+/// under the exact name is preferred over one that differs only in case.
+///
+/// Trees built ahead of rendering, by [`Document`](crate::Document) and
+/// [`CompiledTemplate`](crate::CompiledTemplate), pair tags by their
+/// [`Syntax`] alone, so case differences only pair there for BBCode.
+///
+/// Example:
+/// ```rust
+/// use shortcode_parser::shortcode::Shortcode;
+/// use shortcode_parser::CaseMatching;
+///
+/// let mut sc = Shortcode::new();
+/// sc.add("note", |content, attrs| {
+///     format!("{}: {}", attrs.get("id").unwrap_or(""), content.unwrap_or(""))
+/// });
+/// sc.set_case_matching(CaseMatching::Ascii);
+/// assert_eq!(sc.render("[Note ID=\"1\"]hi[/NOTE]"), "1: hi");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaseMatching {
+    /// Names must match exactly.
+    #[default]
+    Exact,
+    /// ASCII letters match regardless of case.
+    Ascii,
+    /// Names match if they are equal after lowercasing each character,
+    /// so `[Ärger]` closes with `[/ärger]`. Lowercasing is per character,
+    /// without locale or context rules.
+    Unicode,
+}
+
+impl CaseMatching {
+    /// The matching used with `dialect`: BBCode always ignores ASCII case.
+    pub(crate) fn for_dialect(self, dialect: Dialect) -> Self {
+        match (self, dialect) {
+            (CaseMatching::Exact, Dialect::BbCode) => CaseMatching::Ascii,
+            _ => self,
+        }
+    }
+
+    /// Whether two names are the same.
+    pub(crate) fn eq(&self, a: &str, b: &str) -> bool {
+        match self {
+            CaseMatching::Exact => a == b,
+            CaseMatching::Ascii => a.eq_ignore_ascii_case(b),
+            CaseMatching::Unicode => a
+                .chars()
+                .flat_map(char::to_lowercase)
+                .eq(b.chars().flat_map(char::to_lowercase)),
+        }
+    }
+
+    /// A key equal for all names that are the same, borrowing `name` when
+    /// it already is one.
+    pub(crate) fn fold<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match self {
+            CaseMatching::Ascii if name.bytes().any(|b| b.is_ascii_uppercase()) => {
+                Cow::Owned(name.to_ascii_lowercase())
+            }
+            CaseMatching::Unicode if name.chars().any(|c| c.to_lowercase().ne([c])) => {
+                Cow::Owned(name.chars().flat_map(char::to_lowercase).collect())
+            }
+            _ => Cow::Borrowed(name),
+        }
+    }
+}
+
+/// The kind of document shortcodes are embedded in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ContentMode {
+    /// Shortcodes are recognized anywhere.
+    #[default]
+    Plain,
+    /// Shortcodes are not recognized inside HTML comments, inside the markup
+    /// of HTML tags (including attribute values), or inside the given
+    /// elements.
+    Html(HtmlRegions),
+    /// Shortcodes are only recognized in Markdown prose: not in fenced or
+    /// indented code blocks, code spans, inline links `[text](url)` and
+    /// images, or after a backslash escape (`\[`).
+    Markdown,
+}
+
+/// HTML elements whose content is left untouched in [`ContentMode::Html`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlRegions {
+    /// Element names, compared ignoring ASCII case.
+    pub elements: Vec<String>,
+}
+
+impl HtmlRegions {
+    /// Protects the content of the given elements.
+    ///
+    /// ```rust
+    /// use shortcode_parser::shortcode::Shortcode;
+    /// use shortcode_parser::{ContentMode, HtmlRegions, Syntax};
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("x", |_, _| "X".to_string());
+    /// sc.set_syntax(Syntax {
+    ///     content: ContentMode::Html(HtmlRegions::new(["kbd"])),
+    ///     ..Syntax::new()
+    /// });
+    /// assert_eq!(sc.render("<kbd>[x]</kbd> <code>[x]</code>"), "<kbd>[x]</kbd> <code>X</code>");
+    /// ```
+    pub fn new<S: Into<String>>(elements: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            elements: elements.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub(crate) fn is_protected(&self, name: &str) -> bool {
+        self.elements
+            .iter()
+            .any(|element| element.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Default for HtmlRegions {
+    /// `code`, `pre`, `script` and `style`.
+    fn default() -> Self {
+        Self::new(["code", "pre", "script", "style"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_plain() {
+        assert_eq!(Syntax::default(), Syntax::new());
+        assert_eq!(DEFAULT_SYNTAX.content, ContentMode::Plain);
+        assert_eq!(DEFAULT_SYNTAX.dialect, Dialect::Shortcode);
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(Delimiters::BRACKETS.wrap("a b"), "[a b]");
+        assert_eq!(Delimiters::HUGO.wrap("/a"), "{{< /a >}}");
+        assert_eq!(Delimiters::new("«", "»").wrap("a"), "«a»");
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be empty")]
+    fn test_empty_delimiter_panics() {
+        Delimiters::new("", "]");
+    }
+
+    #[test]
+    fn test_case_matching_eq() {
+        assert!(CaseMatching::Exact.eq("b", "b"));
+        assert!(!CaseMatching::Exact.eq("B", "b"));
+        assert!(CaseMatching::Ascii.eq("URL", "url"));
+        assert!(!CaseMatching::Ascii.eq("url", "urls"));
+        assert!(!CaseMatching::Ascii.eq("Ärger", "ärger"));
+        assert!(CaseMatching::Unicode.eq("Ärger", "äRGER"));
+        assert!(CaseMatching::Unicode.eq("ΣΑΣ", "σασ"));
+        assert!(!CaseMatching::Unicode.eq("ä", "a"));
+    }
+
+    #[test]
+    fn test_case_matching_fold() {
+        assert!(matches!(CaseMatching::Ascii.fold("note"), Cow::Borrowed(_)));
+        assert_eq!(CaseMatching::Ascii.fold("NoTe"), "note");
+        assert_eq!(CaseMatching::Ascii.fold("Ä"), "Ä");
+        assert!(matches!(
+            CaseMatching::Unicode.fold("ärger"),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(CaseMatching::Unicode.fold("ÄRGER"), "ärger");
+        assert_eq!(CaseMatching::Exact.fold("B"), "B");
+        assert_eq!(
+            CaseMatching::Exact.for_dialect(Dialect::BbCode),
+            CaseMatching::Ascii
+        );
+        assert_eq!(
+            CaseMatching::Unicode.for_dialect(Dialect::BbCode),
+            CaseMatching::Unicode
+        );
+    }
+
+    #[test]
+    fn test_is_protected_ignores_case() {
+        let regions = HtmlRegions::default();
+        assert!(regions.is_protected("PRE"));
+        assert!(regions.is_protected("script"));
+        assert!(!regions.is_protected("p"));
+    }
+}
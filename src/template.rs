@@ -0,0 +1,240 @@
+use crate::ast::Document;
+use crate::code::{render_nodes, render_source};
+use crate::context::Context;
+use crate::limits::{LimitFallback, RenderError};
+use crate::shortcode::Shortcode;
+use crate::syntax::Syntax;
+
+/// A parsed input that can be rendered many times.
+///
+/// [`Shortcode::render`] tokenizes, parses and builds a tree on every call.
+/// A `CompiledTemplate` does that once and owns the result, so it has no
+/// lifetime tied to the source string. It is `Send + Sync` and can be cached
+/// and shared between threads.
+///
+/// Example:
+/// ```rust
+/// use shortcode_parser::{shortcode::Shortcode, CompiledTemplate, Context};
+///
+/// let template = CompiledTemplate::new("Hello, [user]!");
+///
+/// let mut sc = Shortcode::new();
+/// sc.add_with_context("user", |_, _, ctx| ctx.get("name").unwrap_or("guest").to_string());
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("name", "Ada");
+/// assert_eq!(template.render(&sc, &ctx), "Hello, Ada!");
+/// assert_eq!(template.render(&sc, &Context::new()), "Hello, guest!");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompiledTemplate {
+    document: Document,
+}
+
+impl CompiledTemplate {
+    /// Parses `content` into an owned tree.
+    pub fn new(content: &str) -> Self {
+        Self::from(Document::parse(content))
+    }
+
+    /// Parses `content` once, recognizing tags according to `syntax`.
+    ///
+    /// Tags are paired by exact name. To match what [`Shortcode::render`]
+    /// would expand, use [`CompiledTemplate::for_registry`].
+    pub fn with_syntax(content: &str, syntax: &Syntax) -> Self {
+        Self::from(Document::parse_with(content, syntax))
+    }
+
+    /// Parses `content` once with the registry's [`Shortcode::syntax`] and
+    /// [`Shortcode::case_matching`], so the template pairs tags the same
+    /// way [`Shortcode::render`] does.
+    ///
+    /// ```rust
+    /// use shortcode_parser::{shortcode::Shortcode, CaseMatching, CompiledTemplate, Context};
+    ///
+    /// let mut sc = Shortcode::new();
+    /// sc.add("note", |content, _| format!("<aside>{}</aside>", content.unwrap_or("")));
+    /// sc.set_case_matching(CaseMatching::Ascii);
+    ///
+    /// let template = CompiledTemplate::for_registry("[Note]hi[/note]", &sc);
+    /// assert_eq!(template.render(&sc, &Context::new()), "<aside>hi</aside>");
+    /// ```
+    pub fn for_registry(content: &str, shortcodes: &Shortcode) -> Self {
+        Self::from(Document::parse_with_case(
+            content,
+            shortcodes.syntax(),
+            shortcodes.case_matching(),
+        ))
+    }
+
+    /// The parsed tree this template renders.
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    /// Renders the template against `shortcodes`, passing `ctx` to
+    /// context-aware handlers.
+    ///
+    /// A tag rejected by its handler or schema is written back as source.
+    /// If the registry's [`RenderLimits`](crate::RenderLimits) are exceeded,
+    /// returns their fallback instead.
+    pub fn render(&self, shortcodes: &Shortcode, ctx: &Context) -> String {
+        match render_nodes(&self.document.nodes, shortcodes, ctx, false) {
+            Ok(rendered) => rendered,
+            Err(_) => match &shortcodes.limits().fallback {
+                LimitFallback::Source => {
+                    let syntax = shortcodes.syntax();
+                    let source =
+                        render_source(&self.document.nodes, &syntax.delimiters, syntax.dialect);
+                    shortcodes.output_mode().text(&source).into_owned()
+                }
+                LimitFallback::Text(text) => text.clone(),
+            },
+        }
+    }
+
+    /// Like [`CompiledTemplate::render`], but reports an exceeded limit or
+    /// a rejected tag as an error.
+    pub fn try_render(&self, shortcodes: &Shortcode, ctx: &Context) -> Result<String, RenderError> {
+        render_nodes(&self.document.nodes, shortcodes, ctx, true)
+    }
+}
+
+impl From<Document> for CompiledTemplate {
+    fn from(document: Document) -> Self {
+        Self { document }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<CompiledTemplate>();
+    }
+
+    #[test]
+    fn test_render_matches_shortcode_render() {
+        let mut sc = Shortcode::new();
+        sc.add("b", |content, _| {
+            format!("<b>{}</b>", content.unwrap_or(""))
+        });
+        sc.add("video", |_, attrs| {
+            format!("<video id=\"{}\"/>", attrs.get("id").unwrap_or(""))
+        });
+
+        let inputs = [
+            "plain text",
+            "[b]bold [video id=\"1\"][/b]",
+            "[unknown x=\"1\" flag]inner [b]b[/b][/unknown] tail [/stray]",
+            "[b][b]nested[/b][/b] [unclosed",
+        ];
+        for input in inputs {
+            let template = CompiledTemplate::new(input);
+            assert_eq!(template.render(&sc, &Context::new()), sc.render(input));
+        }
+    }
+
+    #[test]
+    fn test_render_repeatedly_with_contexts() {
+        let mut sc = Shortcode::new();
+        sc.add_with_context("n", |_, _, ctx| ctx.get("n").unwrap_or("").to_string());
+        let template = CompiledTemplate::new("[n],[n]");
+        for n in ["1", "2", "3"] {
+            let mut ctx = Context::new();
+            ctx.insert("n", n);
+            assert_eq!(template.render(&sc, &ctx), format!("{n},{n}"));
+        }
+    }
+
+    #[test]
+    fn test_from_document() {
+        let document = Document::parse("[x] y");
+        let template = CompiledTemplate::from(document.clone());
+        assert_eq!(template.document(), &document);
+
+        let mut sc = Shortcode::new();
+        sc.add("x", |_, _| "X".to_string());
+        assert_eq!(template.render(&sc, &Context::new()), "X y");
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut sc = Shortcode::new();
+        sc.add("x", |_, _| "X".to_string());
+        sc.set_limits(crate::RenderLimits {
+            max_expansions: 1,
+            ..crate::RenderLimits::default()
+        });
+        let template = CompiledTemplate::new("[x] and [x id=\"2\"]");
+        assert_eq!(
+            template.try_render(&sc, &Context::new()),
+            Err(RenderError::ExpansionsExceeded { limit: 1 })
+        );
+        assert_eq!(
+            template.render(&sc, &Context::new()),
+            "[x] and [x id=\"2\"]"
+        );
+    }
+
+    #[test]
+    fn test_limit_fallback_keeps_delimiters() {
+        let mut sc = Shortcode::new();
+        sc.add("x", |content, _| content.unwrap_or_default().to_string());
+        sc.set_syntax(Syntax {
+            delimiters: crate::Delimiters::HUGO,
+            ..Syntax::new()
+        });
+        sc.set_limits(crate::RenderLimits {
+            max_depth: 1,
+            ..crate::RenderLimits::default()
+        });
+        let input = "{{< x >}}{{< x a=\"1\" >}}a{{< /x >}}{{< /x >}} {{< y >}}";
+        let template = CompiledTemplate::with_syntax(input, sc.syntax());
+        assert_eq!(template.render(&sc, &Context::new()), sc.render(input));
+        assert_eq!(template.render(&sc, &Context::new()), input);
+    }
+
+    #[test]
+    fn test_for_registry_case_matching() {
+        let mut sc = Shortcode::new();
+        sc.add("note", |content, _| {
+            format!("<aside>{}</aside>", content.unwrap_or(""))
+        });
+        sc.set_case_matching(crate::CaseMatching::Ascii);
+        for input in ["[Note]hi[/note]", "[NOTE]a [note]b[/Note][/note]"] {
+            let template = CompiledTemplate::for_registry(input, &sc);
+            assert_eq!(template.render(&sc, &Context::new()), sc.render(input));
+        }
+        let unknown = "[Foo]x[/FOO]";
+        assert_eq!(
+            CompiledTemplate::for_registry(unknown, &sc).render(&sc, &Context::new()),
+            unknown
+        );
+        assert_eq!(
+            CompiledTemplate::for_registry("[Note]hi[/note]", &sc).render(&sc, &Context::new()),
+            "<aside>hi</aside>"
+        );
+
+        sc.set_syntax(Syntax::bbcode());
+        sc.set_case_matching(crate::CaseMatching::Exact);
+        let input = "[Note]hi[/NOTE]";
+        let template = CompiledTemplate::for_registry(input, &sc);
+        assert_eq!(template.render(&sc, &Context::new()), sc.render(input));
+    }
+
+    #[test]
+    fn test_outlives_source() {
+        let template = {
+            let source = String::from("[x]");
+            CompiledTemplate::new(&source)
+        };
+        let mut sc = Shortcode::new();
+        sc.add("x", |_, _| "X".to_string());
+        assert_eq!(template.render(&sc, &Context::new()), "X");
+    }
+}
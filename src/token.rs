@@ -1,6 +1,8 @@
+use crate::syntax::Delimiters;
 use std::borrow::Cow;
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Token<'a> {
     Text(&'a str),
     SelfClose(&'a str),
@@ -9,7 +11,7 @@ pub enum Token<'a> {
 }
 
 impl<'a> Token<'a> {
-    pub fn tag_name(&self) -> Option<&str> {
+    pub fn tag_name(&self) -> Option<&'a str> {
         match self {
             Token::SelfClose(name) => Some(name),
             Token::SelfCloseAttr(name, _) => Some(name),
@@ -19,22 +21,40 @@ impl<'a> Token<'a> {
     }
 
     pub fn render_raw(&self) -> Cow<'a, str> {
+        self.render_raw_with(&Delimiters::BRACKETS)
+    }
+
+    /// Like [`Token::render_raw`], but writes tags with `delimiters`.
+    pub fn render_raw_with(&self, delimiters: &Delimiters) -> Cow<'a, str> {
         match self {
             Token::Text(text) => Cow::Borrowed(text),
-            Token::SelfClose(name) => Cow::Owned(format!("[{}]", name)),
-            Token::CloseTag(name) => Cow::Owned(format!("[/{}]", name)),
-            Token::SelfCloseAttr(name, attrs) => {
-                Cow::Owned(format!("[{} {}]", name, Token::attrs_to_string(attrs)))
+            Token::SelfClose(name) => Cow::Owned(delimiters.wrap(name)),
+            Token::CloseTag(name) => {
+                Cow::Owned(delimiters.wrap(&format!("{}{}", delimiters.close_marker(), name)))
             }
+            // A leading value without a name is BBCode's `[name=value]`.
+            Token::SelfCloseAttr(name, attrs) => Cow::Owned(match attrs.as_slice() {
+                [("", Some(value))] => delimiters.wrap(&format!("{}={}", name, quote(value))),
+                [("", Some(value)), rest @ ..] => delimiters.wrap(&format!(
+                    "{}={} {}",
+                    name,
+                    quote(value),
+                    Token::attrs_to_string(rest)
+                )),
+                _ => delimiters.wrap(&format!("{} {}", name, Token::attrs_to_string(attrs))),
+            }),
         }
     }
 
+    /// Writes attribute pairs as they would appear in a tag. Values are
+    /// double-quoted, or single-quoted if they contain a double quote, and
+    /// written as is if they contain both. Names are written as given.
     pub fn attrs_to_string(attrs: &[(&str, Option<&str>)]) -> String {
         attrs
             .iter()
             .map(|(name, value)| {
                 if let Some(v) = value {
-                    format!("{}=\"{}\"", name, v)
+                    format!("{}={}", name, quote(v))
                 } else {
                     name.to_string()
                 }
@@ -50,3 +70,22 @@ impl<'a> Token<'a> {
         }
     }
 }
+
+/// Quotes an attribute value, in double quotes unless it contains one.
+///
+/// The parser has no escapes inside quotes, so a value containing both
+/// kinds of quote is written unquoted, which is how the parser reads such
+/// a value. Values that also contain a space or a bracket cannot be
+/// written; their double quotes are written as `&quot;`. The parser never
+/// produces them, and [`Tag::to_source`](crate::Tag::to_source) rejects them.
+pub(crate) fn quote(value: &str) -> String {
+    if !value.contains('"') {
+        format!("\"{}\"", value)
+    } else if !value.contains('\'') {
+        format!("'{}'", value)
+    } else if !value.starts_with(['"', '\'']) && !value.contains([' ', '[', ']']) {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('"', "&quot;"))
+    }
+}
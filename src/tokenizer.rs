@@ -1,14 +1,45 @@
+use crate::regions;
+use crate::scan;
+use crate::syntax::{ContentMode, Dialect, Syntax, DEFAULT_SYNTAX};
+use std::cell::Cell;
+use std::ops::Range;
+
 /// Byte-level scanner that identifies text regions and tag boundaries.
 ///
-/// Walks the input looking for `[` and `]`, extracting raw segments.
-/// Does not interpret tag names or attributes — that is the parser's job.
+/// Walks the input looking for `[` and `]`, or the configured
+/// [`Delimiters`](crate::Delimiters), extracting raw segments.
+/// Does not interpret tag names or attributes — that is the parser's job,
+/// except that a `]` inside a quoted attribute value does not end a tag.
 /// If an unclosed `[` is encountered, everything from there to the end
 /// is treated as raw text.
+///
+/// The tokenizer is an `Iterator` over `TokenSegment`s, so callers can stop
+/// scanning as soon as they have seen what they need.
+///
+/// A [`Syntax`] can exclude regions of the input, such as HTML `<code>`
+/// elements, from the search; they are returned as part of the text.
+#[derive(Debug, Clone)]
 pub struct Tokenizer<'a> {
     content: &'a str,
     bytes: &'a [u8],
+    pos: usize,
+    emitted: bool,
+    syntax: &'a Syntax,
+    /// Sorted byte ranges where tags are not recognized, found up front for
+    /// syntaxes that need the whole input to decide.
+    protected: Vec<Range<usize>>,
+    /// Start of the tag closing a verbatim block that was just opened.
+    verbatim_end: Option<usize>,
+    /// Where HTML tag markup was found to run to the end of the input.
+    unclosed_markup: Cell<usize>,
+    /// For each of [`BBCODE_VERBATIM`], a position after which no tag
+    /// closes it, so blocks that are never closed are searched for once.
+    unclosed_verbatim: [usize; BBCODE_VERBATIM.len()],
 }
 
+/// BBCode tags whose content is not searched for tags.
+const BBCODE_VERBATIM: &[&str] = &["code"];
+
 /// A raw segment produced by the tokenizer.
 /// Either plain text between tags, or the raw bytes between `[` and `]`.
 #[derive(Debug, PartialEq)]
@@ -19,65 +50,243 @@ pub enum TokenSegment<'a> {
 
 impl<'a> Tokenizer<'a> {
     pub fn new(content: &'a str) -> Self {
+        Self::with_syntax(content, &DEFAULT_SYNTAX)
+    }
+
+    /// Creates a tokenizer that recognizes tags according to `syntax`.
+    ///
+    /// ```rust
+    /// use shortcode_parser::{Syntax, TokenSegment, Tokenizer};
+    ///
+    /// let syntax = Syntax::html();
+    /// let segments: Vec<_> = Tokenizer::with_syntax("<!-- [a] -->[b]", &syntax).collect();
+    /// assert_eq!(
+    ///     segments,
+    ///     [TokenSegment::Text("<!-- [a] -->"), TokenSegment::Tag(b"b")]
+    /// );
+    /// ```
+    pub fn with_syntax(content: &'a str, syntax: &'a Syntax) -> Self {
         Self {
             content,
             bytes: content.as_bytes(),
+            pos: 0,
+            emitted: false,
+            syntax,
+            protected: match syntax.content {
+                ContentMode::Markdown => regions::markdown(content),
+                ContentMode::Plain | ContentMode::Html(_) => Vec::new(),
+            },
+            verbatim_end: None,
+            unclosed_markup: Cell::new(usize::MAX),
+            unclosed_verbatim: [usize::MAX; BBCODE_VERBATIM.len()],
         }
     }
 
-    /// Scan the input and return raw segments.
+    /// Scan the whole input and return raw segments.
     ///
     /// Returns a list of `TokenSegment` values:
     /// - `Text` for regions between tags
-    /// - `Tag` for the raw bytes between `[` and `]` (brackets excluded)
+    /// - `Tag` for the raw bytes between `[` and `]` (delimiters excluded)
     ///
     /// If no tags are found, returns a single `Text` segment covering
     /// the entire input. If an unclosed `[` is encountered, the rest
     /// of the input becomes a single `Text` segment.
     pub fn tokenize(&self) -> Vec<TokenSegment<'a>> {
-        let mut segments = vec![];
-        let mut text_start = 0;
-        let mut pos = 0;
-        let total_len = self.bytes.len();
+        Self::with_syntax(self.content, self.syntax).collect()
+    }
+
+    pub(crate) fn syntax(&self) -> &'a Syntax {
+        self.syntax
+    }
 
-        while pos < total_len {
-            if self.bytes[pos] == b'[' {
-                // Push text before this tag (if any).
-                if text_start < pos {
-                    segments.push(TokenSegment::Text(&self.content[text_start..pos]));
+    /// Position of the first occurrence of `needle` at or after `from`.
+    fn find_str(&self, needle: &str, from: usize) -> Option<usize> {
+        let needle = needle.as_bytes();
+        let mut pos = from;
+        while let Some(offset) = scan::find_byte(needle[0], &self.bytes[pos..]) {
+            let found = pos + offset;
+            if self.bytes[found..].starts_with(needle) {
+                return Some(found);
+            }
+            pos = found + 1;
+        }
+        None
+    }
+
+    /// Position of the closing delimiter of a tag whose inner bytes start at
+    /// `from`. A quoted attribute value, one opening right after `=`, may
+    /// contain the closing delimiter, as in `[chart data='[1,2,3]']`. An
+    /// unterminated quote does not hide it.
+    fn find_close(&self, from: usize) -> Option<usize> {
+        let close = self.syntax.delimiters.close();
+        let mut pos = from;
+        let mut after_eq = false;
+        loop {
+            let end = self.find_str(close, pos)?;
+            let quote = (pos..end).find(|&i| match self.bytes[i] {
+                b'"' | b'\'' if after_eq => true,
+                b'=' => {
+                    after_eq = true;
+                    false
                 }
-                let bracket_pos = pos;
-                // Scan to closing `]`.
-                pos += 1; // Skip `[`
-                let tag_start = pos;
-                while pos < total_len && self.bytes[pos] != b']' {
-                    pos += 1;
+                b' ' => false,
+                _ => {
+                    after_eq = false;
+                    false
                 }
-                if pos < total_len {
-                    // Found closing `]` — emit tag segment.
-                    segments.push(TokenSegment::Tag(&self.bytes[tag_start..pos]));
-                    pos += 1; // Skip `]`
-                    text_start = pos;
-                } else {
-                    // No closing `]` — treat `[` and everything after as text.
-                    segments.push(TokenSegment::Text(&self.content[bracket_pos..total_len]));
-                    text_start = total_len;
-                    break;
+            });
+            let Some(open) = quote else {
+                return Some(end);
+            };
+            match scan::find_byte(self.bytes[open], &self.bytes[open + 1..]) {
+                Some(offset) => {
+                    pos = open + offset + 2;
+                    after_eq = false;
                 }
-            } else {
-                pos += 1;
+                None => return Some(end),
             }
         }
+    }
 
-        // Push any remaining text after the last tag.
-        if text_start < total_len {
-            segments.push(TokenSegment::Text(&self.content[text_start..]));
-        } else if segments.is_empty() {
-            // No tags found and no trailing text — entire input is text.
-            segments.push(TokenSegment::Text(self.content));
+    /// The inner bytes of a tag, with padding removed if the delimiters
+    /// are padded.
+    fn inner(&self, raw: &'a [u8]) -> &'a [u8] {
+        if self.syntax.delimiters.is_padded() {
+            raw.trim_ascii()
+        } else {
+            raw
         }
+    }
 
-        segments
+    /// If `raw` opens a BBCode verbatim block, the position of the tag
+    /// closing it. Blocks without a closing tag are not verbatim.
+    fn verbatim_end(&mut self, raw: &[u8], from: usize) -> Option<usize> {
+        let raw = self.inner(raw);
+        let name_end = raw
+            .iter()
+            .position(|&b| b == b' ' || b == b'=')
+            .unwrap_or(raw.len());
+        let name = &raw[..name_end];
+        let index = BBCODE_VERBATIM
+            .iter()
+            .position(|verbatim| verbatim.as_bytes().eq_ignore_ascii_case(name))?;
+        if from >= self.unclosed_verbatim[index] {
+            return None;
+        }
+        let end = self.find_verbatim_close(BBCODE_VERBATIM[index], from);
+        if end.is_none() {
+            self.unclosed_verbatim[index] = from;
+        }
+        end
+    }
+
+    /// Position of the first tag at or after `from` closing `verbatim`.
+    fn find_verbatim_close(&self, verbatim: &str, from: usize) -> Option<usize> {
+        let delimiters = &self.syntax.delimiters;
+        let mut pos = from;
+        while let Some(open) = self.find_str(delimiters.open(), pos) {
+            let inner_start = open + delimiters.open().len();
+            let close = self.find_str(delimiters.close(), inner_start)?;
+            let closes = self
+                .inner(&self.bytes[inner_start..close])
+                .strip_prefix(delimiters.close_marker().as_bytes())
+                .is_some_and(|name| name.eq_ignore_ascii_case(verbatim.as_bytes()));
+            if closes {
+                return Some(open);
+            }
+            pos = inner_start;
+        }
+        None
+    }
+
+    /// Position of the next opening delimiter at or after `from` that can
+    /// open a tag, or the end of the input.
+    fn next_tag_start(&self, from: usize) -> usize {
+        let delimiter = self.syntax.delimiters.open();
+        let find = |from: usize| self.find_str(delimiter, from);
+
+        let html = match &self.syntax.content {
+            ContentMode::Plain => return find(from).unwrap_or(self.bytes.len()),
+            ContentMode::Markdown => {
+                let mut pos = from;
+                while let Some(open) = find(pos) {
+                    let next = self.protected.partition_point(|range| range.end <= open);
+                    match self.protected.get(next) {
+                        Some(range) if range.start <= open => pos = range.end,
+                        _ => return open,
+                    }
+                }
+                return self.bytes.len();
+            }
+            ContentMode::Html(html) => html,
+        };
+
+        let mut pos = from;
+        let mut bracket = find(pos);
+        while let Some(open) = bracket {
+            let Some(lt) = scan::find_byte(b'<', &self.bytes[pos..open]).map(|offset| pos + offset)
+            else {
+                return open;
+            };
+            let mut unclosed = self.unclosed_markup.get();
+            pos = regions::html(self.content, lt, html, &mut unclosed).unwrap_or(lt + 1);
+            self.unclosed_markup.set(unclosed);
+            if pos > open {
+                bracket = find(pos);
+            }
+        }
+        self.bytes.len()
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = TokenSegment<'a>;
+
+    /// Scan forward to the next segment.
+    ///
+    /// Empty text regions between adjacent tags are skipped. An empty input
+    /// yields a single empty `Text` segment so callers always see the input.
+    fn next(&mut self) -> Option<Self::Item> {
+        let total_len = self.bytes.len();
+        if self.pos >= total_len {
+            if self.emitted {
+                return None;
+            }
+            self.emitted = true;
+            return Some(TokenSegment::Text(self.content));
+        }
+        self.emitted = true;
+
+        let start = self.pos;
+        let tag_pos = match self.verbatim_end.take() {
+            Some(end) => end,
+            None => self.next_tag_start(start),
+        };
+        if tag_pos > start {
+            // Text up to the next tag, or to the end of the input.
+            self.pos = tag_pos;
+            return Some(TokenSegment::Text(&self.content[start..self.pos]));
+        }
+
+        // Scan to the closing delimiter, skipping the opening one.
+        let delimiters = &self.syntax.delimiters;
+        let tag_start = start + delimiters.open().len();
+        match self.find_close(tag_start) {
+            Some(tag_end) => {
+                // Found closing delimiter — emit tag segment and skip past it.
+                self.pos = tag_end + delimiters.close().len();
+                let raw = &self.bytes[tag_start..tag_end];
+                if self.syntax.dialect == Dialect::BbCode {
+                    self.verbatim_end = self.verbatim_end(raw, self.pos);
+                }
+                Some(TokenSegment::Tag(raw))
+            }
+            None => {
+                // No closing delimiter — treat the opener and everything after as text.
+                self.pos = total_len;
+                Some(TokenSegment::Text(&self.content[start..]))
+            }
+        }
     }
 }
 
@@ -85,6 +294,24 @@ impl<'a> Tokenizer<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_iter_short_circuits() {
+        let mut tok = Tokenizer::new("intro [a] middle [b");
+        assert_eq!(tok.next(), Some(TokenSegment::Text("intro ")));
+        assert_eq!(tok.next(), Some(TokenSegment::Tag(b"a")));
+        // The remaining input is only scanned on demand.
+        assert_eq!(tok.next(), Some(TokenSegment::Text(" middle ")));
+        assert_eq!(tok.next(), Some(TokenSegment::Text("[b")));
+        assert_eq!(tok.next(), None);
+    }
+
+    #[test]
+    fn test_iter_empty_yields_once() {
+        let mut tok = Tokenizer::new("");
+        assert_eq!(tok.next(), Some(TokenSegment::Text("")));
+        assert_eq!(tok.next(), None);
+    }
+
     #[test]
     fn test_tokenize_empty() {
         let tok = Tokenizer::new("");
@@ -159,6 +386,22 @@ mod tests {
         assert_eq!(segs[2], TokenSegment::Text("[unclosed"));
     }
 
+    #[test]
+    fn test_tokenize_quoted_close() {
+        let tok = Tokenizer::new("[chart data='[1,2,3]' opts = '{\"a\":[]}']x[it's] [b n=\"a]");
+        let segs = tok.tokenize();
+        assert_eq!(
+            segs,
+            [
+                TokenSegment::Tag(b"chart data='[1,2,3]' opts = '{\"a\":[]}'"),
+                TokenSegment::Text("x"),
+                TokenSegment::Tag(b"it's"),
+                TokenSegment::Text(" "),
+                TokenSegment::Tag(b"b n=\"a"),
+            ]
+        );
+    }
+
     #[test]
     fn test_tokenize_empty_brackets() {
         let tok = Tokenizer::new("[]");
@@ -477,4 +720,165 @@ mod tests {
         assert_eq!(segs[3], TokenSegment::Tag(b"/tag"));
         assert_eq!(segs[4], TokenSegment::Text("   "));
     }
+
+    fn html_segments(content: &str) -> Vec<TokenSegment<'_>> {
+        static HTML: std::sync::LazyLock<Syntax> = std::sync::LazyLock::new(Syntax::html);
+        Tokenizer::with_syntax(content, &HTML).collect()
+    }
+
+    #[test]
+    fn test_html_skips_protected_elements() {
+        assert_eq!(
+            html_segments("<pre>[a]</pre>[b]<script>x = [1];</script>"),
+            [
+                TokenSegment::Text("<pre>[a]</pre>"),
+                TokenSegment::Tag(b"b"),
+                TokenSegment::Text("<script>x = [1];</script>"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_html_skips_comments_and_attributes() {
+        assert_eq!(
+            html_segments("<!-- [a] --><a title=\"[b]\" data-x=[c]>[d]</a>"),
+            [
+                TokenSegment::Text("<!-- [a] --><a title=\"[b]\" data-x=[c]>"),
+                TokenSegment::Tag(b"d"),
+                TokenSegment::Text("</a>"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_html_recognizes_tags_in_plain_elements() {
+        assert_eq!(
+            html_segments("a < b <p>[x]</p>"),
+            [
+                TokenSegment::Text("a < b <p>"),
+                TokenSegment::Tag(b"x"),
+                TokenSegment::Text("</p>"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_html_unclosed_protected_element() {
+        assert_eq!(
+            html_segments("[a]<code>[b]"),
+            [TokenSegment::Tag(b"a"), TokenSegment::Text("<code>[b]")]
+        );
+    }
+
+    #[test]
+    fn test_html_many_unclosed_tags() {
+        // Each unclosed tag would otherwise scan to the end of the input.
+        let content = "<a [x] ".repeat(40_000);
+        let syntax = Syntax::html();
+        let tags = Tokenizer::with_syntax(&content, &syntax)
+            .filter(|segment| matches!(segment, TokenSegment::Tag(_)))
+            .count();
+        assert_eq!(tags, 40_000);
+    }
+
+    #[test]
+    fn test_html_tokenize_keeps_syntax() {
+        let syntax = Syntax::html();
+        let tok = Tokenizer::with_syntax("<code>[a]</code>", &syntax);
+        assert_eq!(tok.tokenize(), [TokenSegment::Text("<code>[a]</code>")]);
+    }
+
+    #[test]
+    fn test_markdown_skips_code_and_links() {
+        let syntax = Syntax::markdown();
+        let segments: Vec<_> =
+            Tokenizer::with_syntax("`[a]` [b] [see](x) [c]\n```\n[d]\n```", &syntax).collect();
+        assert_eq!(
+            segments,
+            [
+                TokenSegment::Text("`[a]` "),
+                TokenSegment::Tag(b"b"),
+                TokenSegment::Text(" [see](x) "),
+                TokenSegment::Tag(b"c"),
+                TokenSegment::Text("\n```\n[d]\n```"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_delimiters() {
+        let syntax = Syntax {
+            delimiters: crate::Delimiters::HUGO,
+            ..Syntax::new()
+        };
+        let segments: Vec<_> =
+            Tokenizer::with_syntax("a {{< b x=\"1\" >}} [c] {{< d", &syntax).collect();
+        assert_eq!(
+            segments,
+            [
+                TokenSegment::Text("a "),
+                TokenSegment::Tag(b" b x=\"1\" "),
+                TokenSegment::Text(" [c] "),
+                TokenSegment::Text("{{< d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multibyte_delimiters() {
+        let syntax = Syntax {
+            delimiters: crate::Delimiters::new("«", "»"),
+            ..Syntax::new()
+        };
+        let segments: Vec<_> = Tokenizer::with_syntax("é«a»ü«/a»", &syntax).collect();
+        assert_eq!(
+            segments,
+            [
+                TokenSegment::Text("é"),
+                TokenSegment::Tag(b"a"),
+                TokenSegment::Text("ü"),
+                TokenSegment::Tag(b"/a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bbcode_code_is_verbatim() {
+        let syntax = Syntax::bbcode();
+        let segments: Vec<_> =
+            Tokenizer::with_syntax("[CODE=rust][b]x[/b][/Code][b]y", &syntax).collect();
+        assert_eq!(
+            segments,
+            [
+                TokenSegment::Tag(b"CODE=rust"),
+                TokenSegment::Text("[b]x[/b]"),
+                TokenSegment::Tag(b"/Code"),
+                TokenSegment::Tag(b"b"),
+                TokenSegment::Text("y"),
+            ]
+        );
+
+        // Empty and unclosed blocks.
+        let segments: Vec<_> = Tokenizer::with_syntax("[code][/code][code][b]", &syntax).collect();
+        assert_eq!(
+            segments,
+            [
+                TokenSegment::Tag(b"code"),
+                TokenSegment::Tag(b"/code"),
+                TokenSegment::Tag(b"code"),
+                TokenSegment::Tag(b"b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bbcode_many_unclosed_code_blocks() {
+        // Each unclosed block would otherwise search to the end of the input.
+        let content = format!("{}x", "[code]".repeat(100_000));
+        let syntax = Syntax::bbcode();
+        let segments: Vec<_> = Tokenizer::with_syntax(&content, &syntax).collect();
+        assert_eq!(segments.len(), 100_001);
+        assert_eq!(segments[99_999], TokenSegment::Tag(b"code"));
+        assert_eq!(segments[100_000], TokenSegment::Text("x"));
+    }
 }